@@ -0,0 +1,327 @@
+extern crate gif;
+extern crate gl;
+use gl::types::*;
+use std::fs::File;
+use std::os::raw::c_void;
+use std::path::Path;
+use std::time::Duration;
+
+use errors::TextureError;
+
+/* Browsers show frames with a delay of 0 or 10ms for 100ms, and GIFs are made to suit them */
+const MIN_FRAME_DELAY_CS: u16 = 2;
+const DEFAULT_FRAME_DELAY_CS: u16 = 10;
+
+/* Every frame of an animated GIF, decoded up front into the layers of a sampler2DArray. The
+ * texture is advanced with the presentation clock like a video channel, looping forever, and the
+ * layer to sample is the current frame. */
+pub struct AnimatedTexture {
+    id: GLuint,
+    width: u32,
+    height: u32,
+    /* When each frame ends, from the start of the loop; the last is the loop's length */
+    frame_ends: Vec<Duration>,
+    position: Duration,
+}
+
+impl AnimatedTexture {
+    pub fn from_gif(path: &Path) -> Result<AnimatedTexture, TextureError> {
+        let invalid = |reason: String| TextureError::Gif { path: path.to_path_buf(), reason };
+        let (width, height, layers, delays) = decode_gif(File::open(path)?).map_err(invalid)?;
+
+        let mut max_layers = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_ARRAY_TEXTURE_LAYERS, &mut max_layers);
+        }
+        if delays.len() > max_layers as usize {
+            return Err(invalid(format!(
+                "{} frames is more than the {} layers an array texture can have here",
+                delays.len(),
+                max_layers
+            )));
+        }
+
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, id);
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                gl::RGBA8 as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                delays.len() as GLsizei,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                layers.as_ptr() as *const c_void,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+
+        let mut end = Duration::from_secs(0);
+        let frame_ends = delays
+            .into_iter()
+            .map(|delay| {
+                end += delay;
+                end
+            })
+            .collect();
+
+        Ok(AnimatedTexture {
+            id,
+            width,
+            height,
+            frame_ends,
+            position: Duration::from_secs(0),
+        })
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_ends.len()
+    }
+
+    pub fn duration(&self) -> Duration {
+        *self.frame_ends.last().unwrap()
+    }
+
+    /* How far into the loop playback is */
+    pub fn position(&self) -> Duration {
+        self.position
+    }
+
+    /* The layer to sample */
+    pub fn current_frame(&self) -> usize {
+        self.frame_ends
+            .iter()
+            .position(|&end| self.position < end)
+            .unwrap_or(self.frame_ends.len() - 1)
+    }
+
+    pub fn advance(&mut self, dt: Duration) {
+        let nanos = (self.position + dt).as_nanos() % self.duration().as_nanos();
+        self.position = Duration::from_nanos(nanos as u64);
+    }
+
+    pub fn rewind(&mut self) {
+        self.position = Duration::from_secs(0);
+    }
+
+    pub fn bind_to_unit(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+        }
+    }
+}
+
+impl Drop for AnimatedTexture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+/* The width and height of the GIF, every frame as it is shown, composited over the frames before
+ * it as the GIF's disposal methods say, in RGBA bottom row first, and how long each is shown */
+fn decode_gif(file: File) -> Result<(u32, u32, Vec<u8>, Vec<Duration>), String> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options.read_info(file).map_err(|e| e.to_string())?;
+    let (width, height) = (decoder.width() as usize, decoder.height() as usize);
+    if width == 0 || height == 0 {
+        return Err("the image is empty".to_string());
+    }
+
+    let mut canvas = vec![0u8; width * height * 4];
+    let mut layers = Vec::new();
+    let mut delays = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().map_err(|e| e.to_string())? {
+        let previous = match frame.dispose {
+            gif::DisposalMethod::Previous => Some(canvas.clone()),
+            _ => None,
+        };
+
+        /* Frames may be smaller than the canvas, and even hang off its edge */
+        let (left, top) = (frame.left as usize, frame.top as usize);
+        let visible_width = (frame.width as usize).min(width.saturating_sub(left));
+        let visible_height = (frame.height as usize).min(height.saturating_sub(top));
+        for y in 0..visible_height {
+            for x in 0..visible_width {
+                let from = (y * frame.width as usize + x) * 4;
+                let pixel = &frame.buffer[from..from + 4];
+                /* Transparent pixels let the frames before show through */
+                if pixel[3] != 0 {
+                    let to = ((top + y) * width + left + x) * 4;
+                    canvas[to..to + 4].copy_from_slice(pixel);
+                }
+            }
+        }
+
+        for row in canvas.chunks(width * 4).rev() {
+            layers.extend_from_slice(row);
+        }
+        let delay = match frame.delay {
+            delay if delay < MIN_FRAME_DELAY_CS => DEFAULT_FRAME_DELAY_CS,
+            delay => delay,
+        };
+        delays.push(Duration::from_millis(u64::from(delay) * 10));
+
+        match frame.dispose {
+            gif::DisposalMethod::Background => {
+                for y in top..top + visible_height {
+                    let row = (y * width + left) * 4;
+                    for byte in &mut canvas[row..row + visible_width * 4] {
+                        *byte = 0;
+                    }
+                }
+            }
+            gif::DisposalMethod::Previous => canvas = previous.unwrap(),
+            gif::DisposalMethod::Keep | gif::DisposalMethod::Any => {}
+        }
+    }
+
+    if delays.is_empty() {
+        return Err("there are no frames".to_string());
+    }
+    Ok((width as u32, height as u32, layers, delays))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_gif, gif, AnimatedTexture};
+    use std::borrow::Cow;
+    use std::env;
+    use std::fs::{self, File};
+    use std::mem::ManuallyDrop;
+    use std::process;
+    use std::time::Duration;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    /* A 2x2 GIF: red all over, then blue in the bottom right corner, cleared again after it is
+     * shown, then a frame that is all transparent */
+    fn write_gif(name: &str) -> File {
+        let path = env::temp_dir().join(format!("slideshow-{}-{}.gif", process::id(), name));
+        {
+            let file = File::create(&path).unwrap();
+            let mut encoder = gif::Encoder::new(file, 2, 2, &[]).unwrap();
+            let frames = [
+                gif::Frame {
+                    width: 2,
+                    height: 2,
+                    delay: 5,
+                    palette: Some(vec![255, 0, 0]),
+                    buffer: Cow::Borrowed(&[0, 0, 0, 0]),
+                    ..gif::Frame::default()
+                },
+                gif::Frame {
+                    width: 1,
+                    height: 1,
+                    left: 1,
+                    top: 1,
+                    delay: 0,
+                    dispose: gif::DisposalMethod::Background,
+                    palette: Some(vec![0, 0, 255]),
+                    buffer: Cow::Borrowed(&[0]),
+                    ..gif::Frame::default()
+                },
+                gif::Frame {
+                    width: 2,
+                    height: 2,
+                    delay: 20,
+                    transparent: Some(0),
+                    palette: Some(vec![0, 0, 0]),
+                    buffer: Cow::Borrowed(&[0, 0, 0, 0]),
+                    ..gif::Frame::default()
+                },
+            ];
+            for frame in &frames {
+                encoder.write_frame(frame).unwrap();
+            }
+        }
+        let file = File::open(&path).unwrap();
+        fs::remove_file(&path).ok();
+        file
+    }
+
+    /* The pixel at `x`, `y` from the top left of `layer`, which is stored bottom row first */
+    fn pixel(layers: &[u8], layer: usize, x: usize, y: usize) -> [u8; 4] {
+        let offset = layer * 16 + ((1 - y) * 2 + x) * 4;
+        [layers[offset], layers[offset + 1], layers[offset + 2], layers[offset + 3]]
+    }
+
+    #[test]
+    fn decodes_frames_over_the_ones_before() {
+        let (width, height, layers, _) = decode_gif(write_gif("composite")).unwrap();
+        assert_eq!((width, height), (2, 2));
+        assert_eq!(layers.len(), 3 * 16);
+
+        assert_eq!(pixel(&layers, 0, 1, 1), RED);
+        assert_eq!(pixel(&layers, 1, 1, 1), BLUE);
+        assert_eq!(pixel(&layers, 1, 0, 0), RED);
+        /* Disposed to the background after it was shown, and the last frame is transparent */
+        assert_eq!(pixel(&layers, 2, 1, 1), CLEAR);
+        assert_eq!(pixel(&layers, 2, 0, 1), RED);
+    }
+
+    #[test]
+    fn decodes_delays_with_a_default_for_short_ones() {
+        let (_, _, _, delays) = decode_gif(write_gif("delays")).unwrap();
+        let expected: Vec<_> = [50, 100, 200].iter().map(|&ms| Duration::from_millis(ms)).collect();
+        assert_eq!(delays, expected);
+    }
+
+    /* There is no texture behind it, so it must never be dropped */
+    fn texture(frame_ends_ms: &[u64]) -> ManuallyDrop<AnimatedTexture> {
+        ManuallyDrop::new(AnimatedTexture {
+            id: 0,
+            width: 2,
+            height: 2,
+            frame_ends: frame_ends_ms.iter().map(|&ms| Duration::from_millis(ms)).collect(),
+            position: Duration::from_secs(0),
+        })
+    }
+
+    #[test]
+    fn current_frame_follows_position() {
+        let mut texture = texture(&[50, 150, 250]);
+        assert_eq!(texture.current_frame(), 0);
+        texture.advance(Duration::from_millis(50));
+        assert_eq!(texture.current_frame(), 1);
+        texture.advance(Duration::from_millis(149));
+        assert_eq!(texture.current_frame(), 2);
+    }
+
+    #[test]
+    fn advance_wraps_around_the_loop() {
+        let mut texture = texture(&[50, 150, 250]);
+        texture.advance(Duration::from_millis(260));
+        assert_eq!(texture.position(), Duration::from_millis(10));
+        assert_eq!(texture.current_frame(), 0);
+        texture.advance(Duration::from_millis(740));
+        assert_eq!(texture.position(), Duration::from_millis(0));
+        texture.rewind();
+        assert_eq!(texture.current_frame(), 0);
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use easing::{self, EasingFn};
+use shader::{ShaderProgram, UniformValue};
+
+/* A value a track reaches `time` seconds after its slide became current */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub time: f32,
+    pub value: UniformValue,
+}
+
+/* Drives one uniform through its keyframes, which are in time order and all of the same type.
+ * Before the first keyframe the uniform holds the first value, and after the last it holds the
+ * last. Booleans switch at each keyframe rather than blending. */
+#[derive(Debug, Clone)]
+pub struct AnimationTrack {
+    pub name: String,
+    pub keyframes: Vec<Keyframe>,
+    pub target_uniform: String,
+    /* Applied to the progress between each pair of keyframes */
+    pub easing: EasingFn,
+}
+
+impl AnimationTrack {
+    pub fn new(name: &str, target_uniform: &str, keyframes: Vec<Keyframe>) -> AnimationTrack {
+        assert!(!keyframes.is_empty(), "animation tracks need at least one keyframe");
+        AnimationTrack {
+            name: name.to_string(),
+            keyframes,
+            target_uniform: target_uniform.to_string(),
+            easing: easing::linear,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: EasingFn) -> AnimationTrack {
+        self.easing = easing;
+        self
+    }
+
+    pub fn value_at(&self, time: f32) -> UniformValue {
+        let next = match self.keyframes.iter().position(|k| k.time > time) {
+            Some(0) => return self.keyframes[0].value,
+            Some(next) => next,
+            None => return self.keyframes[self.keyframes.len() - 1].value,
+        };
+        let (from, to) = (self.keyframes[next - 1], self.keyframes[next]);
+        let t = (self.easing)((time - from.time) / (to.time - from.time));
+        interpolate(from.value, to.value, t)
+    }
+}
+
+/* Plays a slide's tracks from the moment it becomes current */
+#[derive(Debug, Clone, Default)]
+pub struct AnimationPlayer {
+    tracks: Vec<AnimationTrack>,
+    elapsed: Duration,
+}
+
+impl AnimationPlayer {
+    pub fn new(tracks: Vec<AnimationTrack>) -> AnimationPlayer {
+        AnimationPlayer { tracks, elapsed: Duration::from_secs(0) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    pub fn tracks(&self) -> &[AnimationTrack] {
+        &self.tracks
+    }
+
+    pub fn restart(&mut self) {
+        self.elapsed = Duration::from_secs(0);
+    }
+
+    pub fn advance(&mut self, dt: Duration) {
+        self.elapsed += dt;
+    }
+
+    /* Sets each track's uniform to its current value */
+    pub fn upload_to(&self, program: &ShaderProgram) {
+        let time = self.elapsed.as_secs() as f32 + self.elapsed.subsec_nanos() as f32 * 1e-9;
+        for track in &self.tracks {
+            program.set_uniform(&track.target_uniform, &track.value_at(time));
+        }
+    }
+}
+
+fn interpolate(from: UniformValue, to: UniformValue, t: f32) -> UniformValue {
+    fn lerp<const N: usize>(a: [f32; N], b: [f32; N], t: f32) -> [f32; N] {
+        let mut out = a;
+        for (out, &b) in out.iter_mut().zip(b.iter()) {
+            *out += (b - *out) * t;
+        }
+        out
+    }
+
+    match (from, to) {
+        (UniformValue::Float(a), UniformValue::Float(b)) => UniformValue::Float(lerp([a], [b], t)[0]),
+        (UniformValue::Vec2(a), UniformValue::Vec2(b)) => UniformValue::Vec2(lerp(a, b, t)),
+        (UniformValue::Vec3(a), UniformValue::Vec3(b)) => UniformValue::Vec3(lerp(a, b, t)),
+        (UniformValue::Vec4(a), UniformValue::Vec4(b)) => UniformValue::Vec4(lerp(a, b, t)),
+        /* Booleans, and mismatched types, which the deck loader rejects */
+        _ => if t < 1.0 { from } else { to },
+    }
+}
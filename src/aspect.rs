@@ -0,0 +1,76 @@
+use std::str::FromStr;
+
+/* How slides are fitted to a window whose shape differs from the deck's aspect ratio */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AspectMode {
+    /* Letterbox or pillarbox, whichever keeps the whole slide visible */
+    #[default]
+    Fit,
+    /* Fill the width, with bars above and below (or the top and bottom cropped) */
+    Letterbox,
+    /* Fill the height, with bars at the sides (or the sides cropped) */
+    Pillarbox,
+    /* Fill the window, ignoring the aspect ratio */
+    Stretch,
+    /* Fill the window, cropping whatever overhangs */
+    Crop,
+}
+
+impl FromStr for AspectMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AspectMode, String> {
+        match s {
+            "fit" => Ok(AspectMode::Fit),
+            "letterbox" => Ok(AspectMode::Letterbox),
+            "pillarbox" => Ok(AspectMode::Pillarbox),
+            "stretch" => Ok(AspectMode::Stretch),
+            "crop" => Ok(AspectMode::Crop),
+            _ => Err(format!(
+                "expected fit, letterbox, pillarbox, stretch or crop, not {}",
+                s
+            )),
+        }
+    }
+}
+
+pub const DEFAULT_ASPECT_RATIO: f32 = 16.0 / 9.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aspect {
+    pub mode: AspectMode,
+    /* Width over height */
+    pub ratio: f32,
+}
+
+impl Aspect {
+    pub fn new(mode: AspectMode, ratio: f32) -> Aspect {
+        Aspect { mode, ratio }
+    }
+
+    /* Where the slide goes in a window of the given size, as x, y, width and height in pixels.
+     * With Letterbox, Pillarbox and Crop this can extend past the edges of the window. */
+    pub fn rect(&self, width: i32, height: i32) -> [i32; 4] {
+        let window_ratio = width as f32 / height as f32;
+        let fill_width = match self.mode {
+            AspectMode::Stretch => return [0, 0, width, height],
+            AspectMode::Fit => window_ratio < self.ratio,
+            AspectMode::Crop => window_ratio >= self.ratio,
+            AspectMode::Letterbox => true,
+            AspectMode::Pillarbox => false,
+        };
+        let (w, h) = if fill_width {
+            (width, (width as f32 / self.ratio).round() as i32)
+        } else {
+            ((height as f32 * self.ratio).round() as i32, height)
+        };
+        [(width - w) / 2, (height - h) / 2, w.max(1), h.max(1)]
+    }
+}
+
+impl Default for Aspect {
+    fn default() -> Aspect {
+        Aspect::new(AspectMode::default(), DEFAULT_ASPECT_RATIO)
+    }
+}
@@ -0,0 +1,317 @@
+extern crate cpal;
+
+use self::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use self::cpal::{Sample, SampleFormat, StreamConfig};
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use errors::AudioError;
+use shader::ShaderProgram;
+
+/* Samples per analysis window; must be a power of two for the FFT */
+const WINDOW_SIZE: usize = 1024;
+
+/* Upper edges of the bass and mid bands in Hz, treble runs up to the Nyquist frequency */
+const BASS_MAX_HZ: f32 = 250.0;
+const MID_MAX_HZ: f32 = 4000.0;
+
+/* Written by the audio thread and read by the render loop, stored as f32 bits so neither side
+ * ever blocks */
+#[derive(Default)]
+struct Levels {
+    rms: AtomicU32,
+    bass: AtomicU32,
+    mid: AtomicU32,
+    treble: AtomicU32,
+}
+
+fn store(value: &AtomicU32, level: f32) {
+    value.store(level.to_bits(), Ordering::Relaxed);
+}
+
+fn load(value: &AtomicU32) -> f32 {
+    f32::from_bits(value.load(Ordering::Relaxed))
+}
+
+/* Analyses the default input device, exposing the loudness and bass/mid/treble energy of the
+ * most recent window as the iAudioRMS, iAudioBass, iAudioMid and iAudioTreble uniforms */
+pub struct AudioInput {
+    levels: Arc<Levels>,
+    _stream: cpal::Stream,
+}
+
+impl AudioInput {
+    pub fn new() -> Result<AudioInput, AudioError> {
+        let host = cpal::default_host();
+        let device = host.default_input_device().ok_or(AudioError::NoDevice)?;
+        let supported = device
+            .default_input_config()
+            .map_err(|e| AudioError::Config(e.to_string()))?;
+        let format = supported.sample_format();
+        let config: StreamConfig = supported.into();
+
+        let levels = Arc::new(Levels::default());
+        let analyser =
+            Analyser::new(config.channels as usize, config.sample_rate.0 as f32, levels.clone());
+        let stream = match format {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, analyser),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, analyser),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, analyser),
+        }?;
+        stream.play().map_err(|e| AudioError::Stream(e.to_string()))?;
+
+        Ok(AudioInput { levels, _stream: stream })
+    }
+
+    pub fn rms(&self) -> f32 {
+        load(&self.levels.rms)
+    }
+
+    pub fn bass(&self) -> f32 {
+        load(&self.levels.bass)
+    }
+
+    pub fn mid(&self) -> f32 {
+        load(&self.levels.mid)
+    }
+
+    pub fn treble(&self) -> f32 {
+        load(&self.levels.treble)
+    }
+
+    /* As with the ShaderToy uniforms, shaders that don't declare these are skipped silently */
+    pub fn upload_to(&self, program: &ShaderProgram) {
+        let uniforms = [
+            ("iAudioRMS", self.rms()),
+            ("iAudioBass", self.bass()),
+            ("iAudioMid", self.mid()),
+            ("iAudioTreble", self.treble()),
+        ];
+        for &(name, value) in &uniforms {
+            if program.has_uniform(name) {
+                program.set_uniform_f32(name, value);
+            }
+        }
+    }
+}
+
+/* Records the default input device to a 32-bit float WAV file. cpal cannot capture an output
+ * device (loopback is unsupported), so to record what the machine is playing, point the default
+ * input at a monitor of the output, e.g. with PulseAudio. */
+pub struct AudioRecording {
+    stream: cpal::Stream,
+    writer: JoinHandle<io::Result<()>>,
+}
+
+impl AudioRecording {
+    pub fn start(path: &Path) -> Result<AudioRecording, AudioError> {
+        let host = cpal::default_host();
+        let mut file = BufWriter::new(File::create(path).map_err(|e| AudioError::Stream(e.to_string()))?);
+        let (tx, rx) = channel::<Vec<f32>>();
+
+        let device = host.default_input_device().ok_or(AudioError::NoDevice)?;
+        let supported = device
+            .default_input_config()
+            .map_err(|e| AudioError::Config(e.to_string()))?;
+        let stream = build_recording_stream(&device, &supported, tx)?;
+
+        let (channels, sample_rate) = (supported.channels(), supported.sample_rate().0);
+        write_wav_header(&mut file, channels, sample_rate, 0)
+            .map_err(|e| AudioError::Stream(e.to_string()))?;
+        let writer = thread::spawn(move || {
+            let mut data_len = 0u32;
+            for samples in rx {
+                for sample in &samples {
+                    file.write_all(&sample.to_le_bytes())?;
+                }
+                data_len = data_len.saturating_add(samples.len() as u32 * 4);
+            }
+            /* Now that the length is known */
+            file.seek(SeekFrom::Start(0))?;
+            write_wav_header(&mut file, channels, sample_rate, data_len)?;
+            file.flush()
+        });
+
+        stream.play().map_err(|e| AudioError::Stream(e.to_string()))?;
+        Ok(AudioRecording { stream, writer })
+    }
+
+    /* Stops recording and waits for the file to be finished */
+    pub fn finish(self) -> Result<(), AudioError> {
+        /* The stream's callback holds the only sender, so this ends the writer's loop */
+        drop(self.stream);
+        match self.writer.join() {
+            Ok(result) => result.map_err(|e| AudioError::Stream(e.to_string())),
+            Err(_) => Err(AudioError::Stream("the audio writer thread panicked".to_string())),
+        }
+    }
+}
+
+fn build_recording_stream(
+    device: &cpal::Device,
+    supported: &cpal::SupportedStreamConfig,
+    tx: Sender<Vec<f32>>,
+) -> Result<cpal::Stream, AudioError> {
+    fn build<T: Sample>(device: &cpal::Device, config: &StreamConfig, tx: Sender<Vec<f32>>)
+        -> Result<cpal::Stream, AudioError> {
+        device
+            .build_input_stream(
+                config,
+                move |data: &[T], _: &cpal::InputCallbackInfo| {
+                    /* Only fails once recording has finished */
+                    tx.send(data.iter().map(|s| s.to_f32()).collect()).ok();
+                },
+                |e| println!("Warning: audio recording error: {}", e),
+            )
+            .map_err(|e| AudioError::Stream(e.to_string()))
+    }
+
+    let config: StreamConfig = supported.config();
+    match supported.sample_format() {
+        SampleFormat::F32 => build::<f32>(device, &config, tx),
+        SampleFormat::I16 => build::<i16>(device, &config, tx),
+        SampleFormat::U16 => build::<u16>(device, &config, tx),
+    }
+}
+
+/* A canonical header for IEEE float samples, with `data_len` bytes of them */
+fn write_wav_header<W: Write>(w: &mut W, channels: u16, sample_rate: u32, data_len: u32)
+    -> io::Result<()> {
+    let block_align = channels * 4;
+    w.write_all(b"RIFF")?;
+    w.write_all(&(36 + data_len).to_le_bytes())?;
+    w.write_all(b"WAVEfmt ")?;
+    w.write_all(&16u32.to_le_bytes())?;
+    /* WAVE_FORMAT_IEEE_FLOAT */
+    w.write_all(&3u16.to_le_bytes())?;
+    w.write_all(&channels.to_le_bytes())?;
+    w.write_all(&sample_rate.to_le_bytes())?;
+    w.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+    w.write_all(&block_align.to_le_bytes())?;
+    w.write_all(&32u16.to_le_bytes())?;
+    w.write_all(b"data")?;
+    w.write_all(&data_len.to_le_bytes())
+}
+
+fn build_stream<T: Sample>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    mut analyser: Analyser,
+) -> Result<cpal::Stream, AudioError> {
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| analyser.push(data),
+            |e| println!("Warning: audio input error: {}", e),
+        )
+        .map_err(|e| AudioError::Stream(e.to_string()))
+}
+
+struct Analyser {
+    channels: usize,
+    sample_rate: f32,
+    samples: Vec<f32>,
+    levels: Arc<Levels>,
+}
+
+impl Analyser {
+    fn new(channels: usize, sample_rate: f32, levels: Arc<Levels>) -> Analyser {
+        Analyser {
+            channels: channels.max(1),
+            sample_rate,
+            samples: Vec::with_capacity(WINDOW_SIZE),
+            levels,
+        }
+    }
+
+    /* Mixes interleaved frames down to mono and analyses each full window */
+    fn push<T: Sample>(&mut self, data: &[T]) {
+        for frame in data.chunks(self.channels) {
+            let sum: f32 = frame.iter().map(|s| s.to_f32()).sum();
+            self.samples.push(sum / frame.len() as f32);
+            if self.samples.len() == WINDOW_SIZE {
+                self.analyse();
+                self.samples.clear();
+            }
+        }
+    }
+
+    fn analyse(&self) {
+        let n = self.samples.len();
+        let rms = (self.samples.iter().map(|s| s * s).sum::<f32>() / n as f32).sqrt();
+
+        /* Hann window to reduce leakage between bands */
+        let mut re: Vec<f32> = self
+            .samples
+            .iter()
+            .enumerate()
+            .map(|(i, s)| s * 0.5 * (1.0 - (2.0 * PI * i as f32 / (n - 1) as f32).cos()))
+            .collect();
+        let mut im = vec![0.0; n];
+        fft(&mut re, &mut im);
+
+        let bin_hz = self.sample_rate / n as f32;
+        let mut bands = [(0.0, 0); 3];
+        for bin in 1..n / 2 {
+            let magnitude = (re[bin] * re[bin] + im[bin] * im[bin]).sqrt() * 2.0 / n as f32;
+            let frequency = bin as f32 * bin_hz;
+            let band = if frequency < BASS_MAX_HZ {
+                0
+            } else if frequency < MID_MAX_HZ {
+                1
+            } else {
+                2
+            };
+            bands[band].0 += magnitude;
+            bands[band].1 += 1;
+        }
+        let energy = |(sum, count): (f32, usize)| if count > 0 { sum / count as f32 } else { 0.0 };
+
+        store(&self.levels.rms, rms);
+        store(&self.levels.bass, energy(bands[0]));
+        store(&self.levels.mid, energy(bands[1]));
+        store(&self.levels.treble, energy(bands[2]));
+    }
+}
+
+/* In-place iterative radix-2 FFT; the length must be a power of two */
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_re, w_im) = ((angle * k as f32).cos(), (angle * k as f32).sin());
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
@@ -0,0 +1,119 @@
+extern crate gl;
+
+use errors::BackendError;
+use quad::FullscreenQuad;
+use shader::{ShaderProgram, ShaderSource, UniformValue};
+use slide::SlideManager;
+use texture::Texture2D;
+
+/* What drawing a slide needs from a graphics API, so slide logic can be written once and run on
+ * OpenGL now and Vulkan later. A frame is begin_frame, any number of draws, then end_frame.
+ * SlideManager is generic over this, holding each slide's program as a Shader. */
+pub trait RenderBackend {
+    type Shader;
+    type Texture;
+
+    fn compile_shader(&mut self, vertex: &ShaderSource, fragment: &ShaderSource)
+        -> Result<Self::Shader, BackendError>;
+
+    /* `rgba` has 4 bytes per pixel, bottom row first as GL expects */
+    fn create_texture(&mut self, width: u32, height: u32, rgba: &[u8])
+        -> Result<Self::Texture, BackendError>;
+
+    /* Starts drawing a width x height frame, cleared to black */
+    fn begin_frame(&mut self, width: u32, height: u32) -> Result<(), BackendError>;
+
+    /* Covers the frame with `shader`, sampling textures[i] as iChannel<i> */
+    fn draw_fullscreen_quad(&mut self, shader: &Self::Shader, textures: &[&Self::Texture])
+        -> Result<(), BackendError>;
+
+    /* Finishes the frame; presenting it is left to the window */
+    fn end_frame(&mut self) -> Result<(), BackendError>;
+
+    /* Sets a uniform for the shader's following draws. SlideManager calls this with a slide's
+     * deck uniforms whenever the slide becomes current. */
+    fn set_uniform(shader: &Self::Shader, name: &str, value: &UniformValue);
+
+    /* Draws slide `index` of `slides` inside a frame. Slides can carry channels and other
+     * inputs that only some backends know how to bind, so by default this draws the slide's
+     * program with no textures. */
+    fn draw_slide(&mut self, slides: &SlideManager<Self>, index: usize) -> Result<(), BackendError>
+        where Self: Sized {
+        self.draw_fullscreen_quad(&slides.slides()[index].program, &[])
+    }
+}
+
+/* The OpenGL renderer the rest of the slideshow uses, behind RenderBackend. It draws into
+ * whatever framebuffer is bound, so it needs a current context. */
+pub struct GlBackend {
+    quad: FullscreenQuad,
+}
+
+impl GlBackend {
+    pub fn new() -> GlBackend {
+        GlBackend { quad: FullscreenQuad::new() }
+    }
+}
+
+impl Default for GlBackend {
+    fn default() -> GlBackend {
+        GlBackend::new()
+    }
+}
+
+impl RenderBackend for GlBackend {
+    type Shader = ShaderProgram;
+    type Texture = Texture2D;
+
+    fn compile_shader(&mut self, vertex: &ShaderSource, fragment: &ShaderSource)
+        -> Result<ShaderProgram, BackendError> {
+        Ok(ShaderProgram::new(vertex.clone(), fragment.clone(), None, None, None)?)
+    }
+
+    fn create_texture(&mut self, width: u32, height: u32, rgba: &[u8])
+        -> Result<Texture2D, BackendError> {
+        Ok(Texture2D::from_rgba(width, height, Some(rgba)))
+    }
+
+    fn begin_frame(&mut self, width: u32, height: u32) -> Result<(), BackendError> {
+        unsafe {
+            gl::Viewport(0, 0, width as i32, height as i32);
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+        Ok(())
+    }
+
+    fn draw_fullscreen_quad(&mut self, shader: &ShaderProgram, textures: &[&Texture2D])
+        -> Result<(), BackendError> {
+        for (unit, texture) in textures.iter().enumerate() {
+            texture.bind_to_unit(unit as u32);
+            let name = format!("iChannel{}", unit);
+            if shader.has_uniform(&name) {
+                shader.set_uniform_i32(&name, unit as i32);
+            }
+        }
+        let quad = &self.quad;
+        shader.activate(|_| quad.draw());
+        Ok(())
+    }
+
+    fn end_frame(&mut self) -> Result<(), BackendError> {
+        unsafe {
+            gl::Flush();
+        }
+        Ok(())
+    }
+
+    fn set_uniform(shader: &ShaderProgram, name: &str, value: &UniformValue) {
+        shader.set_uniform(name, value);
+    }
+
+    /* Binds the slide's channels, animations and built-in inputs, letterboxes it and draws the
+     * error slide in place of a program that has never built, as the window does */
+    fn draw_slide(&mut self, slides: &SlideManager, index: usize) -> Result<(), BackendError> {
+        let quad = &self.quad;
+        slides.activate_slide(index, |_| quad.draw());
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use animated_texture::AnimatedTexture;
+use framebuffer::Framebuffer;
+use noise::NoiseKind;
+use shader::ShaderProgram;
+use texture::{Texture2D, TextureCubemap};
+use uploader::PendingTexture;
+#[cfg(feature = "video")]
+use video::VideoSlide;
+
+pub const CHANNEL_COUNT: usize = 4;
+
+pub enum TextureSource {
+    File(Texture2D),
+    /* The colour attachment of an offscreen render target */
+    Framebuffer(Rc<Framebuffer>),
+    #[cfg(feature = "video")]
+    Video(VideoSlide),
+    /* Tileable procedural noise from Texture2D::generate_noise */
+    Noise(Texture2D),
+    /* Sampled through a samplerCube rather than a sampler2D */
+    Cubemap(TextureCubemap),
+    /* Sampled through a sampler2DArray, at the layer in iChannelLayer */
+    Animated(AnimatedTexture),
+    /* A placeholder, until the texture from a BackgroundUploader arrives and takes its place as
+     * a File source */
+    Pending(Texture2D, PendingTexture),
+}
+
+impl TextureSource {
+    pub fn noise(kind: NoiseKind, size: u32, seed: u64) -> TextureSource {
+        TextureSource::Noise(Texture2D::generate_noise_of(kind, size, size, seed))
+    }
+
+    /* None for a cubemap or an animated texture, which are not 2D textures */
+    pub fn texture(&self) -> Option<&Texture2D> {
+        match *self {
+            TextureSource::File(ref texture)
+            | TextureSource::Noise(ref texture)
+            | TextureSource::Pending(ref texture, _) => Some(texture),
+            TextureSource::Framebuffer(ref framebuffer) => Some(framebuffer.color_texture()),
+            #[cfg(feature = "video")]
+            TextureSource::Video(ref video) => Some(&video.texture),
+            TextureSource::Cubemap(_) | TextureSource::Animated(_) => None,
+        }
+    }
+}
+
+/* Textures a slide samples as iChannel0 to iChannel3, following ShaderToy. Image and video slides
+ * bind their own picture to unit 0 after these, so it takes the place of channel 0. */
+#[derive(Default)]
+pub struct ChannelBinding {
+    channels: [Option<TextureSource>; CHANNEL_COUNT],
+}
+
+impl ChannelBinding {
+    pub fn new() -> ChannelBinding {
+        ChannelBinding::default()
+    }
+
+    pub fn set(&mut self, channel: usize, source: Option<TextureSource>) {
+        assert!(channel < CHANNEL_COUNT, "there are only {} channels", CHANNEL_COUNT);
+        self.channels[channel] = source;
+    }
+
+    pub fn get(&self, channel: usize) -> Option<&TextureSource> {
+        self.channels.get(channel).and_then(Option::as_ref)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.iter().all(Option::is_none)
+    }
+
+    /* Binds channel N to texture unit N and points the iChannelN sampler at it, along with its
+     * entry in iChannelResolution. Uniforms the program doesn't declare are skipped. */
+    pub fn bind_all(&self, program: &ShaderProgram) {
+        for (i, source) in self.channels.iter().enumerate() {
+            match *source {
+                Some(TextureSource::Cubemap(ref cubemap)) => bind_cubemap_channel(program, i, cubemap),
+                Some(TextureSource::Animated(ref animated)) => {
+                    bind_animated_channel(program, i, animated)
+                }
+                Some(ref source) => bind_channel(program, i, source.texture().unwrap()),
+                None => {}
+            }
+        }
+    }
+
+    /* Moves video and animated channels on with the presentation clock, and swaps in any
+     * pending textures that have arrived */
+    pub fn advance(&mut self, dt: Duration) {
+        for source in self.channels.iter_mut() {
+            let arrived = match *source {
+                Some(TextureSource::Pending(_, ref mut pending)) => pending.poll(),
+                Some(TextureSource::Animated(ref mut animated)) => {
+                    animated.advance(dt);
+                    None
+                }
+                _ => None,
+            };
+            if let Some(texture) = arrived {
+                *source = Some(TextureSource::File(texture));
+            }
+        }
+
+        #[cfg(feature = "video")]
+        {
+            for source in self.channels.iter_mut() {
+                if let Some(TextureSource::Video(ref mut video)) = *source {
+                    if let Err(e) = video.advance(dt) {
+                        println!("Warning: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/* Binds `texture` as iChannel`channel` of `program`, the same way ChannelBinding::bind_all binds
+ * each of its sources */
+pub fn bind_channel(program: &ShaderProgram, channel: usize, texture: &Texture2D) {
+    texture.bind_to_unit(channel as u32);
+
+    let sampler = format!("iChannel{}", channel);
+    if program.has_uniform(&sampler) {
+        program.set_uniform_sampler2d(&sampler, channel as u32);
+    }
+    let resolution = format!("iChannelResolution[{}]", channel);
+    if program.has_uniform(&resolution) {
+        program.set_uniform_vec3(
+            &resolution,
+            [texture.width() as f32, texture.height() as f32, 1.0],
+        );
+    }
+}
+
+/* As bind_channel, for a cubemap; iChannelResolution is the size of a face */
+pub fn bind_cubemap_channel(program: &ShaderProgram, channel: usize, cubemap: &TextureCubemap) {
+    cubemap.bind_to_unit(channel as u32);
+
+    let sampler = format!("iChannel{}", channel);
+    if program.has_uniform(&sampler) {
+        program.set_uniform_i32(&sampler, channel as i32);
+    }
+    let resolution = format!("iChannelResolution[{}]", channel);
+    if program.has_uniform(&resolution) {
+        program.set_uniform_vec3(&resolution, [cubemap.size() as f32, cubemap.size() as f32, 1.0]);
+    }
+}
+
+/* As bind_channel, for an animated texture. iChannelLayer[channel] is the frame to show, as the
+ * third coordinate of texture(), and iChannelTime[channel] how far into the loop it is, in
+ * seconds. */
+pub fn bind_animated_channel(program: &ShaderProgram, channel: usize, animated: &AnimatedTexture) {
+    animated.bind_to_unit(channel as u32);
+
+    let sampler = format!("iChannel{}", channel);
+    if program.has_uniform(&sampler) {
+        program.set_uniform_i32(&sampler, channel as i32);
+    }
+    let resolution = format!("iChannelResolution[{}]", channel);
+    if program.has_uniform(&resolution) {
+        program.set_uniform_vec3(
+            &resolution,
+            [animated.width() as f32, animated.height() as f32, 1.0],
+        );
+    }
+    let layer = format!("iChannelLayer[{}]", channel);
+    if program.has_uniform(&layer) {
+        program.set_uniform_f32(&layer, animated.current_frame() as f32);
+    }
+    let time = format!("iChannelTime[{}]", channel);
+    if program.has_uniform(&time) {
+        program.set_uniform_f32(&time, animated.position().as_secs_f32());
+    }
+}
@@ -0,0 +1,721 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use aspect::AspectMode;
+use config::Config;
+use deck::{Deck, SlideSelection};
+use pdf::{PageSize, PdfExport};
+use present::PresentMode;
+use window::GlProfile;
+
+const DEFAULT_LEADER_PORT: u16 = 9000;
+
+#[derive(Debug)]
+pub struct Options {
+    pub deck: PathBuf,
+    pub fullscreen: bool,
+    pub width: u32,
+    pub height: u32,
+    /* Zero-based, converted from the one-based value given on the command line */
+    pub start_slide: usize,
+    /* Start on the slide with this name in the deck instead, from --start-at or goto */
+    pub start_at: Option<String>,
+    /* Present slides marked skip in the deck */
+    pub include_skipped: bool,
+    /* Zero-based; present only this slide of the deck */
+    pub only_slide: Option<usize>,
+    /* Present the slides in a random order, from this seed if given */
+    pub shuffle: bool,
+    pub seed: Option<u64>,
+    pub present_mode: PresentMode,
+    /* Samples per pixel for the window and offscreen framebuffers; 1 disables MSAA */
+    pub msaa: u32,
+    /* Overrides the deck's aspect mode when set */
+    pub aspect: Option<AspectMode>,
+    pub notes: bool,
+    /* Present full screen with a separate window for the speaker */
+    pub presenter_window: bool,
+    /* Zero-based monitors to spread the slide across, one column each, left to right */
+    pub displays: Vec<usize>,
+    /* Render every slide to a PNG in this directory and exit instead of presenting */
+    pub export: Option<PathBuf>,
+    /* Time this many frames of each slide, print the results as JSON and exit */
+    pub bench: Option<u32>,
+    /* Render --export or --bench without a window, through EGL */
+    pub headless: bool,
+    pub auto_advance: Option<Duration>,
+    /* Record how long each slide is shown for */
+    pub rehearsal: bool,
+    /* Auto advance each slide after its rehearsed time */
+    pub timed: bool,
+    /* Grade every slide through this .cube LUT */
+    pub lut: Option<PathBuf>,
+    /* Append a JSON line to this file for each slide change, reload and so on */
+    pub event_log: Option<PathBuf>,
+    /* Hide the mouse cursor after it has been still this long, overriding the deck */
+    pub cursor_idle: Option<Duration>,
+    pub gl_debug: bool,
+    pub gl_profile: GlProfile,
+    pub audio: bool,
+    /* Read the deck's MIDI controls from the first input port whose name contains this */
+    pub midi_device: Option<String>,
+    /* Accept remote control connections on this port */
+    pub remote_port: Option<u16>,
+    /* Lead other instances from this port; they connect like remote controls */
+    pub leader: Option<u16>,
+    /* Mirror the slide changes of the leader at this host:port */
+    pub follow: Option<String>,
+    /* Set by the serve subcommand: export the slides and serve them over HTTP on this port */
+    pub serve: Option<u16>,
+    /* Set by the export-pdf subcommand */
+    pub export_pdf: Option<PdfExport>,
+    /* Set by the export-gif subcommand */
+    pub export_gif: Option<AnimationExport>,
+    /* Set by the export-video subcommand */
+    pub export_video: Option<AnimationExport>,
+    /* Set by the record subcommand */
+    pub record: Option<Recording>,
+    /* Set by the preview-transition subcommand */
+    pub preview_transition: Option<TransitionPreview>,
+    /* Load the deck's shaders from SPIR-V that compile-shaders wrote here, where it matches */
+    pub spirv_cache: Option<PathBuf>,
+    /* Set by the compile-shaders subcommand: write the deck's shaders as SPIR-V here and exit */
+    pub compile_shaders: Option<PathBuf>,
+    /* Set by the info subcommand: print the deck's [metadata] and exit */
+    pub info: bool,
+    /* Set by the config subcommand: print the merged config and exit */
+    pub dump_config: bool,
+}
+
+/* Present as usual while writing what the audience sees, and hears, to a video */
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub output: PathBuf,
+    pub fps: u32,
+}
+
+/* Play the deck's transition between two slides, over and over while it is worked on */
+#[derive(Debug, Clone)]
+pub struct TransitionPreview {
+    /* Zero-based, like start_slide */
+    pub from: usize,
+    pub to: usize,
+    /* Start again on its own once finished, rather than waiting for Space */
+    pub looping: bool,
+}
+
+/* One slide rendered for a while at a fixed frame rate, for the animated export subcommands */
+#[derive(Debug, Clone)]
+pub struct AnimationExport {
+    /* Zero-based, like start_slide */
+    pub slide: usize,
+    pub duration: Duration,
+    pub fps: u32,
+    pub output: PathBuf,
+}
+
+impl Options {
+    /* `config` supplies the defaults for flags the user config file can set */
+    pub fn from_args(config: &Config) -> Result<Options, String> {
+        let matches = App::new("slideshow")
+            .about("Presents a deck of shader slides")
+            .arg(Arg::with_name("deck")
+                 .long("deck")
+                 .global(true)
+                 .takes_value(true)
+                 .value_name("PATH")
+                 .default_value("deck.toml")
+                 .help("Deck manifest to present"))
+            .arg(Arg::with_name("fullscreen")
+                 .long("fullscreen")
+                 .help("Start in fullscreen mode"))
+            .arg(Arg::with_name("width")
+                 .long("width")
+                 .global(true)
+                 .takes_value(true)
+                 .help("Window width in pixels"))
+            .arg(Arg::with_name("height")
+                 .long("height")
+                 .global(true)
+                 .takes_value(true)
+                 .help("Window height in pixels"))
+            .arg(Arg::with_name("start-slide")
+                 .long("start-slide")
+                 .takes_value(true)
+                 .value_name("N")
+                 .help("Slide to start on, counting from 1"))
+            .arg(Arg::with_name("start-at")
+                 .long("start-at")
+                 .takes_value(true)
+                 .value_name("NAME")
+                 .conflicts_with("start-slide")
+                 .help("Slide to start on, by its name in the deck"))
+            .arg(Arg::with_name("include-skipped")
+                 .long("include-skipped")
+                 .global(true)
+                 .help("Present slides marked skip in the deck as well"))
+            .arg(Arg::with_name("only-slide")
+                 .long("only-slide")
+                 .global(true)
+                 .takes_value(true)
+                 .value_name("N")
+                 .conflicts_with_all(&["include-skipped", "start-slide", "start-at"])
+                 .help("Present only slide N of the deck, counting from 1, even if it is skipped"))
+            .arg(Arg::with_name("shuffle")
+                 .long("shuffle")
+                 .help("Present the slides in a random order, for practice"))
+            .arg(Arg::with_name("seed")
+                 .long("seed")
+                 .takes_value(true)
+                 .value_name("SEED")
+                 .requires("shuffle")
+                 .help("Shuffle the same way every time with this seed"))
+            .arg(Arg::with_name("vsync")
+                 .long("vsync")
+                 .overrides_with_all(&["no-vsync", "present-mode"])
+                 .help("Synchronise buffer swaps with the display, like --present-mode vsync"))
+            .arg(Arg::with_name("no-vsync")
+                 .long("no-vsync")
+                 .overrides_with_all(&["vsync", "present-mode"])
+                 .help("Swap buffers as fast as possible, like --present-mode immediate"))
+            .arg(Arg::with_name("present-mode")
+                 .long("present-mode")
+                 .takes_value(true)
+                 .value_name("MODE")
+                 .overrides_with_all(&["vsync", "no-vsync"])
+                 .help("vsync (default), immediate, or a frame rate to cap at, e.g. 30"))
+            .arg(Arg::with_name("msaa")
+                 .long("msaa")
+                 .global(true)
+                 .takes_value(true)
+                 .value_name("SAMPLES")
+                 .possible_values(&["1", "2", "4", "8"])
+                 .help("Antialias with this many samples per pixel (default 1, off)"))
+            .arg(Arg::with_name("aspect")
+                 .long("aspect")
+                 .takes_value(true)
+                 .value_name("MODE")
+                 .possible_values(&["fit", "letterbox", "pillarbox", "stretch", "crop"])
+                 .help("How to fit slides to the window, overriding the deck"))
+            .arg(Arg::with_name("notes")
+                 .long("notes")
+                 .help("Open a second window showing the speaker notes"))
+            .arg(Arg::with_name("presenter-window")
+                 .long("presenter-window")
+                 .conflicts_with("notes")
+                 .help("Present full screen, with the current and next slides, notes and timer \
+                        in a second window"))
+            .arg(Arg::with_name("displays")
+                 .long("displays")
+                 .takes_value(true)
+                 .value_name("MONITORS")
+                 .help("Also spread the slide across these monitors, counting from 1, one \
+                        column each from left to right, e.g. 2,3"))
+            .arg(Arg::with_name("export")
+                 .long("export")
+                 .takes_value(true)
+                 .value_name("DIR")
+                 .help("Save each slide as a PNG in DIR and exit"))
+            .arg(Arg::with_name("bench")
+                 .long("bench")
+                 .takes_value(true)
+                 .value_name("FRAMES")
+                 .conflicts_with("export")
+                 .help("Time FRAMES frames of each slide, print the results as JSON and exit"))
+            .arg(Arg::with_name("headless")
+                 .long("headless")
+                 .help("Run --export or --bench without a window or display server (Linux only)"))
+            .arg(Arg::with_name("auto-advance")
+                 .long("auto-advance")
+                 .takes_value(true)
+                 .value_name("SECS")
+                 .help("Move to the next slide after SECS, overriding the deck's durations"))
+            .arg(Arg::with_name("rehearsal")
+                 .long("rehearsal")
+                 .conflicts_with_all(&["timed", "auto-advance"])
+                 .help("Record the time spent on each slide to the deck's .timings.toml on exit"))
+            .arg(Arg::with_name("timed")
+                 .long("timed")
+                 .conflicts_with("auto-advance")
+                 .help("Advance each slide after the time recorded for it by --rehearsal"))
+            .arg(Arg::with_name("lut")
+                 .long("lut")
+                 .takes_value(true)
+                 .value_name("PATH")
+                 .help("Colour grade every slide through a 3D LUT in .cube format, e.g. for the \
+                        venue's lighting"))
+            .arg(Arg::with_name("event-log")
+                 .long("event-log")
+                 .takes_value(true)
+                 .value_name("PATH")
+                 .help("Write slide changes, reloads and so on to PATH as JSON Lines"))
+            .arg(Arg::with_name("cursor-idle")
+                 .long("cursor-idle")
+                 .takes_value(true)
+                 .value_name("SECS")
+                 .help("Hide the mouse cursor after SECS without movement (default 3)"))
+            .arg(Arg::with_name("spirv-cache")
+                 .long("spirv-cache")
+                 .takes_value(true)
+                 .value_name("DIR")
+                 .help("Load shaders from the SPIR-V written to DIR by compile-shaders, compiling \
+                        the GLSL of any that have changed since"))
+            .arg(Arg::with_name("gl-debug")
+                 .long("gl-debug")
+                 .help("Request a debug context and report GL messages synchronously"))
+            .arg(Arg::with_name("gles")
+                 .long("gles")
+                 .conflicts_with("headless")
+                 .help("Use an OpenGL ES 3.0 context, rewriting shaders to GLSL ES, e.g. on a \
+                        Raspberry Pi"))
+            .arg(Arg::with_name("remote-port")
+                 .long("remote-port")
+                 .takes_value(true)
+                 .value_name("PORT")
+                 .help("Accept WebSocket remote control connections on PORT"))
+            .arg(Arg::with_name("leader")
+                 .long("leader")
+                 .conflicts_with("remote-port")
+                 .help("Let other instances follow this one's slides; remote controls can connect \
+                        too"))
+            .arg(Arg::with_name("port")
+                 .long("port")
+                 .takes_value(true)
+                 .value_name("PORT")
+                 .requires("leader")
+                 .help("Port for --leader to listen on (default 9000)"))
+            .arg(Arg::with_name("follow")
+                 .long("follow")
+                 .takes_value(true)
+                 .value_name("ADDR")
+                 .conflicts_with("leader")
+                 .help("Follow the slides of the instance started with --leader at ADDR, e.g. \
+                        192.168.1.10:9000"))
+            .arg(Arg::with_name("audio")
+                 .long("audio")
+                 .help("Drive the iAudio* uniforms from the default microphone"))
+            .arg(Arg::with_name("midi-device")
+                 .long("midi-device")
+                 .takes_value(true)
+                 .value_name("NAME")
+                 .help("MIDI input port for the deck's [midi] controls (default: the first)"))
+            .subcommand(SubCommand::with_name("serve")
+                 .about("Export the slides and serve them as a web page, re-exporting when the \
+                         deck changes")
+                 .arg(Arg::with_name("port")
+                      .long("port")
+                      .takes_value(true)
+                      .value_name("PORT")
+                      .default_value("8080")
+                      .help("Port to listen on")))
+            .subcommand(SubCommand::with_name("export-pdf")
+                 .about("Save every slide as a page of a PDF and exit")
+                 .arg(Arg::with_name("output")
+                      .required(true)
+                      .value_name("OUTPUT")
+                      .help("PDF file to write"))
+                 .arg(Arg::with_name("page-size")
+                      .long("page-size")
+                      .takes_value(true)
+                      .value_name("SIZE")
+                      .possible_values(&["a4", "16:9"])
+                      .default_value("16:9")
+                      .help("A4 portrait pages, or 16:9 pages the shape of a slide"))
+                 .arg(Arg::with_name("notes")
+                      .long("notes")
+                      .help("Print each slide's speaker notes under it")))
+            .subcommand(animation_args(SubCommand::with_name("export-gif")
+                 .about("Save a looping animated GIF of one slide and exit"), "25"))
+            .subcommand(animation_args(SubCommand::with_name("export-video")
+                 .about("Save an MP4 or WebM video of one slide with ffmpeg and exit"), "60"))
+            .subcommand(SubCommand::with_name("record")
+                 .about("Present as usual, recording the window and system audio to a video \
+                         with ffmpeg")
+                 .arg(Arg::with_name("output")
+                      .long("output")
+                      .takes_value(true)
+                      .required(true)
+                      .value_name("OUTPUT")
+                      .help("Video file to write, e.g. recording.mp4"))
+                 .arg(Arg::with_name("fps")
+                      .long("fps")
+                      .takes_value(true)
+                      .value_name("FPS")
+                      .default_value("30")
+                      .help("Frames per second of the recording")))
+            .subcommand(SubCommand::with_name("preview-transition")
+                 .about("Play only the transition between two slides, reloading it when its \
+                         shaders change")
+                 .arg(Arg::with_name("from")
+                      .long("from")
+                      .takes_value(true)
+                      .required(true)
+                      .value_name("N")
+                      .help("Slide to transition from, counting from 1"))
+                 .arg(Arg::with_name("to")
+                      .long("to")
+                      .takes_value(true)
+                      .required(true)
+                      .value_name("N")
+                      .help("Slide to transition to, counting from 1"))
+                 .arg(Arg::with_name("loop")
+                      .long("loop")
+                      .help("Play the transition again as soon as it finishes, instead of on Space")))
+            .subcommand(SubCommand::with_name("goto")
+                 .about("Present from the slide with this name in the deck, like --start-at")
+                 .arg(Arg::with_name("name")
+                      .required(true)
+                      .value_name("NAME")
+                      .help("Name of the slide")))
+            .subcommand(SubCommand::with_name("compile-shaders")
+                 .about("Compile every shader in the deck to SPIR-V ahead of time and exit, for \
+                         --spirv-cache to load")
+                 .arg(Arg::with_name("output-dir")
+                      .long("output-dir")
+                      .takes_value(true)
+                      .required(true)
+                      .value_name("DIR")
+                      .help("Directory to write the .spv files to, created if needed")))
+            .subcommand(SubCommand::with_name("info")
+                 .about("Print the deck's title, author, version and date"))
+            .subcommand(SubCommand::with_name("config")
+                 .about("Show the settings from the user config file, the deck and the command line")
+                 .arg(Arg::with_name("dump")
+                      .long("dump")
+                      .required(true)
+                      .help("Print the merged settings as TOML")))
+            .get_matches();
+
+        let width = parse(&matches, "width")?.unwrap_or(config.width);
+        let height = parse(&matches, "height")?.unwrap_or(config.height);
+        if width == 0 || height == 0 {
+            return Err("Window width and height must be greater than zero".to_string());
+        }
+
+        let start_slide = match parse::<usize>(&matches, "start-slide")? {
+            Some(0) => return Err("--start-slide counts from 1".to_string()),
+            Some(n) => n - 1,
+            None => 0,
+        };
+
+        let start_at = match matches.subcommand_matches("goto") {
+            Some(goto) if matches.is_present("start-slide") || matches.is_present("start-at") => {
+                return Err(format!(
+                    "goto {} cannot be combined with --start-slide or --start-at",
+                    goto.value_of("name").unwrap()
+                ))
+            }
+            Some(goto) => goto.value_of("name").map(str::to_string),
+            None => matches.value_of("start-at").map(str::to_string),
+        };
+
+        let displays = match matches.value_of("displays") {
+            Some(monitors) => monitors
+                .split(',')
+                .map(|monitor| match monitor.trim().parse::<usize>() {
+                    Ok(0) => Err("--displays counts monitors from 1".to_string()),
+                    Ok(n) => Ok(n - 1),
+                    Err(_) => Err(format!("Invalid value for --displays: {}", monitors)),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+
+        let only_slide = match parse::<usize>(&matches, "only-slide")? {
+            Some(0) => return Err("--only-slide counts from 1".to_string()),
+            Some(n) => Some(n - 1),
+            None => None,
+        };
+
+        let auto_advance = match parse::<f32>(&matches, "auto-advance")? {
+            Some(secs) if secs <= 0.0 => {
+                return Err("--auto-advance must be greater than zero".to_string())
+            }
+            Some(secs) => Some(Duration::from_millis((secs * 1000.0) as u64)),
+            None => None,
+        };
+
+        let cursor_idle = match parse::<f32>(&matches, "cursor-idle")? {
+            Some(secs) if secs <= 0.0 => {
+                return Err("--cursor-idle must be greater than zero".to_string())
+            }
+            Some(secs) => Some(Duration::from_millis((secs * 1000.0) as u64)),
+            None => None,
+        };
+
+        let present_mode = if matches.is_present("no-vsync") {
+            PresentMode::Immediate
+        } else if matches.is_present("vsync") {
+            PresentMode::Vsync
+        } else {
+            match matches.value_of("present-mode") {
+                Some(mode) => mode.parse().map_err(|e| format!("Invalid --present-mode: {}", e))?,
+                None => config.present_mode,
+            }
+        };
+
+        let bench = match parse::<u32>(&matches, "bench")? {
+            Some(0) => return Err("--bench needs at least one frame".to_string()),
+            frames => frames,
+        };
+
+        let headless = matches.is_present("headless");
+        if headless && bench.is_none() && !matches.is_present("export") {
+            return Err("--headless needs --export or --bench".to_string());
+        }
+
+        let leader = if matches.is_present("leader") {
+            Some(parse(&matches, "port")?.unwrap_or(DEFAULT_LEADER_PORT))
+        } else {
+            None
+        };
+
+        let serve = match matches.subcommand_matches("serve") {
+            Some(serve) => parse(serve, "port")?,
+            None => None,
+        };
+
+        let export_pdf = match matches.subcommand_matches("export-pdf") {
+            Some(pdf) => Some(PdfExport {
+                output: PathBuf::from(pdf.value_of("output").unwrap()),
+                page_size: parse::<PageSize>(pdf, "page-size")?.unwrap_or_default(),
+                notes: pdf.is_present("notes"),
+            }),
+            None => None,
+        };
+
+        let export_gif = match matches.subcommand_matches("export-gif") {
+            Some(gif) => Some(AnimationExport::from_matches(gif)?),
+            None => None,
+        };
+
+        let export_video = match matches.subcommand_matches("export-video") {
+            Some(video) => Some(AnimationExport::from_matches(video)?),
+            None => None,
+        };
+
+        let record = match matches.subcommand_matches("record") {
+            Some(record) => Some(Recording {
+                output: PathBuf::from(record.value_of("output").unwrap()),
+                fps: match parse::<u32>(record, "fps")? {
+                    Some(fps) if fps > 0 => fps,
+                    _ => return Err("--fps must be greater than zero".to_string()),
+                },
+            }),
+            None => None,
+        };
+
+        let preview_transition = match matches.subcommand_matches("preview-transition") {
+            Some(preview) => Some(TransitionPreview::from_matches(preview)?),
+            None => None,
+        };
+
+        Ok(Options {
+            deck: PathBuf::from(matches.value_of("deck").unwrap()),
+            fullscreen: matches.is_present("fullscreen"),
+            width,
+            height,
+            start_slide,
+            start_at,
+            include_skipped: matches.is_present("include-skipped"),
+            only_slide,
+            shuffle: matches.is_present("shuffle"),
+            seed: parse(&matches, "seed")?,
+            present_mode,
+            msaa: parse(&matches, "msaa")?.unwrap_or(1),
+            aspect: parse(&matches, "aspect")?,
+            notes: matches.is_present("notes"),
+            presenter_window: matches.is_present("presenter-window"),
+            displays,
+            export: matches.value_of("export").map(PathBuf::from),
+            bench,
+            headless,
+            auto_advance,
+            rehearsal: matches.is_present("rehearsal"),
+            timed: matches.is_present("timed"),
+            lut: matches.value_of("lut").map(PathBuf::from),
+            event_log: matches.value_of("event-log").map(PathBuf::from),
+            cursor_idle,
+            gl_debug: matches.is_present("gl-debug"),
+            gl_profile: if matches.is_present("gles") { GlProfile::Es3 } else { GlProfile::Core },
+            audio: matches.is_present("audio"),
+            midi_device: matches.value_of("midi-device").map(str::to_string),
+            remote_port: parse(&matches, "remote-port")?,
+            leader,
+            follow: matches.value_of("follow").map(str::to_string),
+            serve,
+            export_pdf,
+            export_gif,
+            export_video,
+            record,
+            preview_transition,
+            spirv_cache: matches.value_of("spirv-cache").map(PathBuf::from),
+            compile_shaders: matches
+                .subcommand_matches("compile-shaders")
+                .map(|compile| PathBuf::from(compile.value_of("output-dir").unwrap())),
+            info: matches.subcommand_matches("info").is_some(),
+            dump_config: matches.subcommand_matches("config").is_some(),
+        })
+    }
+
+    pub fn slide_selection(&self) -> SlideSelection {
+        match self.only_slide {
+            Some(index) => SlideSelection::Only(index),
+            None if self.include_skipped => SlideSelection::All,
+            None => SlideSelection::Presented,
+        }
+    }
+
+    /* Checks that need the deck, but not a GL context */
+    pub fn validate(&self, deck: &Deck) -> Result<(), String> {
+        if self.audio && !cfg!(feature = "audio") {
+            return Err("--audio needs a build with the audio feature enabled".to_string());
+        }
+        if (self.midi_device.is_some() || deck.config.midi.is_some()) && !cfg!(feature = "midi") {
+            return Err("MIDI controls need a build with the midi feature enabled".to_string());
+        }
+        if self.midi_device.is_some() && deck.config.midi.is_none() {
+            return Err("--midi-device needs a [midi] section in the deck".to_string());
+        }
+        if self.remote_port.is_some() && !cfg!(feature = "remote") {
+            return Err("--remote-port needs a build with the remote feature enabled".to_string());
+        }
+        if (self.leader.is_some() || self.follow.is_some()) && !cfg!(feature = "remote") {
+            return Err("--leader and --follow need a build with the remote feature enabled".to_string());
+        }
+        if self.compile_shaders.is_some() && !cfg!(feature = "spirv") {
+            return Err("compile-shaders needs a build with the spirv feature enabled".to_string());
+        }
+
+        if let Some(only) = self.only_slide {
+            if only >= deck.config.slides.len() {
+                return Err(format!(
+                    "--only-slide {} is out of range, the deck has {} slides",
+                    only + 1,
+                    deck.config.slides.len()
+                ));
+            }
+        }
+        /* Slides are numbered as they are presented, after any skipped ones are left out */
+        let count = deck.selected_slides(self.slide_selection()).len();
+        if count == 0 {
+            return Err("Every slide in the deck is skipped; try --include-skipped".to_string());
+        }
+        if let Some(animation) = self.export_gif.as_ref().or(self.export_video.as_ref()) {
+            if animation.slide >= count {
+                return Err(format!(
+                    "--slide {} is out of range, the deck has {} slides",
+                    animation.slide + 1,
+                    count
+                ));
+            }
+        }
+        if let Some(ref preview) = self.preview_transition {
+            if deck.config.transition.is_none() {
+                return Err("The deck has no [transition] to preview".to_string());
+            }
+            for &(flag, slide) in &[("--from", preview.from), ("--to", preview.to)] {
+                if slide >= count {
+                    return Err(format!(
+                        "{} {} is out of range, the deck has {} slides",
+                        flag,
+                        slide + 1,
+                        count
+                    ));
+                }
+            }
+        }
+        if let Some(ref name) = self.start_at {
+            if deck.slide_named(name, self.slide_selection()).is_none() {
+                return Err(format!("There is no presented slide called {}", name));
+            }
+        }
+        if self.start_slide >= count {
+            return Err(format!(
+                "--start-slide {} is out of range, the deck has {} slides",
+                self.start_slide + 1,
+                count
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl AnimationExport {
+    fn from_matches(matches: &ArgMatches) -> Result<AnimationExport, String> {
+        let slide = match parse::<usize>(matches, "slide")? {
+            Some(0) => return Err("--slide counts from 1".to_string()),
+            Some(n) => n - 1,
+            None => 0,
+        };
+        let duration = match parse::<f32>(matches, "duration")? {
+            Some(secs) if secs > 0.0 => Duration::from_millis((secs * 1000.0) as u64),
+            _ => return Err("--duration must be greater than zero".to_string()),
+        };
+        let fps = match parse::<u32>(matches, "fps")? {
+            Some(fps) if fps > 0 => fps,
+            _ => return Err("--fps must be greater than zero".to_string()),
+        };
+        Ok(AnimationExport {
+            slide,
+            duration,
+            fps,
+            output: PathBuf::from(matches.value_of("output").unwrap()),
+        })
+    }
+}
+
+impl TransitionPreview {
+    fn from_matches(matches: &ArgMatches) -> Result<TransitionPreview, String> {
+        let slide = |name: &str| match parse::<usize>(matches, name)? {
+            Some(0) => Err(format!("--{} counts from 1", name)),
+            Some(n) => Ok(n - 1),
+            None => unreachable!("clap requires --{}", name),
+        };
+        Ok(TransitionPreview {
+            from: slide("from")?,
+            to: slide("to")?,
+            looping: matches.is_present("loop"),
+        })
+    }
+}
+
+/* The arguments shared by the animated export subcommands */
+fn animation_args<'a, 'b>(command: App<'a, 'b>, default_fps: &'a str) -> App<'a, 'b> {
+    command
+        .arg(Arg::with_name("output")
+             .required(true)
+             .value_name("OUTPUT")
+             .help("File to write"))
+        .arg(Arg::with_name("slide")
+             .long("slide")
+             .takes_value(true)
+             .value_name("N")
+             .default_value("1")
+             .help("Slide to render, counting from 1"))
+        .arg(Arg::with_name("duration")
+             .long("duration")
+             .takes_value(true)
+             .value_name("SECS")
+             .default_value("5")
+             .help("Length of the animation"))
+        .arg(Arg::with_name("fps")
+             .long("fps")
+             .takes_value(true)
+             .value_name("FPS")
+             .default_value(default_fps)
+             .help("Frames per second"))
+}
+
+fn parse<T: FromStr>(matches: &ArgMatches, name: &str) -> Result<Option<T>, String> {
+    match matches.value_of(name) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid value for --{}: {}", name, value)),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,171 @@
+extern crate gl;
+extern crate syntect;
+
+use gl::types::*;
+use std::cell::Cell;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use self::syntect::easy::HighlightLines;
+use self::syntect::highlighting::{Color, ThemeSet};
+use self::syntect::parsing::SyntaxSet;
+
+use errors::{CodeError, ShaderError};
+use quad::{PASSTHROUGH_VERTEX_SHADER, SOLID_COLOR_FRAGMENT_SHADER};
+use shader::{ShaderProgram, ShaderSource};
+use text::TextRenderer;
+
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+pub const DEFAULT_FONT_SIZE: f32 = 24.0;
+
+/* Gap between the code and the edges of the viewport, in pixels */
+const MARGIN: f32 = 40.0;
+const TAB: &str = "    ";
+
+/* Listings too long for the viewport pause at the top and bottom, and scroll in between */
+const SCROLL_PAUSE_SECS: f32 = 3.0;
+const SCROLL_LINES_PER_SEC: f32 = 2.0;
+
+/* A run of text in a single colour */
+type Span = ([f32; 4], String);
+
+/* A slide showing a syntax highlighted source file. The file is highlighted once when the slide
+ * is loaded and drawn with its own, preferably monospace, font on top of the theme's background
+ * colour. */
+pub struct CodeSlide {
+    lines: Vec<Vec<Span>>,
+    background: [f32; 4],
+    text: TextRenderer,
+    size: f32,
+    elapsed: Cell<Duration>,
+}
+
+impl CodeSlide {
+    pub fn from_file(
+        path: &Path,
+        font: &Path,
+        size: f32,
+        theme: &str,
+    ) -> Result<CodeSlide, CodeError> {
+        let source = fs::read_to_string(path)?;
+
+        let syntaxes = SyntaxSet::load_defaults_newlines();
+        let syntax = match syntaxes.find_syntax_for_file(path)? {
+            Some(syntax) => syntax,
+            None => syntaxes.find_syntax_plain_text(),
+        };
+        let themes = ThemeSet::load_defaults();
+        let theme = themes
+            .themes
+            .get(theme)
+            .ok_or_else(|| CodeError::UnknownTheme(theme.to_string()))?;
+
+        let mut highlighter = HighlightLines::new(syntax, theme);
+        let lines = source
+            .lines()
+            .map(|line| {
+                /* The newlines syntax definitions expect every line to keep its line ending */
+                let line = format!("{}\n", line.replace('\t', TAB));
+                highlighter
+                    .highlight(&line, &syntaxes)
+                    .into_iter()
+                    .map(|(style, text)| (rgba(style.foreground), text.trim_end_matches('\n').to_string()))
+                    .filter(|(_, text)| !text.is_empty())
+                    .collect()
+            })
+            .collect();
+
+        Ok(CodeSlide {
+            lines,
+            background: theme.settings.background.map(rgba).unwrap_or([0.0, 0.0, 0.0, 1.0]),
+            text: TextRenderer::from_file(font)?,
+            size,
+            elapsed: Cell::new(Duration::from_secs(0)),
+        })
+    }
+
+    /* The program only fills the viewport with the theme's background; `render` draws the code
+     * on top of it */
+    pub fn program() -> Result<ShaderProgram, ShaderError> {
+        ShaderProgram::new(
+            ShaderSource::Inline(PASSTHROUGH_VERTEX_SHADER.to_string()),
+            ShaderSource::Inline(SOLID_COLOR_FRAGMENT_SHADER.to_string()),
+            None,
+            None,
+            None,
+        )
+    }
+
+    pub fn bind(&self, program: &ShaderProgram) {
+        program.set_uniform_vec4("u_color", self.background);
+    }
+
+    pub fn advance(&self, dt: Duration) {
+        self.elapsed.set(self.elapsed.get() + dt);
+    }
+
+    /* Draws the lines that fit inside the viewport, scrolled according to the time spent on the
+     * slide so far */
+    pub fn render(&self) {
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        let height = viewport[3] as f32 - 2.0 * MARGIN;
+        if height <= 0.0 {
+            return;
+        }
+
+        let line_height = self.text.line_height(self.size);
+        let visible = (height / line_height).floor() as usize;
+        let offset = self.scroll_offset(visible);
+        let first = offset.floor() as usize;
+
+        unsafe {
+            /* Keep partly scrolled lines inside the margins */
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::Scissor(
+                viewport[0],
+                viewport[1] + MARGIN as GLint,
+                viewport[2],
+                height as GLsizei,
+            );
+        }
+        let mut y = MARGIN - offset.fract() * line_height;
+        for line in self.lines.iter().skip(first).take(visible + 1) {
+            let mut x = MARGIN;
+            for &(color, ref text) in line {
+                self.text.draw_string(text, x, y, self.size, color);
+                x += self.text.measure(text, self.size);
+            }
+            y += line_height;
+        }
+        unsafe {
+            gl::Disable(gl::SCISSOR_TEST);
+        }
+    }
+
+    /* How many lines to scroll past, given the number that fit in the viewport */
+    fn scroll_offset(&self, visible: usize) -> f32 {
+        if self.lines.len() <= visible {
+            return 0.0;
+        }
+        let overflow = (self.lines.len() - visible) as f32;
+        let scroll_secs = overflow / SCROLL_LINES_PER_SEC;
+        let cycle = SCROLL_PAUSE_SECS * 2.0 + scroll_secs;
+
+        let elapsed = self.elapsed.get();
+        let t = (elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 * 1e-9) % cycle;
+        ((t - SCROLL_PAUSE_SECS) * SCROLL_LINES_PER_SEC).clamp(0.0, overflow)
+    }
+}
+
+fn rgba(color: Color) -> [f32; 4] {
+    [
+        f32::from(color.r) / 255.0,
+        f32::from(color.g) / 255.0,
+        f32::from(color.b) / 255.0,
+        f32::from(color.a) / 255.0,
+    ]
+}
@@ -0,0 +1,260 @@
+use glfw::modifiers::Modifiers;
+use glfw::Key;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use toml;
+
+use cli::Options;
+use deck::Deck;
+use history::DEFAULT_HISTORY_CAPACITY;
+use input::{InputHandler, NavigationAction, CONFIGURABLE_ACTIONS};
+use present::PresentMode;
+
+pub const DEFAULT_WIDTH: u32 = 800;
+pub const DEFAULT_HEIGHT: u32 = 600;
+
+/* Keys that can be named in the [keys] table, by their GLFW names */
+const KEY_NAMES: &[Key] = &[
+    Key::Space, Key::Apostrophe, Key::Comma, Key::Minus, Key::Period, Key::Slash,
+    Key::Num0, Key::Num1, Key::Num2, Key::Num3, Key::Num4,
+    Key::Num5, Key::Num6, Key::Num7, Key::Num8, Key::Num9,
+    Key::Semicolon, Key::Equal,
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K,
+    Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V,
+    Key::W, Key::X, Key::Y, Key::Z,
+    Key::LeftBracket, Key::Backslash, Key::RightBracket, Key::GraveAccent,
+    Key::Escape, Key::Enter, Key::Tab, Key::Backspace, Key::Insert, Key::Delete,
+    Key::Right, Key::Left, Key::Down, Key::Up, Key::PageUp, Key::PageDown, Key::Home, Key::End,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6,
+    Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12,
+];
+
+const MODIFIER_NAMES: &[(&str, Modifiers)] = &[
+    ("Ctrl", Modifiers::Control),
+    ("Shift", Modifiers::Shift),
+    ("Alt", Modifiers::Alt),
+    ("Super", Modifiers::Super),
+];
+
+/* Defaults for every deck, from the user's config file. Decks override these, and command line
+ * flags override both. */
+#[derive(Debug, Clone)]
+pub struct Config {
+    /* Used when the deck doesn't set a font */
+    pub font: Option<PathBuf>,
+    pub width: u32,
+    pub height: u32,
+    pub present_mode: PresentMode,
+    /* How many slides back Alt+Left can go */
+    pub history: usize,
+    /* Replacement bindings for the actions the file lists; the rest keep their defaults */
+    pub keys: Vec<(NavigationAction, Vec<(Key, Modifiers)>)>,
+}
+
+/* The file as written. `present_mode` takes the same values as --present-mode, and each entry of
+ * `keys` lists key names for one action, e.g. next = ["Right", "Space", "Ctrl+N"]. */
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    font: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    present_mode: Option<String>,
+    history: Option<usize>,
+    keys: BTreeMap<String, Vec<String>>,
+}
+
+impl Config {
+    /* Reads $XDG_CONFIG_HOME/slideshow/config.toml, or ~/.config/slideshow/config.toml. A
+     * missing file gives the compiled-in defaults, and so does a broken one, with a warning. */
+    pub fn load_user() -> Config {
+        let path = match Config::user_path() {
+            Some(path) => path,
+            None => return Config::default(),
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(_) => return Config::default(),
+        };
+        Config::parse(&text).unwrap_or_else(|e| {
+            println!("Warning: ignoring {}: {}", path.display(), e);
+            Config::default()
+        })
+    }
+
+    pub fn user_path() -> Option<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("slideshow").join("config.toml"))
+    }
+
+    fn parse(text: &str) -> Result<Config, String> {
+        let file: ConfigFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let defaults = Config::default();
+
+        let present_mode = match file.present_mode {
+            Some(ref mode) => mode.parse().map_err(|e| format!("invalid present_mode: {}", e))?,
+            None => defaults.present_mode,
+        };
+        if file.width == Some(0) || file.height == Some(0) {
+            return Err("width and height must be greater than zero".to_string());
+        }
+
+        let mut keys = Vec::with_capacity(file.keys.len());
+        for (name, bindings) in &file.keys {
+            let action = CONFIGURABLE_ACTIONS
+                .iter()
+                .find(|&&(action_name, _)| action_name == name)
+                .map(|&(_, action)| action)
+                .ok_or_else(|| format!("unknown action {} in [keys]", name))?;
+            let bindings = bindings
+                .iter()
+                .map(|binding| parse_binding(binding).ok_or_else(|| format!("unknown key {}", binding)))
+                .collect::<Result<Vec<_>, _>>()?;
+            keys.push((action, bindings));
+        }
+
+        Ok(Config {
+            font: file.font.map(PathBuf::from),
+            width: file.width.unwrap_or(defaults.width),
+            height: file.height.unwrap_or(defaults.height),
+            present_mode,
+            history: file.history.unwrap_or(defaults.history),
+            keys,
+        })
+    }
+
+    pub fn input_handler(&self) -> InputHandler {
+        let mut input = InputHandler::default();
+        for &(action, ref bindings) in &self.keys {
+            input.rebind(action, bindings);
+        }
+        input
+    }
+
+    /* This config with the deck's and the command line's settings applied over it, i.e. what a
+     * presentation would actually use */
+    pub fn merged(&self, options: &Options, deck: Option<&Deck>) -> Config {
+        let deck_font = deck
+            .and_then(|deck| deck.config.font.as_ref().map(|font| deck.resolve(font.get_ref())));
+        let input = self.input_handler();
+        Config {
+            font: deck_font.or_else(|| self.font.clone()),
+            width: options.width,
+            height: options.height,
+            present_mode: options.present_mode,
+            history: self.history,
+            keys: CONFIGURABLE_ACTIONS
+                .iter()
+                .map(|&(_, action)| (action, input.bindings_for(action)))
+                .collect(),
+        }
+    }
+
+    /* As a config file, so the output can be used as a starting point for one */
+    pub fn to_toml(&self) -> String {
+        let keys = self
+            .keys
+            .iter()
+            .map(|&(action, ref bindings)| {
+                let name = CONFIGURABLE_ACTIONS
+                    .iter()
+                    .find(|&&(_, a)| a == action)
+                    .map_or("", |&(name, _)| name);
+                (name.to_string(), bindings.iter().map(|&(key, mods)| binding_name(key, mods)).collect())
+            })
+            .collect();
+        let file = ConfigFile {
+            font: self.font.as_ref().map(|font| font.display().to_string()),
+            width: Some(self.width),
+            height: Some(self.height),
+            present_mode: Some(self.present_mode.to_string()),
+            history: Some(self.history),
+            keys,
+        };
+        toml::to_string(&file).expect("config serialises")
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            font: None,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            present_mode: PresentMode::default(),
+            history: DEFAULT_HISTORY_CAPACITY,
+            keys: Vec::new(),
+        }
+    }
+}
+
+/* e.g. "Right", "Ctrl+Shift+N", ignoring case */
+fn parse_binding(binding: &str) -> Option<(Key, Modifiers)> {
+    let mut parts: Vec<&str> = binding.split('+').map(str::trim).collect();
+    let key_name = parts.pop()?;
+    let mut modifiers = Modifiers::empty();
+    for part in parts {
+        let &(_, modifier) = MODIFIER_NAMES.iter().find(|&&(name, _)| name.eq_ignore_ascii_case(part))?;
+        modifiers |= modifier;
+    }
+    let key = KEY_NAMES
+        .iter()
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(key_name))?;
+    Some((*key, modifiers))
+}
+
+fn binding_name(key: Key, modifiers: Modifiers) -> String {
+    let mut name = String::new();
+    for &(modifier_name, modifier) in MODIFIER_NAMES {
+        if modifiers.contains(modifier) {
+            name.push_str(modifier_name);
+            name.push('+');
+        }
+    }
+    name.push_str(&format!("{:?}", key));
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{binding_name, parse_binding};
+    use glfw::modifiers::Modifiers;
+    use glfw::Key;
+
+    #[test]
+    fn parses_a_key_on_its_own() {
+        assert_eq!(parse_binding("Right"), Some((Key::Right, Modifiers::empty())));
+        assert_eq!(parse_binding("pageup"), Some((Key::PageUp, Modifiers::empty())));
+    }
+
+    #[test]
+    fn parses_modifiers_ignoring_case_and_spaces() {
+        let expected = Some((Key::N, Modifiers::Control | Modifiers::Shift));
+        assert_eq!(parse_binding("Ctrl+Shift+N"), expected);
+        assert_eq!(parse_binding("shift + CTRL + n"), expected);
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_modifiers() {
+        assert_eq!(parse_binding("Hyper+N"), None);
+        assert_eq!(parse_binding("Ctrl+Banana"), None);
+        assert_eq!(parse_binding(""), None);
+        assert_eq!(parse_binding("Ctrl+"), None);
+    }
+
+    #[test]
+    fn binding_names_parse_back() {
+        for &(key, modifiers) in &[
+            (Key::Space, Modifiers::empty()),
+            (Key::F5, Modifiers::Alt),
+            (Key::C, Modifiers::Control | Modifiers::Shift | Modifiers::Super),
+        ] {
+            assert_eq!(parse_binding(&binding_name(key, modifiers)), Some((key, modifiers)));
+        }
+    }
+}
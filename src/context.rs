@@ -0,0 +1,90 @@
+extern crate khronos_egl as egl;
+
+use std::os::raw::c_void;
+use std::ptr;
+
+use errors::ContextError;
+
+/* An OpenGL 3.3 core context with no window, for exporting and benchmarking on machines without
+ * a display server. On Linux it is an EGL pbuffer, with libEGL loaded at runtime so that normal
+ * windowed builds don't need it installed. With Mesa, setting EGL_PLATFORM=surfaceless avoids
+ * needing X11 or Wayland at all. */
+pub struct Context {
+    egl: egl::DynamicInstance<egl::EGL1_4>,
+    display: egl::Display,
+    surface: egl::Surface,
+    context: egl::Context,
+}
+
+impl Context {
+    #[cfg(target_os = "linux")]
+    pub fn new_headless(width: u32, height: u32) -> Result<Context, ContextError> {
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_4>::load_required() }
+            .map_err(|e| ContextError::Load(e.to_string()))?;
+        let call = |call: &'static str| move |e: egl::Error| ContextError::Egl { call, error: e.to_string() };
+
+        let display = egl.get_display(egl::DEFAULT_DISPLAY).ok_or(ContextError::NoDisplay)?;
+        egl.initialize(display).map_err(call("eglInitialize"))?;
+        egl.bind_api(egl::OPENGL_API).map_err(call("eglBindAPI"))?;
+
+        let config_attributes = [
+            egl::SURFACE_TYPE, egl::PBUFFER_BIT,
+            egl::RENDERABLE_TYPE, egl::OPENGL_BIT,
+            egl::RED_SIZE, 8,
+            egl::GREEN_SIZE, 8,
+            egl::BLUE_SIZE, 8,
+            egl::ALPHA_SIZE, 8,
+            egl::DEPTH_SIZE, 24,
+            egl::NONE,
+        ];
+        let config = egl
+            .choose_first_config(display, &config_attributes)
+            .map_err(call("eglChooseConfig"))?
+            .ok_or(ContextError::NoConfig)?;
+
+        let surface_attributes = [
+            egl::WIDTH, width as egl::Int,
+            egl::HEIGHT, height as egl::Int,
+            egl::NONE,
+        ];
+        let surface = egl
+            .create_pbuffer_surface(display, config, &surface_attributes)
+            .map_err(call("eglCreatePbufferSurface"))?;
+
+        let context_attributes = [
+            egl::CONTEXT_MAJOR_VERSION, 3,
+            egl::CONTEXT_MINOR_VERSION, 3,
+            egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            egl::NONE,
+        ];
+        let context = egl
+            .create_context(display, config, None, &context_attributes)
+            .map_err(call("eglCreateContext"))?;
+        egl.make_current(display, Some(surface), Some(surface), Some(context))
+            .map_err(call("eglMakeCurrent"))?;
+
+        Ok(Context { egl, display, surface, context })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new_headless(_width: u32, _height: u32) -> Result<Context, ContextError> {
+        Err(ContextError::Unsupported)
+    }
+
+    /* For gl::load_with, like glfw::Window::get_proc_address */
+    pub fn get_proc_address(&self, name: &str) -> *const c_void {
+        match self.egl.get_proc_address(name) {
+            Some(f) => f as *const c_void,
+            None => ptr::null(),
+        }
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        self.egl.make_current(self.display, None, None, None).ok();
+        self.egl.destroy_surface(self.display, self.surface).ok();
+        self.egl.destroy_context(self.display, self.context).ok();
+        self.egl.terminate(self.display).ok();
+    }
+}
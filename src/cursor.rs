@@ -0,0 +1,35 @@
+use glfw::{CursorMode, Window};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/* Hides the mouse cursor over the slides once it has been still for `timeout`, and shows it again
+ * as soon as it moves */
+pub struct CursorManager {
+    timeout: Duration,
+    last_move: Instant,
+    hidden: bool,
+}
+
+impl CursorManager {
+    pub fn new(timeout: Duration, now: Instant) -> CursorManager {
+        CursorManager {
+            timeout,
+            last_move: now,
+            hidden: false,
+        }
+    }
+
+    pub fn on_mouse_move(&mut self, now: Instant) {
+        self.last_move = now;
+    }
+
+    /* Only changes the cursor mode when the cursor should switch between shown and hidden */
+    pub fn on_frame(&mut self, window: &mut Window, now: Instant) {
+        let idle = now.duration_since(self.last_move) >= self.timeout;
+        if idle != self.hidden {
+            window.set_cursor_mode(if idle { CursorMode::Hidden } else { CursorMode::Normal });
+            self.hidden = idle;
+        }
+    }
+}
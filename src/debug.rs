@@ -0,0 +1,58 @@
+extern crate gl;
+use gl::types::*;
+use std::ffi::CStr;
+use std::os::raw::c_void;
+use std::ptr;
+
+/* Routes driver debug messages to the log crate. Needs a GL 4.3 or KHR_debug context, so this
+ * does nothing if the callback cannot be loaded, and nothing at all in release builds. */
+#[cfg(debug_assertions)]
+pub fn install_debug_callback() {
+    if !gl::DebugMessageCallback::is_loaded() {
+        warn!("glDebugMessageCallback is not available, GL debug output is disabled");
+        return;
+    }
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        gl::DebugMessageCallback(debug_callback, ptr::null());
+    }
+}
+
+#[cfg(not(debug_assertions))]
+pub fn install_debug_callback() {}
+
+#[cfg(debug_assertions)]
+extern "system" fn debug_callback(
+    source: GLenum,
+    gltype: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    _length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut c_void,
+) {
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    let source = match source {
+        gl::DEBUG_SOURCE_API => "api",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    };
+    let kind = match gltype {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behaviour",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behaviour",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        _ => "other",
+    };
+
+    match severity {
+        gl::DEBUG_SEVERITY_HIGH => error!("GL {} {} {}: {}", source, kind, id, message),
+        gl::DEBUG_SEVERITY_MEDIUM => warn!("GL {} {} {}: {}", source, kind, id, message),
+        gl::DEBUG_SEVERITY_LOW => info!("GL {} {} {}: {}", source, kind, id, message),
+        _ => debug!("GL {} {} {}: {}", source, kind, id, message),
+    }
+}
@@ -0,0 +1,1070 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use serde::de::{self, Deserialize, Deserializer};
+use toml::value::Datetime;
+use toml::{self, Spanned, Value};
+
+use animated_texture::AnimatedTexture;
+use animation::{AnimationPlayer, AnimationTrack, Keyframe};
+use aspect::{AspectMode, DEFAULT_ASPECT_RATIO};
+use channels::{ChannelBinding, TextureSource, CHANNEL_COUNT};
+use code_slide::{CodeSlide, DEFAULT_FONT_SIZE, DEFAULT_THEME};
+use easing;
+use errors::DeckError;
+use image_slide::{ImageFit, ImageSlide};
+use noise::NoiseKind;
+use shader::{ShaderProgram, ShaderSource, ShaderStage, UniformValue};
+use slide::{Slide, SlideManager, DEFAULT_BACKGROUND};
+use texture::{Texture2D, TextureCubemap};
+use transition::Transition;
+use uploader::BackgroundUploader;
+#[cfg(feature = "video")]
+use video::VideoSlide;
+
+#[derive(Debug, Deserialize)]
+pub struct DeckConfig {
+    pub slides: Vec<SlideConfig>,
+    pub transition: Option<TransitionConfig>,
+    /* A TrueType or OpenType font; slide titles are only drawn when this is set */
+    pub font: Option<Spanned<String>>,
+    pub aspect: Option<AspectMode>,
+    /* Width and height, e.g. [4, 3]; 16:9 when not set */
+    pub aspect_ratio: Option<[f32; 2]>,
+    /* The colour of shader slides that give no vertex or fragment shader, e.g. "#1a1a2e" */
+    pub background: Option<Spanned<String>>,
+    /* Seconds without mouse movement before the cursor is hidden */
+    pub cursor_idle_secs: Option<f32>,
+    #[serde(rename = "loop")]
+    pub loop_range: Option<LoopConfig>,
+    pub midi: Option<MidiConfig>,
+    pub metadata: Option<DeckMetadata>,
+}
+
+/* About the talk itself, from [metadata], for `slideshow info`, the presenter window's title and
+ * the event log */
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DeckMetadata {
+    pub title: String,
+    pub author: String,
+    pub version: String,
+    /* A TOML date, e.g. date = 2024-05-01, not a string */
+    #[serde(deserialize_with = "deserialize_date")]
+    pub date: NaiveDate,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MidiConfig {
+    pub mappings: Vec<MidiMapping>,
+}
+
+/* Controller `cc` sets float uniform `uniform`, with 0 to 127 mapped onto `range` */
+#[derive(Debug, Clone, Deserialize)]
+pub struct MidiMapping {
+    pub cc: u8,
+    pub uniform: String,
+    #[serde(default = "default_midi_range")]
+    pub range: [f32; 2],
+}
+
+fn default_midi_range() -> [f32; 2] {
+    [0.0, 1.0]
+}
+
+/* Slides to cycle through, counting from 1 and including both ends */
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct LoopConfig {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlideType {
+    #[default]
+    Shader,
+    Image,
+    Video,
+    Code,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SlideConfig {
+    pub title: Option<String>,
+    /* For jumping to the slide by name, e.g. with --start-at, rather than by number */
+    pub name: Option<Spanned<String>>,
+    #[serde(rename = "type", default)]
+    pub kind: SlideType,
+    /* Required for shader slides, unless both are left out to fill the slide with `background` */
+    pub vertex: Option<Spanned<String>>,
+    pub fragment: Option<Spanned<String>>,
+    pub geometry: Option<Spanned<String>>,
+    pub tess_control: Option<Spanned<String>>,
+    pub tess_eval: Option<Spanned<String>>,
+    pub duration_secs: Option<f32>,
+    pub notes: Option<String>,
+    /* Required for image and video slides; video slides may also set vertex and fragment */
+    pub path: Option<Spanned<String>>,
+    #[serde(default)]
+    pub fit: ImageFit,
+    /* Code slides only: a monospace font, its size in pixels, and a syntect theme name */
+    pub font: Option<Spanned<String>>,
+    pub font_size: Option<f32>,
+    pub theme: Option<String>,
+    /* Left out of the presentation unless --include-skipped is given */
+    #[serde(default)]
+    pub skip: bool,
+    /* Overrides the deck's background for this slide */
+    pub background: Option<Spanned<String>>,
+    /* The width and height the slide was made for, e.g. [1024, 768]; it is letterboxed to
+     * fit at this aspect ratio, and iResolution reports the letterboxed area's size in pixels */
+    pub resolution: Option<[u32; 2]>,
+    /* Preprocessor defines for the slide's shaders, "NAME" or "NAME=VALUE" */
+    #[serde(default)]
+    pub defines: Vec<Spanned<String>>,
+    #[serde(default)]
+    pub uniforms: BTreeMap<String, Spanned<Value>>,
+    /* Keyed by channel number, 0 to 3 */
+    #[serde(default)]
+    pub channels: BTreeMap<String, ChannelConfig>,
+    /* From [[slides.animations]] tables following the slide */
+    #[serde(default)]
+    pub animations: Vec<AnimationConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnimationConfig {
+    pub name: String,
+    pub uniform: String,
+    /* One of easing::NAMES, applied between each pair of keyframes; linear when not set */
+    pub easing: Option<Spanned<String>>,
+    pub keyframes: Vec<KeyframeConfig>,
+}
+
+/* `value` takes the same forms as a slide's uniforms */
+#[derive(Debug, Deserialize)]
+pub struct KeyframeConfig {
+    pub time: f32,
+    pub value: Spanned<Value>,
+}
+
+/* Exactly one of texture, video, noise or faces should be set: faces with type "cubemap", a
+ * texture that is an animated GIF with type "gif", and any of the others with the default type */
+#[derive(Debug, Deserialize)]
+pub struct ChannelConfig {
+    #[serde(rename = "type", default)]
+    pub kind: ChannelKind,
+    pub texture: Option<Spanned<String>>,
+    /* For a cubemap, the images for +X, -X, +Y, -Y, +Z and -Z in that order */
+    pub faces: Option<Vec<Spanned<String>>>,
+    pub video: Option<Spanned<String>>,
+    /* Side length of a square noise texture */
+    pub noise: Option<u32>,
+    #[serde(default)]
+    pub noise_type: NoiseKind,
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelKind {
+    /* A sampler2D, from a texture, video or noise */
+    #[default]
+    #[serde(rename = "2d")]
+    Texture,
+    /* A samplerCube, from six faces */
+    Cubemap,
+    /* A sampler2DArray with a layer per frame, from the animated GIF in texture */
+    Gif,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransitionConfig {
+    pub vertex: Spanned<String>,
+    pub enter: Spanned<String>,
+    pub exit: Spanned<String>,
+    pub duration_secs: f32,
+    /* One of easing::NAMES; linear when not set */
+    pub easing: Option<Spanned<String>>,
+}
+
+#[derive(Debug)]
+pub struct Deck {
+    pub path: PathBuf,
+    /* The directory holding the manifest, which relative paths in it are resolved against */
+    pub base_dir: PathBuf,
+    pub config: DeckConfig,
+    /* Parsed from each slide's `uniforms` table, in slide order */
+    pub uniforms: Vec<Vec<(String, UniformValue)>>,
+    /* Parsed from each slide's `animations`, in slide order */
+    pub animations: Vec<Vec<AnimationTrack>>,
+    /* Parsed from each slide's `defines`, in slide order */
+    pub defines: Vec<Vec<(String, String)>>,
+    /* Parsed from `background`, in slide order */
+    pub backgrounds: Vec<[f32; 4]>,
+    /* Values saved from the uniform editor, keyed by slide index, which win over `uniforms` */
+    pub overrides: UniformOverrides,
+    /* SPIR-V from compile-shaders, loaded in place of compiling the GLSL where it matches */
+    pub spirv_cache: Option<PathBuf>,
+}
+
+pub type UniformOverrides = BTreeMap<usize, BTreeMap<String, UniformValue>>;
+
+/* Time on each slide, keyed by slide index in the manifest */
+pub type SlideTimings = BTreeMap<usize, Duration>;
+
+/* Which of the deck's slides SlideManager::from_deck creates */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlideSelection {
+    /* Every slide not marked skip */
+    #[default]
+    Presented,
+    All,
+    /* Just this one, zero-based, whether or not it is skipped */
+    Only(usize),
+}
+
+/* One of the deck's programs, from Deck::shader_programs */
+#[derive(Debug)]
+pub struct DeckProgram {
+    /* e.g. "slide 3" or "transition enter" */
+    pub name: String,
+    pub stages: Vec<(ShaderStage, ShaderSource)>,
+    pub defines: Vec<(String, String)>,
+}
+
+impl Deck {
+    pub fn from_file(path: &Path) -> Result<Deck, DeckError> {
+        let mut file = File::open(path)?;
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+
+        let config: DeckConfig = toml::from_str(&text)?;
+        if config.slides.is_empty() {
+            return Err(DeckError::Empty);
+        }
+        if let Some([width, height]) = config.aspect_ratio {
+            if width <= 0.0 || height <= 0.0 {
+                return Err(DeckError::InvalidAspectRatio(path.to_path_buf()));
+            }
+        }
+        if let Some(range) = config.loop_range {
+            if range.start == 0 || range.end < range.start || range.end > config.slides.len() {
+                return Err(DeckError::InvalidLoop(path.to_path_buf()));
+            }
+        }
+        for mapping in config.midi.iter().flat_map(|midi| &midi.mappings) {
+            if mapping.cc > 127 {
+                return Err(DeckError::InvalidMidi {
+                    manifest: path.to_path_buf(),
+                    uniform: mapping.uniform.clone(),
+                    reason: "controller numbers run from 0 to 127",
+                });
+            }
+        }
+        if let Some(secs) = config.cursor_idle_secs {
+            if secs <= 0.0 {
+                return Err(DeckError::InvalidCursorIdle(path.to_path_buf()));
+            }
+        }
+        if let Some(ref transition) = config.transition {
+            if transition.duration_secs <= 0.0 {
+                return Err(DeckError::InvalidTransitionDuration(path.to_path_buf()));
+            }
+        }
+
+        let mut names = HashSet::new();
+        for name in config.slides.iter().filter_map(|slide| slide.name.as_ref()) {
+            if !names.insert(name.get_ref()) {
+                return Err(DeckError::DuplicateName {
+                    manifest: path.to_path_buf(),
+                    line: line_number(&text, name.start()),
+                    name: name.get_ref().clone(),
+                });
+            }
+        }
+
+        let base_dir = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+
+        /* Check every referenced file up front, while we still have the source text to point at */
+        let mut paths = Vec::new();
+        for (i, slide) in config.slides.iter().enumerate() {
+            let required: &[(&'static str, &Option<Spanned<String>>)] = match slide.kind {
+                SlideType::Shader if slide.vertex.is_none() && slide.fragment.is_none() => &[],
+                SlideType::Shader => &[("vertex", &slide.vertex), ("fragment", &slide.fragment)],
+                SlideType::Image | SlideType::Video => &[("path", &slide.path)],
+                SlideType::Code => &[("path", &slide.path), ("font", &slide.font)],
+            };
+            if slide.kind == SlideType::Video && !cfg!(feature = "video") {
+                return Err(DeckError::UnsupportedType {
+                    manifest: path.to_path_buf(),
+                    slide: i,
+                    feature: "video",
+                });
+            }
+            for &(field, value) in required {
+                if value.is_none() {
+                    return Err(DeckError::MissingField {
+                        manifest: path.to_path_buf(),
+                        slide: i,
+                        field,
+                    });
+                }
+            }
+            if let Some([width, height]) = slide.resolution {
+                if width == 0 || height == 0 {
+                    return Err(DeckError::InvalidResolution {
+                        manifest: path.to_path_buf(),
+                        slide: i,
+                    });
+                }
+            }
+            /* Zero would have SlideManager::advance move on every frame */
+            if let Some(secs) = slide.duration_secs {
+                if secs <= 0.0 {
+                    return Err(DeckError::InvalidDuration {
+                        manifest: path.to_path_buf(),
+                        slide: i,
+                    });
+                }
+            }
+
+            paths.extend(slide.vertex.iter());
+            paths.extend(slide.fragment.iter());
+            paths.extend(slide.path.iter());
+            paths.extend(slide.font.iter());
+            paths.extend(slide.geometry.iter());
+            paths.extend(slide.tess_control.iter());
+            paths.extend(slide.tess_eval.iter());
+
+            for (name, channel) in &slide.channels {
+                let invalid = |reason| DeckError::InvalidChannel {
+                    manifest: path.to_path_buf(),
+                    slide: i,
+                    channel: name.clone(),
+                    reason,
+                };
+                match name.parse::<usize>() {
+                    Ok(n) if n < CHANNEL_COUNT => {}
+                    _ => return Err(invalid("channels are numbered 0 to 3")),
+                }
+                let sources = channel.texture.is_some() as u32
+                    + channel.video.is_some() as u32
+                    + channel.noise.is_some() as u32;
+                match channel.kind {
+                    ChannelKind::Texture | ChannelKind::Gif if channel.faces.is_some() => {
+                        return Err(invalid("faces need type = \"cubemap\""))
+                    }
+                    ChannelKind::Texture if sources != 1 => {
+                        return Err(invalid("set exactly one of texture, video or noise"))
+                    }
+                    ChannelKind::Gif if sources != 1 || channel.texture.is_none() => {
+                        return Err(invalid("a gif channel takes the GIF file as its texture"))
+                    }
+                    ChannelKind::Cubemap if sources != 0 => {
+                        return Err(invalid("a cubemap takes faces, not texture, video or noise"))
+                    }
+                    ChannelKind::Cubemap if channel.faces.as_ref().map_or(0, Vec::len) != 6 => {
+                        return Err(invalid("a cubemap needs 6 faces, +X, -X, +Y, -Y, +Z and -Z"))
+                    }
+                    _ => {}
+                }
+                if channel.noise == Some(0) {
+                    return Err(invalid("noise size must be greater than zero"));
+                }
+                if channel.video.is_some() && !cfg!(feature = "video") {
+                    return Err(DeckError::UnsupportedType {
+                        manifest: path.to_path_buf(),
+                        slide: i,
+                        feature: "video",
+                    });
+                }
+                paths.extend(channel.texture.iter());
+                paths.extend(channel.video.iter());
+                paths.extend(channel.faces.iter().flatten());
+            }
+        }
+        if let Some(ref transition) = config.transition {
+            paths.push(&transition.vertex);
+            paths.push(&transition.enter);
+            paths.push(&transition.exit);
+            if let Some(ref name) = transition.easing {
+                if easing::by_name(name.get_ref()).is_none() {
+                    return Err(DeckError::UnknownEasing {
+                        manifest: path.to_path_buf(),
+                        line: line_number(&text, name.start()),
+                        name: name.get_ref().clone(),
+                    });
+                }
+            }
+        }
+        paths.extend(config.font.iter());
+
+        for p in paths {
+            if !base_dir.join(p.get_ref()).is_file() {
+                return Err(DeckError::InvalidPath {
+                    manifest: path.to_path_buf(),
+                    line: line_number(&text, p.start()),
+                    path: p.get_ref().clone(),
+                });
+            }
+        }
+
+        let mut uniforms = Vec::with_capacity(config.slides.len());
+        for slide in &config.slides {
+            let mut values = Vec::with_capacity(slide.uniforms.len());
+            for (name, value) in &slide.uniforms {
+                match uniform_value(value.get_ref()) {
+                    Some(v) => values.push((name.clone(), v)),
+                    None => {
+                        return Err(DeckError::InvalidUniform {
+                            manifest: path.to_path_buf(),
+                            line: line_number(&text, value.start()),
+                            name: name.clone(),
+                        });
+                    }
+                }
+            }
+            uniforms.push(values);
+        }
+
+        let mut animations = Vec::with_capacity(config.slides.len());
+        for (i, slide) in config.slides.iter().enumerate() {
+            let mut tracks = Vec::with_capacity(slide.animations.len());
+            for track in &slide.animations {
+                let invalid = |reason| DeckError::InvalidAnimation {
+                    manifest: path.to_path_buf(),
+                    slide: i,
+                    name: track.name.clone(),
+                    reason,
+                };
+                let mut keyframes: Vec<Keyframe> = Vec::with_capacity(track.keyframes.len());
+                for keyframe in &track.keyframes {
+                    let value = uniform_value(keyframe.value.get_ref()).ok_or_else(|| {
+                        DeckError::InvalidUniform {
+                            manifest: path.to_path_buf(),
+                            line: line_number(&text, keyframe.value.start()),
+                            name: track.uniform.clone(),
+                        }
+                    })?;
+                    if keyframe.time < 0.0 {
+                        return Err(invalid("keyframe times must not be negative"));
+                    }
+                    if let Some(previous) = keyframes.last() {
+                        if keyframe.time <= previous.time {
+                            return Err(invalid("keyframes must be in increasing time order"));
+                        }
+                        if mem::discriminant(&value) != mem::discriminant(&previous.value) {
+                            return Err(invalid("keyframes must all have the same type"));
+                        }
+                    }
+                    keyframes.push(Keyframe { time: keyframe.time, value });
+                }
+                if keyframes.is_empty() {
+                    return Err(invalid("needs at least one keyframe"));
+                }
+
+                let mut animation = AnimationTrack::new(&track.name, &track.uniform, keyframes);
+                if let Some(ref name) = track.easing {
+                    match easing::by_name(name.get_ref()) {
+                        Some(easing) => animation = animation.with_easing(easing),
+                        None => {
+                            return Err(DeckError::UnknownEasing {
+                                manifest: path.to_path_buf(),
+                                line: line_number(&text, name.start()),
+                                name: name.get_ref().clone(),
+                            })
+                        }
+                    }
+                }
+                tracks.push(animation);
+            }
+            animations.push(tracks);
+        }
+
+        let mut defines = Vec::with_capacity(config.slides.len());
+        for slide in &config.slides {
+            let mut values: Vec<(String, String)> = Vec::with_capacity(slide.defines.len());
+            for define in &slide.defines {
+                let invalid = |reason| DeckError::InvalidDefine {
+                    manifest: path.to_path_buf(),
+                    line: line_number(&text, define.start()),
+                    define: define.get_ref().clone(),
+                    reason,
+                };
+                let (name, value) = parse_define(define.get_ref()).map_err(invalid)?;
+                match values.iter().find(|d| d.0 == name) {
+                    Some(earlier) if earlier.1 == value => return Err(invalid("defined twice")),
+                    Some(_) => return Err(invalid("conflicts with an earlier value")),
+                    None => values.push((name, value)),
+                }
+            }
+            defines.push(values);
+        }
+
+        let color = |value: &Spanned<String>| {
+            parse_color(value.get_ref()).map_err(|reason| DeckError::InvalidColor {
+                manifest: path.to_path_buf(),
+                line: line_number(&text, value.start()),
+                color: value.get_ref().clone(),
+                reason,
+            })
+        };
+        let default_background = match config.background {
+            Some(ref value) => color(value)?,
+            None => DEFAULT_BACKGROUND,
+        };
+        let mut backgrounds = Vec::with_capacity(config.slides.len());
+        for slide in &config.slides {
+            backgrounds.push(match slide.background {
+                Some(ref value) => color(value)?,
+                None => default_background,
+            });
+        }
+
+        let overrides = load_overrides(&Deck::overrides_path(path), config.slides.len())?;
+
+        Ok(Deck {
+            path: path.to_path_buf(),
+            base_dir,
+            config,
+            uniforms,
+            animations,
+            defines,
+            backgrounds,
+            overrides,
+            spirv_cache: None,
+        })
+    }
+
+    /* Programs are looked up in `dir`, as written by compile-shaders, before compiling their
+     * GLSL */
+    pub fn with_spirv_cache(mut self, dir: PathBuf) -> Deck {
+        self.spirv_cache = Some(dir);
+        self
+    }
+
+    /* `path` as given in the manifest, relative to the manifest's directory unless it is
+     * absolute */
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        self.base_dir.join(path)
+    }
+
+    fn shader_source(&self, path: &Spanned<String>) -> ShaderSource {
+        ShaderSource::File(self.resolve(path.get_ref()))
+    }
+
+    fn optional_shader_source(&self, path: &Option<Spanned<String>>) -> Option<ShaderSource> {
+        path.as_ref().map(|path| self.shader_source(path))
+    }
+
+    /* The stages and defines of every program in the deck built from its own GLSL, named for
+     * messages, e.g. to compile them ahead of time. Programs built into the slideshow, such as
+     * those of image slides, are left out. */
+    pub fn shader_programs(&self) -> Vec<DeckProgram> {
+        let mut programs = Vec::new();
+        for (i, slide) in self.config.slides.iter().enumerate() {
+            if let Some(stages) = self.slide_stages(slide) {
+                programs.push(DeckProgram {
+                    name: format!("slide {}", i + 1),
+                    stages,
+                    defines: self.defines[i].clone(),
+                });
+            }
+        }
+        if let Some(ref transition) = self.config.transition {
+            for &(name, fragment) in &[("enter", &transition.enter), ("exit", &transition.exit)] {
+                programs.push(DeckProgram {
+                    name: format!("transition {}", name),
+                    stages: self.transition_stages(transition, fragment),
+                    defines: Vec::new(),
+                });
+            }
+        }
+        programs
+    }
+
+    /* The stages of a slide's own program, if it has both a vertex and a fragment shader */
+    fn slide_stages(&self, slide: &SlideConfig) -> Option<Vec<(ShaderStage, ShaderSource)>> {
+        let (vertex, fragment) = match (&slide.vertex, &slide.fragment) {
+            (&Some(ref vertex), &Some(ref fragment)) => (vertex, fragment),
+            _ => return None,
+        };
+        let mut stages = vec![
+            (ShaderStage::Vertex, self.shader_source(vertex)),
+            (ShaderStage::Fragment, self.shader_source(fragment)),
+        ];
+        let optional = [
+            (ShaderStage::Geometry, &slide.geometry),
+            (ShaderStage::TessControl, &slide.tess_control),
+            (ShaderStage::TessEvaluation, &slide.tess_eval),
+        ];
+        for &(stage, path) in &optional {
+            stages.extend(self.optional_shader_source(path).map(|source| (stage, source)));
+        }
+        Some(stages)
+    }
+
+    fn transition_stages(&self, transition: &TransitionConfig, fragment: &Spanned<String>)
+        -> Vec<(ShaderStage, ShaderSource)> {
+        vec![
+            (ShaderStage::Vertex, self.shader_source(&transition.vertex)),
+            (ShaderStage::Fragment, self.shader_source(fragment)),
+        ]
+    }
+
+    /* The program from the SPIR-V cache, if there is one and it holds every stage as it is now.
+     * Anything else leaves the caller to compile the GLSL. */
+    fn cached_program(&self, stages: &[(ShaderStage, ShaderSource)], defines: &[(String, String)])
+        -> Option<ShaderProgram> {
+        let cache_dir = self.spirv_cache.as_ref()?;
+        match ShaderProgram::from_spirv_cache(stages, defines, cache_dir) {
+            Ok(program) => program,
+            Err(e) => {
+                println!("Warning: cannot load cached SPIR-V, compiling GLSL: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        match self.config.aspect_ratio {
+            Some([width, height]) => width / height,
+            None => DEFAULT_ASPECT_RATIO,
+        }
+    }
+
+    /* Indices into config.slides of the slides `selection` picks, in order */
+    pub fn selected_slides(&self, selection: SlideSelection) -> Vec<usize> {
+        match selection {
+            SlideSelection::Presented => (0..self.config.slides.len())
+                .filter(|&i| !self.config.slides[i].skip)
+                .collect(),
+            SlideSelection::All => (0..self.config.slides.len()).collect(),
+            SlideSelection::Only(index) => vec![index],
+        }
+    }
+
+    /* The position among the slides `selection` picks of the slide called `name`, if it is
+     * one of them */
+    pub fn slide_named(&self, name: &str, selection: SlideSelection) -> Option<usize> {
+        self.selected_slides(selection).into_iter().position(|i| {
+            self.config.slides[i].name.as_ref().map_or(false, |n| n.get_ref() == name)
+        })
+    }
+
+    pub fn cursor_idle(&self) -> Option<Duration> {
+        self.config
+            .cursor_idle_secs
+            .map(|secs| Duration::from_millis((secs * 1000.0) as u64))
+    }
+
+    /* The sidecar file written by the uniform editor: deck.toml is paired with
+     * deck.uniforms.toml */
+    pub fn overrides_path(path: &Path) -> PathBuf {
+        path.with_extension("uniforms.toml")
+    }
+
+    /* The sidecar file written by --rehearsal: deck.toml is paired with deck.timings.toml */
+    pub fn timings_path(path: &Path) -> PathBuf {
+        path.with_extension("timings.toml")
+    }
+}
+
+/* The sidecar is a table per slide, numbered from 1 as in the rest of the UI, mapping uniform
+ * names to values in the same format as the deck manifest */
+fn load_overrides(path: &Path, slide_count: usize) -> Result<UniformOverrides, DeckError> {
+    let mut overrides = BTreeMap::new();
+    if !path.is_file() {
+        return Ok(overrides);
+    }
+
+    let text = fs::read_to_string(path)?;
+    let tables: BTreeMap<String, BTreeMap<String, Spanned<Value>>> = toml::from_str(&text)?;
+    for (slide, values) in tables {
+        let index = match slide.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= slide_count => n - 1,
+            _ => {
+                println!("Warning: {} refers to missing slide {}", path.display(), slide);
+                continue;
+            }
+        };
+        let mut parsed = BTreeMap::new();
+        for (name, value) in values {
+            match uniform_value(value.get_ref()) {
+                Some(v) => parsed.insert(name, v),
+                None => {
+                    return Err(DeckError::InvalidUniform {
+                        manifest: path.to_path_buf(),
+                        line: line_number(&text, value.start()),
+                        name,
+                    });
+                }
+            };
+        }
+        overrides.insert(index, parsed);
+    }
+    info!("Loaded uniform overrides from {}", path.display());
+    Ok(overrides)
+}
+
+pub fn save_overrides(path: &Path, overrides: &UniformOverrides) -> io::Result<()> {
+    let tables: BTreeMap<String, BTreeMap<&str, Value>> = overrides
+        .iter()
+        .filter(|&(_, values)| !values.is_empty())
+        .map(|(index, values)| {
+            let values = values.iter().map(|(name, v)| (name.as_str(), toml_value(v))).collect();
+            ((index + 1).to_string(), values)
+        })
+        .collect();
+    let text = toml::to_string(&tables).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    fs::write(path, text)
+}
+
+/* The timings sidecar maps slide numbers, counting from 1, to seconds. Unlike the overrides
+ * there is nothing to fall back on, so a missing file is an error. */
+pub fn load_timings(path: &Path, slide_count: usize) -> Result<SlideTimings, DeckError> {
+    let text = fs::read_to_string(path)?;
+    let seconds: BTreeMap<String, f64> = toml::from_str(&text)?;
+    let mut timings = BTreeMap::new();
+    for (slide, secs) in seconds {
+        match slide.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= slide_count && secs > 0.0 => {
+                timings.insert(n - 1, Duration::from_millis((secs * 1000.0) as u64));
+            }
+            _ => println!("Warning: ignoring {} = {} in {}", slide, secs, path.display()),
+        }
+    }
+    Ok(timings)
+}
+
+pub fn save_timings(path: &Path, timings: &SlideTimings) -> io::Result<()> {
+    /* Tenths of a second are plenty, and keep the file readable */
+    let seconds: BTreeMap<String, f64> = timings
+        .iter()
+        .map(|(index, duration)| {
+            ((index + 1).to_string(), (duration.as_secs_f64() * 10.0).round() / 10.0)
+        })
+        .collect();
+    let text = toml::to_string(&seconds).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    fs::write(path, text)
+}
+
+impl SlideManager {
+    /* Slide indices follow the selection, but each slide keeps its index in the manifest for
+     * looking up its uniforms and overrides. A shader that fails to build fails the whole deck,
+     * as exports should not quietly write blank slides. */
+    pub fn from_deck(deck: &Deck, selection: SlideSelection) -> Result<SlideManager, DeckError> {
+        SlideManager::build(deck, selection, false, None)
+    }
+
+    /* As from_deck, but for presenting: a broken shader slide is still created, showing its
+     * error in place of the slide until a reload fixes it. Channel textures are loaded by
+     * `uploader` when given, with a placeholder until they arrive. */
+    pub fn from_deck_allowing_errors(
+        deck: &Deck,
+        selection: SlideSelection,
+        uploader: Option<&BackgroundUploader>,
+    ) -> Result<SlideManager, DeckError> {
+        SlideManager::build(deck, selection, true, uploader)
+    }
+
+    fn build(
+        deck: &Deck,
+        selection: SlideSelection,
+        allow_errors: bool,
+        uploader: Option<&BackgroundUploader>,
+    ) -> Result<SlideManager, DeckError> {
+        let selected = deck.selected_slides(selection);
+        if selected.is_empty() {
+            return Err(DeckError::Empty);
+        }
+
+        let mut slides = Vec::with_capacity(selected.len());
+        for i in selected {
+            let config = &deck.config.slides[i];
+            let title = match config.title {
+                Some(ref title) => title.clone(),
+                None => format!("Slide {}", i + 1),
+            };
+
+            /* Deck::from_file has already checked the required fields are present */
+            let slide = match config.kind {
+                SlideType::Shader if config.vertex.is_none() => {
+                    Slide::new_fill(&title, deck.backgrounds[i])?
+                }
+                SlideType::Shader => {
+                    let vertex = deck.optional_shader_source(&config.vertex).unwrap();
+                    let fragment = deck.optional_shader_source(&config.fragment).unwrap();
+                    let geometry = deck.optional_shader_source(&config.geometry);
+                    let tess_control = deck.optional_shader_source(&config.tess_control);
+                    let tess_eval = deck.optional_shader_source(&config.tess_eval);
+                    let stages = deck.slide_stages(config).unwrap();
+                    let cached = deck.cached_program(&stages, &deck.defines[i]);
+                    let program = if let Some(program) = cached {
+                        program
+                    } else if allow_errors {
+                        let program = ShaderProgram::allowing_errors(
+                            vertex,
+                            fragment,
+                            geometry,
+                            tess_control,
+                            tess_eval,
+                            deck.defines[i].clone(),
+                        );
+                        if let Some(error) = program.build_error() {
+                            println!("Error building shader for {}: {}", title, error);
+                        }
+                        program
+                    } else {
+                        ShaderProgram::with_defines(
+                            vertex,
+                            fragment,
+                            geometry,
+                            tess_control,
+                            tess_eval,
+                            deck.defines[i].clone(),
+                        )?
+                    };
+                    Slide::new(&title, program)
+                }
+                SlideType::Image => {
+                    let path = deck.resolve(config.path.as_ref().unwrap().get_ref());
+                    Slide::new_image(&title, ImageSlide::from_file(&path, config.fit)?)?
+                }
+                SlideType::Code => {
+                    let code = CodeSlide::from_file(
+                        &deck.resolve(config.path.as_ref().unwrap().get_ref()),
+                        &deck.resolve(config.font.as_ref().unwrap().get_ref()),
+                        config.font_size.unwrap_or(DEFAULT_FONT_SIZE),
+                        config.theme.as_ref().map_or(DEFAULT_THEME, |t| t.as_str()),
+                    )?;
+                    Slide::new_code(&title, code)?
+                }
+                #[cfg(feature = "video")]
+                SlideType::Video => {
+                    let path = deck.resolve(config.path.as_ref().unwrap().get_ref());
+                    let shaders = (
+                        deck.optional_shader_source(&config.vertex),
+                        deck.optional_shader_source(&config.fragment),
+                    );
+                    let cached = deck
+                        .slide_stages(config)
+                        .and_then(|stages| deck.cached_program(&stages, &deck.defines[i]));
+                    let program = match (cached, shaders) {
+                        (Some(program), _) => program,
+                        (None, (Some(vertex), Some(fragment))) => ShaderProgram::with_defines(
+                            vertex,
+                            fragment,
+                            None,
+                            None,
+                            None,
+                            deck.defines[i].clone(),
+                        )?,
+                        (None, _) => ImageSlide::program()?,
+                    };
+                    Slide::new_video(&title, program, VideoSlide::from_file(&path)?)
+                }
+                #[cfg(not(feature = "video"))]
+                SlideType::Video => unreachable!("Deck::from_file rejects video slides"),
+            };
+
+            let mut slide = slide
+                .with_manifest_index(i)
+                .with_uniforms(deck.uniforms[i].clone())
+                .with_animations(AnimationPlayer::new(deck.animations[i].clone()))
+                .with_channels(channel_binding(deck, config, uploader)?);
+            for (name, value) in deck.overrides.get(&i).into_iter().flatten() {
+                slide.set_uniform_value(name, *value);
+            }
+            if let Some(secs) = config.duration_secs {
+                slide = slide.with_auto_advance_after(Duration::from_millis((secs * 1000.0) as u64));
+            }
+            if let Some(ref notes) = config.notes {
+                slide = slide.with_notes(notes);
+            }
+            if let Some(ref name) = config.name {
+                slide = slide.with_name(name.get_ref());
+            }
+            if let Some([width, height]) = config.resolution {
+                slide = slide.with_resolution(width, height);
+            }
+            slides.push(slide);
+        }
+
+        /* The range is of deck slides, so it shrinks to the ones actually created */
+        let loop_range = deck.config.loop_range.and_then(|range| {
+            let in_range = |slide: &Slide| {
+                slide.manifest_index + 1 >= range.start && slide.manifest_index < range.end
+            };
+            let start = slides.iter().position(in_range)?;
+            let end = slides.iter().rposition(in_range)?;
+            Some((start, end))
+        });
+
+        let mut manager = SlideManager::new(slides);
+        if let Some((start, end)) = loop_range {
+            manager.loop_range(start, end);
+        }
+        if let Some(ref config) = deck.config.transition {
+            let program = |fragment: &Spanned<String>| {
+                let stages = deck.transition_stages(config, fragment);
+                if let Some(program) = deck.cached_program(&stages, &[]) {
+                    return Ok(program);
+                }
+                ShaderProgram::new(
+                    deck.shader_source(&config.vertex),
+                    deck.shader_source(fragment),
+                    None,
+                    None,
+                    None,
+                )
+            };
+            let duration = Duration::from_millis((config.duration_secs * 1000.0) as u64);
+            let mut transition = Transition::new(program(&config.enter)?, program(&config.exit)?, duration);
+            /* Deck::from_file has already checked the name */
+            if let Some(ref name) = config.easing {
+                transition = transition.with_easing(easing::by_name(name.get_ref()).unwrap());
+            }
+            manager.set_transition(Some(transition));
+        }
+
+        Ok(manager)
+    }
+}
+
+/* Deck::from_file has already validated the channel numbers and sources */
+fn channel_binding(deck: &Deck, config: &SlideConfig, uploader: Option<&BackgroundUploader>)
+    -> Result<ChannelBinding, DeckError> {
+    let mut binding = ChannelBinding::new();
+    for (name, channel) in &config.channels {
+        let index: usize = name.parse().unwrap();
+        let source = if let Some(ref faces) = channel.faces {
+            let paths: Vec<PathBuf> = faces.iter().map(|face| deck.resolve(face.get_ref())).collect();
+            let faces = [&*paths[0], &*paths[1], &*paths[2], &*paths[3], &*paths[4], &*paths[5]];
+            TextureSource::Cubemap(TextureCubemap::from_faces(faces)?)
+        } else if let (ChannelKind::Gif, Some(ref texture)) = (channel.kind, &channel.texture) {
+            TextureSource::Animated(AnimatedTexture::from_gif(&deck.resolve(texture.get_ref()))?)
+        } else if let Some(ref texture) = channel.texture {
+            let path = deck.resolve(texture.get_ref());
+            match uploader {
+                Some(uploader) => {
+                    TextureSource::Pending(Texture2D::placeholder(), uploader.upload_file_async(path))
+                }
+                None => TextureSource::File(Texture2D::from_file(&path)?),
+            }
+        } else if let Some(size) = channel.noise {
+            let seed = channel.seed.unwrap_or(index as u64);
+            TextureSource::noise(channel.noise_type, size, seed)
+        } else {
+            video_source(&deck.resolve(channel.video.as_ref().unwrap().get_ref()))?
+        };
+        binding.set(index, Some(source));
+    }
+    Ok(binding)
+}
+
+#[cfg(feature = "video")]
+fn video_source(path: &Path) -> Result<TextureSource, DeckError> {
+    Ok(TextureSource::Video(VideoSlide::from_file(path)?))
+}
+
+#[cfg(not(feature = "video"))]
+fn video_source(_path: &Path) -> Result<TextureSource, DeckError> {
+    unreachable!("Deck::from_file rejects video channels")
+}
+
+/* TOML has dates of its own, which toml hands over as a Datetime rather than a string */
+fn deserialize_date<'de, D>(deserializer: D) -> Result<NaiveDate, D::Error>
+    where D: Deserializer<'de> {
+    let datetime = Datetime::deserialize(deserializer)?;
+    let text = datetime.to_string();
+    NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+        .map_err(|_| de::Error::custom(format!("expected a date such as 2024-05-01, found {}", text)))
+}
+
+/* "NAME" defines NAME as 1; "NAME=VALUE" defines it as VALUE */
+fn parse_define(text: &str) -> Result<(String, String), &'static str> {
+    let (name, value) = match text.find('=') {
+        Some(i) => (text[..i].trim(), text[i + 1..].trim()),
+        None => (text.trim(), "1"),
+    };
+    let starts_well = name.chars().next().map_or(false, |c| c.is_ascii_alphabetic() || c == '_');
+    if !starts_well || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err("names are letters, digits and underscores, not starting with a digit");
+    }
+    if value.is_empty() || value.contains('\n') {
+        return Err("values must be a single, non-empty line");
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/* "#rgb", "#rrggbb" or "#rrggbbaa", as in CSS */
+fn parse_color(text: &str) -> Result<[f32; 4], &'static str> {
+    let digits = match text.strip_prefix('#') {
+        Some(digits) => digits,
+        None => return Err("colours start with #"),
+    };
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("use hexadecimal digits 0-9 and a-f");
+    }
+    let expanded: String = match digits.len() {
+        3 => digits.chars().flat_map(|c| vec![c, c]).collect(),
+        6 | 8 => digits.to_string(),
+        _ => return Err("use 3, 6 or 8 hexadecimal digits"),
+    };
+
+    let mut color = [1.0; 4];
+    for (i, component) in color.iter_mut().enumerate().take(expanded.len() / 2) {
+        let byte = u8::from_str_radix(&expanded[i * 2..i * 2 + 2], 16).unwrap();
+        *component = f32::from(byte) / 255.0;
+    }
+    Ok(color)
+}
+
+/* Numbers map to floats, arrays of two to four numbers to vectors */
+fn uniform_value(value: &Value) -> Option<UniformValue> {
+    fn number(value: &Value) -> Option<f32> {
+        match *value {
+            Value::Float(f) => Some(f as f32),
+            Value::Integer(i) => Some(i as f32),
+            _ => None,
+        }
+    }
+
+    match *value {
+        Value::Boolean(b) => Some(UniformValue::Bool(b)),
+        Value::Array(ref items) => {
+            let items: Option<Vec<f32>> = items.iter().map(number).collect();
+            match items {
+                Some(ref v) if v.len() == 2 => Some(UniformValue::Vec2([v[0], v[1]])),
+                Some(ref v) if v.len() == 3 => Some(UniformValue::Vec3([v[0], v[1], v[2]])),
+                Some(ref v) if v.len() == 4 => Some(UniformValue::Vec4([v[0], v[1], v[2], v[3]])),
+                _ => None,
+            }
+        }
+        ref other => number(other).map(UniformValue::Float),
+    }
+}
+
+fn toml_value(value: &UniformValue) -> Value {
+    /* Going through the shortest decimal form keeps 0.1 from being written as 0.10000000149 */
+    let float = |f: f32| Value::Float(f.to_string().parse().unwrap_or_else(|_| f64::from(f)));
+    let array = |v: &[f32]| Value::Array(v.iter().cloned().map(float).collect());
+    match *value {
+        UniformValue::Float(f) => float(f),
+        UniformValue::Vec2(ref v) => array(v),
+        UniformValue::Vec3(ref v) => array(v),
+        UniformValue::Vec4(ref v) => array(v),
+        UniformValue::Bool(b) => Value::Boolean(b),
+    }
+}
+
+fn line_number(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
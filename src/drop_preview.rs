@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+
+use errors::ShaderError;
+use quad::PASSTHROUGH_VERTEX_SHADER;
+use shader::{ActivatedShader, ShaderProgram, ShaderSource, ShaderStage};
+use shadertoy::ShaderToyUniforms;
+use slide::{Slide, SlideKind};
+
+/* A shader file dropped onto the window, drawn in place of the current slide until it is adopted
+ * as that slide's shader or discarded. It is the slide that is current at the time which adopts
+ * it, not necessarily the one it was dropped on. */
+pub struct DroppedShader {
+    pub path: PathBuf,
+    pub program: ShaderProgram,
+}
+
+/* Whether `path` is something that can be dropped: a .frag or .vert file */
+pub fn is_shader_file(path: &Path) -> bool {
+    path.extension().map_or(false, |extension| extension == "frag" || extension == "vert")
+}
+
+impl DroppedShader {
+    /* Builds a program from the dropped file and the other stage of `slide`'s program: a .frag
+     * goes with the slide's vertex shader, or a passthrough one if it has none, and a .vert with
+     * the slide's fragment shader */
+    pub fn load(path: &Path, slide: &Slide) -> Result<DroppedShader, ShaderError> {
+        let dropped = ShaderSource::File(path.to_path_buf());
+        let existing = |stage| slide.program.stage_source(stage).cloned();
+        let (vertex, fragment) = if path.extension().map_or(false, |extension| extension == "vert") {
+            let fragment = existing(ShaderStage::Fragment).ok_or_else(|| {
+                ShaderError::Link("the slide has no fragment shader to go with the vertex shader".to_string())
+            })?;
+            (dropped, fragment)
+        } else {
+            /* Slides without a vertex shader of their own draw the quad as it is */
+            let vertex = existing(ShaderStage::Vertex)
+                .unwrap_or_else(|| ShaderSource::Inline(PASSTHROUGH_VERTEX_SHADER.to_string()));
+            (vertex, dropped)
+        };
+
+        let program = ShaderProgram::new(vertex, fragment, None, None, None)?;
+        for &(ref name, ref value) in &slide.uniforms {
+            program.set_uniform(name, value);
+        }
+        Ok(DroppedShader { path: path.to_path_buf(), program })
+    }
+
+    /* Draws with the slide's channels and animations, as the slide itself would */
+    pub fn render<F>(&self, slide: &Slide, shadertoy: &ShaderToyUniforms, f: F)
+        where F: Fn(&ActivatedShader) {
+        shadertoy.upload_to(&self.program);
+        slide.channels.bind_all(&self.program);
+        slide.animations.upload_to(&self.program);
+        self.program.activate(f);
+    }
+
+    /* Makes the program `slide`'s own for the rest of the presentation; the deck is not changed.
+     * Image, code and fill slides become plain shader slides, as their built-in program is what
+     * has been replaced. */
+    pub fn adopt(self, slide: &mut Slide) {
+        slide.program = self.program;
+        slide.kind = SlideKind::Shader;
+        slide.apply_uniforms();
+    }
+}
@@ -0,0 +1,46 @@
+use std::f32::consts::PI;
+
+/* Maps linear progress in [0, 1] to eased progress. Every curve starts at 0 and ends at 1, though
+ * some overshoot in between. */
+pub type EasingFn = fn(f32) -> f32;
+
+/* The names the deck manifest accepts for `easing` */
+pub const NAMES: &[&str] = &["linear", "ease_in_quad", "ease_out_cubic", "ease_in_out_sine", "spring"];
+
+pub fn by_name(name: &str) -> Option<EasingFn> {
+    let easing: EasingFn = match name {
+        "linear" => linear,
+        "ease_in_quad" => ease_in_quad,
+        "ease_out_cubic" => ease_out_cubic,
+        "ease_in_out_sine" => ease_in_out_sine,
+        "spring" => spring,
+        _ => return None,
+    };
+    Some(easing)
+}
+
+pub fn linear(t: f32) -> f32 {
+    t
+}
+
+pub fn ease_in_quad(t: f32) -> f32 {
+    t * t
+}
+
+pub fn ease_out_cubic(t: f32) -> f32 {
+    let u = 1.0 - t;
+    1.0 - u * u * u
+}
+
+pub fn ease_in_out_sine(t: f32) -> f32 {
+    (1.0 - (PI * t).cos()) / 2.0
+}
+
+/* Overshoots and settles like a damped spring. The oscillation has all but died out by the end,
+ * so 1 is returned exactly there rather than jumping from the last wobble. */
+pub fn spring(t: f32) -> f32 {
+    if t >= 1.0 {
+        return 1.0;
+    }
+    1.0 - (-6.0 * t).exp() * (4.5 * PI * t).cos()
+}
@@ -1 +1,773 @@
-pub type Result<T> = ::std::result::Result<T, Box<::std::error::Error>>;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use gl::types::GLenum;
+use image;
+use toml;
+
+use easing;
+use shader::ShaderStage;
+
+pub type Result<T> = ::std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io(io::Error),
+    Compile { stage: ShaderStage, log: String },
+    Link(String),
+    Uniform(String),
+    Preprocess(PreprocessError),
+    /* A program binary that can't be used, see ShaderProgram::load_binary */
+    Binary(String),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShaderError::Io(ref e) => write!(f, "ERROR::SHADER::IO\n{}", e),
+            ShaderError::Compile { stage, ref log } => write!(
+                f,
+                "ERROR::SHADER::{}::COMPILATION_FAILED\n{}",
+                stage.to_string().to_uppercase(),
+                log
+            ),
+            ShaderError::Link(ref log) => {
+                write!(f, "ERROR::SHADER::PROGRAM::COMPILATION_FAILED\n{}", log)
+            }
+            ShaderError::Uniform(ref msg) => write!(f, "{}", msg),
+            ShaderError::Preprocess(ref e) => write!(f, "ERROR::SHADER::PREPROCESS\n{}", e),
+            ShaderError::Binary(ref msg) => write!(f, "ERROR::SHADER::PROGRAM::BINARY\n{}", msg),
+        }
+    }
+}
+
+impl Error for ShaderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ShaderError::Io(ref e) => Some(e),
+            ShaderError::Preprocess(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/* io::Error can't be cloned, so a copy of one keeps only its kind and message */
+impl Clone for ShaderError {
+    fn clone(&self) -> ShaderError {
+        match *self {
+            ShaderError::Io(ref e) => ShaderError::Io(io::Error::new(e.kind(), e.to_string())),
+            ShaderError::Compile { stage, ref log } => ShaderError::Compile { stage, log: log.clone() },
+            ShaderError::Link(ref log) => ShaderError::Link(log.clone()),
+            ShaderError::Uniform(ref msg) => ShaderError::Uniform(msg.clone()),
+            ShaderError::Preprocess(ref e) => ShaderError::Preprocess(e.clone()),
+            ShaderError::Binary(ref msg) => ShaderError::Binary(msg.clone()),
+        }
+    }
+}
+
+impl From<io::Error> for ShaderError {
+    fn from(e: io::Error) -> ShaderError {
+        ShaderError::Io(e)
+    }
+}
+
+impl From<PreprocessError> for ShaderError {
+    fn from(e: PreprocessError) -> ShaderError {
+        ShaderError::Preprocess(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum DeckError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    InvalidPath { manifest: PathBuf, line: usize, path: String },
+    InvalidUniform { manifest: PathBuf, line: usize, name: String },
+    MissingField { manifest: PathBuf, slide: usize, field: &'static str },
+    UnsupportedType { manifest: PathBuf, slide: usize, feature: &'static str },
+    InvalidChannel { manifest: PathBuf, slide: usize, channel: String, reason: &'static str },
+    InvalidColor { manifest: PathBuf, line: usize, color: String, reason: &'static str },
+    UnknownEasing { manifest: PathBuf, line: usize, name: String },
+    InvalidDefine { manifest: PathBuf, line: usize, define: String, reason: &'static str },
+    InvalidAnimation { manifest: PathBuf, slide: usize, name: String, reason: &'static str },
+    InvalidResolution { manifest: PathBuf, slide: usize },
+    InvalidDuration { manifest: PathBuf, slide: usize },
+    DuplicateName { manifest: PathBuf, line: usize, name: String },
+    Shader(ShaderError),
+    Texture(TextureError),
+    Video(VideoError),
+    Code(CodeError),
+    InvalidAspectRatio(PathBuf),
+    InvalidCursorIdle(PathBuf),
+    InvalidTransitionDuration(PathBuf),
+    InvalidLoop(PathBuf),
+    InvalidMidi { manifest: PathBuf, uniform: String, reason: &'static str },
+    Empty,
+}
+
+impl fmt::Display for DeckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DeckError::Io(ref e) => write!(f, "Cannot read deck manifest: {}", e),
+            DeckError::Parse(ref e) => write!(f, "Cannot parse deck manifest: {}", e),
+            DeckError::InvalidPath { ref manifest, line, ref path } => write!(
+                f,
+                "{}:{}: cannot find file {}",
+                manifest.display(),
+                line,
+                path
+            ),
+            DeckError::InvalidUniform { ref manifest, line, ref name } => write!(
+                f,
+                "{}:{}: uniform {} must be a number, a boolean, or an array of 2 to 4 numbers",
+                manifest.display(),
+                line,
+                name
+            ),
+            DeckError::InvalidResolution { ref manifest, slide } => write!(
+                f,
+                "{}: slide {} needs a resolution width and height greater than zero",
+                manifest.display(),
+                slide + 1
+            ),
+            DeckError::InvalidDuration { ref manifest, slide } => write!(
+                f,
+                "{}: slide {} duration_secs must be greater than zero",
+                manifest.display(),
+                slide + 1
+            ),
+            DeckError::DuplicateName { ref manifest, line, ref name } => write!(
+                f,
+                "{}:{}: another slide is already called {}",
+                manifest.display(),
+                line,
+                name
+            ),
+            DeckError::MissingField { ref manifest, slide, field } => write!(
+                f,
+                "{}: slide {} needs a {} for its type",
+                manifest.display(),
+                slide + 1,
+                field
+            ),
+            DeckError::UnsupportedType { ref manifest, slide, feature } => write!(
+                f,
+                "{}: slide {} needs a build with the {} feature enabled",
+                manifest.display(),
+                slide + 1,
+                feature
+            ),
+            DeckError::InvalidChannel { ref manifest, slide, ref channel, reason } => write!(
+                f,
+                "{}: slide {} channel {}: {}",
+                manifest.display(),
+                slide + 1,
+                channel,
+                reason
+            ),
+            DeckError::InvalidColor { ref manifest, line, ref color, reason } => write!(
+                f,
+                "{}:{}: invalid colour {:?}: {}",
+                manifest.display(),
+                line,
+                color,
+                reason
+            ),
+            DeckError::UnknownEasing { ref manifest, line, ref name } => write!(
+                f,
+                "{}:{}: unknown easing {}, expected one of {}",
+                manifest.display(),
+                line,
+                name,
+                easing::NAMES.join(", ")
+            ),
+            DeckError::InvalidDefine { ref manifest, line, ref define, reason } => write!(
+                f,
+                "{}:{}: invalid define {:?}: {}",
+                manifest.display(),
+                line,
+                define,
+                reason
+            ),
+            DeckError::InvalidAnimation { ref manifest, slide, ref name, reason } => write!(
+                f,
+                "{}: slide {} animation {}: {}",
+                manifest.display(),
+                slide + 1,
+                name,
+                reason
+            ),
+            DeckError::Shader(ref e) => write!(f, "{}", e),
+            DeckError::Texture(ref e) => write!(f, "{}", e),
+            DeckError::Video(ref e) => write!(f, "{}", e),
+            DeckError::Code(ref e) => write!(f, "{}", e),
+            DeckError::InvalidAspectRatio(ref manifest) => write!(
+                f,
+                "{}: aspect_ratio must be a width and height greater than zero",
+                manifest.display()
+            ),
+            DeckError::InvalidCursorIdle(ref manifest) => write!(
+                f,
+                "{}: cursor_idle_secs must be greater than zero",
+                manifest.display()
+            ),
+            DeckError::InvalidTransitionDuration(ref manifest) => write!(
+                f,
+                "{}: transition duration_secs must be greater than zero",
+                manifest.display()
+            ),
+            DeckError::InvalidLoop(ref manifest) => write!(
+                f,
+                "{}: loop needs a start from 1 and an end no earlier than it, within the deck",
+                manifest.display()
+            ),
+            DeckError::InvalidMidi { ref manifest, ref uniform, reason } => write!(
+                f,
+                "{}: MIDI mapping for {}: {}",
+                manifest.display(),
+                uniform,
+                reason
+            ),
+            DeckError::Empty => write!(f, "Deck manifest does not contain any slides"),
+        }
+    }
+}
+
+impl Error for DeckError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            DeckError::Io(ref e) => Some(e),
+            DeckError::Parse(ref e) => Some(e),
+            DeckError::Shader(ref e) => Some(e),
+            DeckError::Texture(ref e) => Some(e),
+            DeckError::Video(ref e) => Some(e),
+            DeckError::Code(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DeckError {
+    fn from(e: io::Error) -> DeckError {
+        DeckError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for DeckError {
+    fn from(e: toml::de::Error) -> DeckError {
+        DeckError::Parse(e)
+    }
+}
+
+impl From<ShaderError> for DeckError {
+    fn from(e: ShaderError) -> DeckError {
+        DeckError::Shader(e)
+    }
+}
+
+impl From<TextureError> for DeckError {
+    fn from(e: TextureError) -> DeckError {
+        DeckError::Texture(e)
+    }
+}
+
+impl From<VideoError> for DeckError {
+    fn from(e: VideoError) -> DeckError {
+        DeckError::Video(e)
+    }
+}
+
+impl From<CodeError> for DeckError {
+    fn from(e: CodeError) -> DeckError {
+        DeckError::Code(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    Io(io::Error),
+    Image(image::ImageError),
+    /* Faces of a cubemap that can't be put together, e.g. of different sizes */
+    Cubemap(String),
+    /* A .cube file that can't be read as a 3D LUT */
+    Lut { path: PathBuf, line: usize, reason: String },
+    /* An animated GIF that can't be decoded, or has too many frames */
+    Gif { path: PathBuf, reason: String },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TextureError::Io(ref e) => write!(f, "Cannot read texture: {}", e),
+            TextureError::Image(ref e) => write!(f, "Cannot decode texture: {}", e),
+            TextureError::Cubemap(ref msg) => write!(f, "Invalid cubemap: {}", msg),
+            TextureError::Lut { ref path, line, ref reason } => {
+                write!(f, "{}:{}: invalid LUT: {}", path.display(), line, reason)
+            }
+            TextureError::Gif { ref path, ref reason } => {
+                write!(f, "Cannot decode animated GIF {}: {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+impl Error for TextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            TextureError::Io(ref e) => Some(e),
+            TextureError::Image(ref e) => Some(e),
+            TextureError::Cubemap(_) | TextureError::Lut { .. } | TextureError::Gif { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for TextureError {
+    fn from(e: io::Error) -> TextureError {
+        TextureError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for TextureError {
+    fn from(e: image::ImageError) -> TextureError {
+        TextureError::Image(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum GlError {
+    IncompleteFramebuffer(GLenum),
+}
+
+impl fmt::Display for GlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GlError::IncompleteFramebuffer(status) => {
+                write!(f, "Framebuffer is incomplete (status 0x{:x})", status)
+            }
+        }
+    }
+}
+
+impl Error for GlError {}
+
+/* From a RenderBackend */
+#[derive(Debug)]
+pub enum BackendError {
+    Shader(ShaderError),
+    /* What failed and the VkResult it failed with */
+    Vulkan(String),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BackendError::Shader(ref e) => write!(f, "{}", e),
+            BackendError::Vulkan(ref msg) => write!(f, "Vulkan: {}", msg),
+        }
+    }
+}
+
+impl Error for BackendError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            BackendError::Shader(ref e) => Some(e),
+            BackendError::Vulkan(_) => None,
+        }
+    }
+}
+
+impl From<ShaderError> for BackendError {
+    fn from(e: ShaderError) -> BackendError {
+        BackendError::Shader(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum LutError {
+    Texture(TextureError),
+    Shader(ShaderError),
+}
+
+impl fmt::Display for LutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LutError::Texture(ref e) => write!(f, "{}", e),
+            LutError::Shader(ref e) => write!(f, "Cannot build the LUT pass: {}", e),
+        }
+    }
+}
+
+impl Error for LutError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LutError::Texture(ref e) => Some(e),
+            LutError::Shader(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<TextureError> for LutError {
+    fn from(e: TextureError) -> LutError {
+        LutError::Texture(e)
+    }
+}
+
+impl From<ShaderError> for LutError {
+    fn from(e: ShaderError) -> LutError {
+        LutError::Shader(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum RenderGraphError {
+    DuplicatePass(String),
+    UnknownInput { pass: String, input: String },
+    /* The input draws straight to the screen, so there is nothing to sample */
+    NoOutput { pass: String, input: String },
+    /* The passes in the cycle, starting and ending with the same one */
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for RenderGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RenderGraphError::DuplicatePass(ref name) => {
+                write!(f, "There is more than one render pass called {}", name)
+            }
+            RenderGraphError::UnknownInput { ref pass, ref input } => {
+                write!(f, "Render pass {} reads from {}, which is not a pass", pass, input)
+            }
+            RenderGraphError::NoOutput { ref pass, ref input } => {
+                write!(f, "Render pass {} reads from {}, which has no framebuffer", pass, input)
+            }
+            RenderGraphError::Cycle(ref passes) => {
+                write!(f, "Render passes depend on each other: {}", passes.join(" -> "))
+            }
+        }
+    }
+}
+
+impl Error for RenderGraphError {}
+
+#[derive(Debug)]
+pub enum WindowError {
+    Create,
+    NoMonitor(usize),
+}
+
+impl fmt::Display for WindowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WindowError::Create => write!(f, "Cannot create window"),
+            WindowError::NoMonitor(index) => write!(f, "There is no monitor {}", index + 1),
+        }
+    }
+}
+
+impl Error for WindowError {}
+
+#[derive(Debug)]
+pub enum NavigationError {
+    /* No presented slide has this name, either in the deck or because it was skipped */
+    UnknownName(String),
+}
+
+impl fmt::Display for NavigationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NavigationError::UnknownName(ref name) => write!(f, "There is no slide called {}", name),
+        }
+    }
+}
+
+impl Error for NavigationError {}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Gl(GlError),
+    Backend(BackendError),
+    NoSuchSlide(usize),
+    Pdf(String),
+    Gif(String),
+    Video(String),
+    Audio(AudioError),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExportError::Io(ref e) => write!(f, "Cannot write image: {}", e),
+            ExportError::Gl(ref e) => write!(f, "Cannot render slide: {}", e),
+            ExportError::Backend(ref e) => write!(f, "Cannot render slide: {}", e),
+            ExportError::NoSuchSlide(index) => write!(f, "There is no slide {}", index + 1),
+            ExportError::Pdf(ref e) => write!(f, "Cannot write PDF: {}", e),
+            ExportError::Gif(ref e) => write!(f, "Cannot write GIF: {}", e),
+            ExportError::Video(ref e) => write!(f, "Cannot write video: {}", e),
+            ExportError::Audio(ref e) => write!(f, "Cannot record audio: {}", e),
+        }
+    }
+}
+
+impl Error for ExportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ExportError::Io(ref e) => Some(e),
+            ExportError::Gl(ref e) => Some(e),
+            ExportError::Backend(ref e) => Some(e),
+            ExportError::Audio(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> ExportError {
+        ExportError::Io(e)
+    }
+}
+
+impl From<BackendError> for ExportError {
+    fn from(e: BackendError) -> ExportError {
+        ExportError::Backend(e)
+    }
+}
+
+impl From<GlError> for ExportError {
+    fn from(e: GlError) -> ExportError {
+        ExportError::Gl(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum PreprocessError {
+    Io { path: PathBuf, error: io::Error },
+    NotFound { file: Option<PathBuf>, line: usize, name: String },
+    Malformed { file: Option<PathBuf>, line: usize, text: String },
+    Circular(Vec<PathBuf>),
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PreprocessError::Io { ref path, ref error } => {
+                write!(f, "Cannot read include {}: {}", path.display(), error)
+            }
+            PreprocessError::NotFound { ref file, line, ref name } => {
+                write_location(f, file, line)?;
+                write!(f, "cannot find include {}", name)
+            }
+            PreprocessError::Malformed { ref file, line, ref text } => {
+                write_location(f, file, line)?;
+                write!(f, "malformed include directive: {}", text.trim())
+            }
+            PreprocessError::Circular(ref chain) => {
+                let names: Vec<String> = chain.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "Circular include: {}", names.join(" -> "))
+            }
+        }
+    }
+}
+
+fn write_location(f: &mut fmt::Formatter, file: &Option<PathBuf>, line: usize) -> fmt::Result {
+    match *file {
+        Some(ref file) => write!(f, "{}:{}: ", file.display(), line),
+        None => write!(f, "line {}: ", line),
+    }
+}
+
+impl Error for PreprocessError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            PreprocessError::Io { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Clone for PreprocessError {
+    fn clone(&self) -> PreprocessError {
+        match *self {
+            PreprocessError::Io { ref path, ref error } => PreprocessError::Io {
+                path: path.clone(),
+                error: io::Error::new(error.kind(), error.to_string()),
+            },
+            PreprocessError::NotFound { ref file, line, ref name } => {
+                PreprocessError::NotFound { file: file.clone(), line, name: name.clone() }
+            }
+            PreprocessError::Malformed { ref file, line, ref text } => {
+                PreprocessError::Malformed { file: file.clone(), line, text: text.clone() }
+            }
+            PreprocessError::Circular(ref chain) => PreprocessError::Circular(chain.clone()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AudioError {
+    NoDevice,
+    Config(String),
+    Stream(String),
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AudioError::NoDevice => write!(f, "No audio input device available"),
+            AudioError::Config(ref e) => write!(f, "Cannot configure audio input: {}", e),
+            AudioError::Stream(ref e) => write!(f, "Cannot open audio input stream: {}", e),
+        }
+    }
+}
+
+impl Error for AudioError {}
+
+#[derive(Debug)]
+pub enum MidiError {
+    Init(String),
+    /* No port matched the requested name, or there are no ports at all */
+    NoDevice { requested: Option<String>, available: Vec<String> },
+    Connect(String),
+}
+
+impl fmt::Display for MidiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MidiError::Init(ref e) => write!(f, "Cannot start MIDI input: {}", e),
+            MidiError::NoDevice { requested: None, .. } => write!(f, "No MIDI input ports available"),
+            MidiError::NoDevice { requested: Some(ref name), ref available } => write!(
+                f,
+                "No MIDI input port matches {}; available ports: {}",
+                name,
+                available.join(", ")
+            ),
+            MidiError::Connect(ref e) => write!(f, "Cannot open MIDI input port: {}", e),
+        }
+    }
+}
+
+impl Error for MidiError {}
+
+#[derive(Debug)]
+pub enum VideoError {
+    NoVideoStream(PathBuf),
+    Decode(String),
+}
+
+impl fmt::Display for VideoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VideoError::NoVideoStream(ref path) => {
+                write!(f, "{} does not contain a video stream", path.display())
+            }
+            VideoError::Decode(ref e) => write!(f, "Cannot decode video: {}", e),
+        }
+    }
+}
+
+impl Error for VideoError {}
+
+#[derive(Debug)]
+pub enum TextError {
+    Io(io::Error),
+    Font(&'static str),
+    Shader(ShaderError),
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TextError::Io(ref e) => write!(f, "Cannot read font: {}", e),
+            TextError::Font(e) => write!(f, "Cannot load font: {}", e),
+            TextError::Shader(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for TextError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            TextError::Io(ref e) => Some(e),
+            TextError::Shader(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TextError {
+    fn from(e: io::Error) -> TextError {
+        TextError::Io(e)
+    }
+}
+
+impl From<ShaderError> for TextError {
+    fn from(e: ShaderError) -> TextError {
+        TextError::Shader(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum CodeError {
+    Io(io::Error),
+    Text(TextError),
+    UnknownTheme(String),
+}
+
+impl fmt::Display for CodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CodeError::Io(ref e) => write!(f, "Cannot read source file: {}", e),
+            CodeError::Text(ref e) => write!(f, "{}", e),
+            CodeError::UnknownTheme(ref name) => write!(f, "Unknown highlighting theme {}", name),
+        }
+    }
+}
+
+impl Error for CodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            CodeError::Io(ref e) => Some(e),
+            CodeError::Text(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CodeError {
+    fn from(e: io::Error) -> CodeError {
+        CodeError::Io(e)
+    }
+}
+
+impl From<TextError> for CodeError {
+    fn from(e: TextError) -> CodeError {
+        CodeError::Text(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum ContextError {
+    Load(String),
+    NoDisplay,
+    NoConfig,
+    Egl { call: &'static str, error: String },
+    Unsupported,
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ContextError::Load(ref e) => write!(f, "Cannot load libEGL: {}", e),
+            ContextError::NoDisplay => write!(f, "No EGL display available"),
+            ContextError::NoConfig => write!(f, "No EGL config supports OpenGL pbuffers"),
+            ContextError::Egl { call, ref error } => write!(f, "{} failed: {}", call, error),
+            ContextError::Unsupported => {
+                write!(f, "Headless rendering is only supported on Linux")
+            }
+        }
+    }
+}
+
+impl Error for ContextError {}
@@ -0,0 +1,83 @@
+use serde_json;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{channel, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use deck::DeckMetadata;
+
+/* What happened, written as the "event" field. Slides count from 1 in the order presented, like
+ * the rest of the UI. */
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /* The first line of every log, saying which deck it is for */
+    Header { deck: String, metadata: Option<DeckMetadata> },
+    Start { slide: usize },
+    SlideAdvance { from: usize, to: usize },
+    /* `shader` is the file that changed, or the slide's fragment shader for a manual reload */
+    Reload { shader: String, ok: bool },
+    Quit { slide: usize },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    /* Milliseconds since the Unix epoch */
+    ts: u64,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/* Writes events to a JSON Lines file for looking at afterwards, e.g. how long each slide was up
+ * for, or whether a shader kept being reloaded. Writing happens on a background thread so a slow
+ * disk never holds up a frame. */
+pub struct EventLog {
+    events: Option<Sender<(u64, Event)>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl EventLog {
+    /* Truncates any existing file at `path` */
+    pub fn create(path: &Path) -> io::Result<EventLog> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let (events, queue) = channel();
+        let writer = thread::spawn(move || {
+            for (ts, event) in queue {
+                let line = serde_json::to_string(&Line { ts, event: &event })
+                    .expect("Cannot serialise event");
+                if let Err(e) = writeln!(file, "{}", line) {
+                    println!("Warning: stopped writing the event log: {}", e);
+                    return;
+                }
+            }
+            file.flush().ok();
+        });
+
+        Ok(EventLog {
+            events: Some(events),
+            writer: Some(writer),
+        })
+    }
+
+    pub fn log(&self, event: Event) {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        if let Some(ref events) = self.events {
+            /* The writer has only gone if it failed, and it said so then */
+            events.send((ts, event)).ok();
+        }
+    }
+}
+
+impl Drop for EventLog {
+    /* Waits for the events already logged to be written */
+    fn drop(&mut self) {
+        self.events = None;
+        if let Some(writer) = self.writer.take() {
+            writer.join().ok();
+        }
+    }
+}
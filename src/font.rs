@@ -0,0 +1,49 @@
+/* A tiny 5x7 bitmap font covering what the overlays need: digits and a little punctuation. Each
+ * glyph is seven rows, top first, with the leftmost pixel in bit 4. */
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+fn glyph(c: char) -> [u8; 7] {
+    match c {
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '/' => [0b00001, 0b00010, 0b00010, 0b00100, 0b01000, 0b01000, 0b10000],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        _ => [0; 7],
+    }
+}
+
+/* Renders `text` as RGBA8 pixels with a one pixel gap between glyphs, returning the width,
+ * height and the rows bottom first, ready for Texture2D::upload. Characters without a glyph
+ * are left blank. */
+pub fn rasterize(text: &str, foreground: [u8; 4], background: [u8; 4]) -> (u32, u32, Vec<u8>) {
+    let count = text.chars().count().max(1) as u32;
+    let width = count * (GLYPH_WIDTH + 1) - 1;
+    let height = GLYPH_HEIGHT;
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+
+    for row in (0..GLYPH_HEIGHT as usize).rev() {
+        for (i, c) in text.chars().enumerate() {
+            if i > 0 {
+                pixels.extend_from_slice(&background);
+            }
+            let bits = glyph(c)[row];
+            for column in (0..GLYPH_WIDTH).rev() {
+                let colour = if bits & (1 << column) != 0 { foreground } else { background };
+                pixels.extend_from_slice(&colour);
+            }
+        }
+    }
+    pixels.resize((width * height * 4) as usize, 0);
+    (width, height, pixels)
+}
@@ -0,0 +1,280 @@
+extern crate gl;
+use gl::types::*;
+use std::cell::Cell;
+use std::os::raw::c_void;
+use std::ptr;
+
+use errors::GlError;
+use texture::Texture2D;
+
+/* Where drawing actually goes when a framebuffer has more than one sample per pixel. It is
+ * resolved into the single sampled colour texture on unbind. */
+#[derive(Debug)]
+struct Multisample {
+    id: GLuint,
+    color: GLuint,
+    depth: GLuint,
+    samples: u32,
+}
+
+#[derive(Debug)]
+pub struct Framebuffer {
+    id: GLuint,
+    depth: GLuint,
+    color: Texture2D,
+    multisample: Option<Multisample>,
+    previous_viewport: Cell<[GLint; 4]>,
+    /* So framebuffers can be bound while another one is, e.g. for transitions */
+    previous_binding: Cell<GLuint>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Result<Framebuffer, GlError> {
+        Framebuffer::with_samples(width, height, 1)
+    }
+
+    pub fn with_samples(width: u32, height: u32, samples: u32) -> Result<Framebuffer, GlError> {
+        let (mut id, mut depth) = (0, 0);
+        unsafe {
+            gl::GenFramebuffers(1, &mut id);
+            gl::GenRenderbuffers(1, &mut depth);
+        }
+
+        let multisample = if samples > 1 {
+            let mut multisample = Multisample { id: 0, color: 0, depth: 0, samples };
+            unsafe {
+                gl::GenFramebuffers(1, &mut multisample.id);
+                gl::GenRenderbuffers(1, &mut multisample.color);
+                gl::GenRenderbuffers(1, &mut multisample.depth);
+            }
+            Some(multisample)
+        } else {
+            None
+        };
+
+        let framebuffer = Framebuffer {
+            id,
+            depth,
+            color: Texture2D::from_rgba(width, height, None),
+            multisample,
+            previous_viewport: Cell::new([0; 4]),
+            previous_binding: Cell::new(0),
+        };
+        framebuffer.attach()?;
+        Ok(framebuffer)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), GlError> {
+        if width == self.width() && height == self.height() {
+            return Ok(());
+        }
+        self.color.upload(width, height, None);
+        self.attach()
+    }
+
+    /* (Re)allocates the depth storage and attaches both buffers at the current size */
+    fn attach(&self) -> Result<(), GlError> {
+        let (width, height) = (self.width() as GLsizei, self.height() as GLsizei);
+        unsafe {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH24_STENCIL8, width, height);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                self.color.id(),
+                0,
+                );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                self.depth,
+                );
+            check_status()?;
+
+            if let Some(ref multisample) = self.multisample {
+                let samples = multisample.samples as GLsizei;
+                for &(buffer, format) in &[
+                    (multisample.color, gl::RGBA8),
+                    (multisample.depth, gl::DEPTH24_STENCIL8),
+                ] {
+                    gl::BindRenderbuffer(gl::RENDERBUFFER, buffer);
+                    gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples, format, width, height);
+                }
+                gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+                gl::BindFramebuffer(gl::FRAMEBUFFER, multisample.id);
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::RENDERBUFFER,
+                    multisample.color,
+                    );
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    gl::DEPTH_STENCIL_ATTACHMENT,
+                    gl::RENDERBUFFER,
+                    multisample.depth,
+                    );
+                check_status()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn width(&self) -> u32 {
+        self.color.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.color.height()
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.multisample.as_ref().map_or(1, |m| m.samples)
+    }
+
+    /* Binding also sets the viewport to cover the framebuffer; unbind restores the old viewport
+     * and framebuffer */
+    pub fn bind(&self) {
+        unsafe {
+            let mut viewport = [0; 4];
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            self.previous_viewport.set(viewport);
+            let mut binding = 0;
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut binding);
+            self.previous_binding.set(binding as GLuint);
+
+            let id = self.multisample.as_ref().map_or(self.id, |m| m.id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+            gl::Viewport(0, 0, self.width() as GLsizei, self.height() as GLsizei);
+        }
+    }
+
+    /* Also resolves any multisampled drawing into the colour texture */
+    pub fn unbind(&self) {
+        let viewport = self.previous_viewport.get();
+        unsafe {
+            if let Some(ref multisample) = self.multisample {
+                let (width, height) = (self.width() as GLint, self.height() as GLint);
+                gl::BindFramebuffer(gl::READ_FRAMEBUFFER, multisample.id);
+                gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.id);
+                gl::BlitFramebuffer(
+                    0, 0, width, height,
+                    0, 0, width, height,
+                    gl::COLOR_BUFFER_BIT,
+                    gl::NEAREST,
+                    );
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.previous_binding.get());
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+    }
+
+    /* Copies the colour texture into `rect` (x, y, width and height) of the window */
+    pub fn blit_to_window(&self, rect: [GLint; 4]) {
+        let (width, height) = (self.width() as GLint, self.height() as GLint);
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            gl::BlitFramebuffer(
+                0, 0, width, height,
+                rect[0], rect[1], rect[0] + rect[2], rect[1] + rect[3],
+                gl::COLOR_BUFFER_BIT,
+                gl::LINEAR,
+                );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /* Scales `rect` (x, y, width and height) of the window into the whole colour texture, the
+     * reverse of blit_to_window */
+    pub fn blit_from_window(&self, rect: [GLint; 4]) {
+        let (width, height) = (self.width() as GLint, self.height() as GLint);
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.id);
+            gl::BlitFramebuffer(
+                rect[0], rect[1], rect[0] + rect[2], rect[1] + rect[3],
+                0, 0, width, height,
+                gl::COLOR_BUFFER_BIT,
+                gl::LINEAR,
+                );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    pub fn color_texture(&self) -> &Texture2D {
+        &self.color
+    }
+
+    /* Reads back the colour texture as tightly packed RGBA rows, bottom row first. Call this
+     * after unbind so multisampled drawing has been resolved. */
+    pub fn read_pixels(&self) -> Vec<u8> {
+        let mut pixels = vec![0u8; self.width() as usize * self.height() as usize * 4];
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                self.width() as GLsizei,
+                self.height() as GLsizei,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut c_void,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+        pixels
+    }
+
+    /* Like read_pixels, but into the pixel pack buffer `buffer`, which must hold width * height *
+     * 4 bytes. This returns straight away and the copy happens on the GPU, so the buffer can be
+     * mapped a frame or two later without waiting. */
+    pub fn read_pixels_to_buffer(&self, buffer: GLuint) {
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, buffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                self.width() as GLsizei,
+                self.height() as GLsizei,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                ptr::null_mut(),
+            );
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+    }
+}
+
+unsafe fn check_status() -> Result<(), GlError> {
+    let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    if status != gl::FRAMEBUFFER_COMPLETE {
+        return Err(GlError::IncompleteFramebuffer(status));
+    }
+    Ok(())
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+            gl::DeleteRenderbuffers(1, &self.depth);
+            if let Some(ref multisample) = self.multisample {
+                gl::DeleteFramebuffers(1, &multisample.id);
+                gl::DeleteRenderbuffers(1, &multisample.color);
+                gl::DeleteRenderbuffers(1, &multisample.depth);
+            }
+        }
+    }
+}
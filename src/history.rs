@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+
+pub const DEFAULT_HISTORY_CAPACITY: usize = 32;
+
+/* The slides visited, in order, like a browser's back and forward buttons. Going back returns to
+ * the slide shown before the current one however it was reached, which is not necessarily the one
+ * before it in the deck, e.g. after jumping ahead by accident. */
+#[derive(Debug, Clone)]
+pub struct NavigationHistory {
+    /* Most recent last; the oldest entries are dropped beyond `capacity` */
+    back: VecDeque<usize>,
+    /* Most recent last, i.e. the next slide forward() goes to is at the end */
+    forward: Vec<usize>,
+    capacity: usize,
+}
+
+impl NavigationHistory {
+    pub fn with_capacity(capacity: usize) -> NavigationHistory {
+        NavigationHistory {
+            back: VecDeque::with_capacity(capacity),
+            forward: Vec::new(),
+            capacity,
+        }
+    }
+
+    /* Records leaving slide `from` for another. Going somewhere new drops the forward history. */
+    pub fn visit(&mut self, from: usize) {
+        self.forward.clear();
+        self.push_back(from);
+    }
+
+    /* The slide to go back to from `current`, which forward() will then return to */
+    pub fn back(&mut self, current: usize) -> Option<usize> {
+        let index = self.back.pop_back()?;
+        self.forward.push(current);
+        Some(index)
+    }
+
+    /* Replays the slide left by the last back() */
+    pub fn forward(&mut self, current: usize) -> Option<usize> {
+        let index = self.forward.pop()?;
+        self.push_back(current);
+        Some(index)
+    }
+
+    pub fn clear(&mut self) {
+        self.back.clear();
+        self.forward.clear();
+    }
+
+    fn push_back(&mut self, index: usize) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.back.len() == self.capacity {
+            self.back.pop_front();
+        }
+        self.back.push_back(index);
+    }
+}
+
+impl Default for NavigationHistory {
+    fn default() -> NavigationHistory {
+        NavigationHistory::with_capacity(DEFAULT_HISTORY_CAPACITY)
+    }
+}
@@ -1,5 +1,7 @@
 use std::sync::mpsc::{Sender, Receiver, channel};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::thread;
 use notify::{raw_watcher, RawEvent, RecursiveMode, Watcher, op, RecommendedWatcher};
 
 pub struct Hotloader<'a> {
@@ -8,6 +10,11 @@ pub struct Hotloader<'a> {
     watcher: RecommendedWatcher,
 }
 
+#[derive(Debug, Clone)]
+pub struct ReloadEvent {
+    pub path: PathBuf,
+}
+
 impl<'a> Hotloader<'a> {
     pub fn watch(path: &'a str) -> Result<Hotloader, super::notify::Error> {
         let (inputs, messages) = channel();
@@ -15,14 +22,14 @@ impl<'a> Hotloader<'a> {
         watcher.watch(path, RecursiveMode::Recursive)?;
 
         Ok(Hotloader {
-            messages: messages,
-            path: path,
-            watcher: watcher,
+            messages,
+            path,
+            watcher,
         })
     }
 
     pub fn has_event(&self) -> Option<PathBuf> {
-        let result = match self.messages.try_recv() {
+        match self.messages.try_recv() {
             Ok(RawEvent { path: Some(path), op: Ok(op), .. }) => {
                 if op.contains(op::WRITE) {
                     Some(path)
@@ -31,8 +38,46 @@ impl<'a> Hotloader<'a> {
                 }
             },
             _ => None,
-        };
+        }
+    }
+}
+
+/* Watch the given files from a background thread, sending a ReloadEvent on `tx` whenever one of
+ * them is written to. The parent directories are watched rather than the files themselves so
+ * that editors which save by renaming over the original are still picked up. */
+pub fn watch_files<P: AsRef<Path>>(files: &[P], tx: Sender<ReloadEvent>) -> Result<(), super::notify::Error> {
+    let mut targets = Vec::with_capacity(files.len());
+    for file in files {
+        let path = fs::canonicalize(file).map_err(super::notify::Error::Io)?;
+        targets.push(path);
+    }
+    if targets.is_empty() {
+        return Ok(());
+    }
 
-        result
+    let (inputs, messages) = channel();
+    let mut watcher = raw_watcher(inputs)?;
+    for target in &targets {
+        if let Some(parent) = target.parent() {
+            watcher.watch(parent, RecursiveMode::NonRecursive)?;
+        }
     }
+
+    thread::spawn(move || {
+        /* The watcher stops delivering events once dropped, so it lives on this thread */
+        let _watcher = watcher;
+        for event in messages {
+            if let RawEvent { path: Some(path), op: Ok(op), .. } = event {
+                if !(op.contains(op::WRITE) || op.contains(op::CLOSE_WRITE) || op.contains(op::RENAME)) {
+                    continue;
+                }
+
+                if targets.contains(&path) && tx.send(ReloadEvent { path }).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(())
 }
@@ -0,0 +1,92 @@
+extern crate gl;
+use gl::types::*;
+use std::path::Path;
+
+use errors::{ShaderError, TextureError};
+use shader::{ShaderProgram, ShaderSource};
+use texture::Texture2D;
+
+const IMAGE_VERTEX_SHADER: &str = "#version 330 core
+layout (location = 0) in vec3 aPos;
+out vec2 uv;
+
+void main() {
+    uv = aPos.xy * 0.5 + 0.5;
+    gl_Position = vec4(aPos, 1.0);
+}
+";
+
+const IMAGE_FRAGMENT_SHADER: &str = "#version 330 core
+in vec2 uv;
+out vec4 FragColor;
+uniform sampler2D u_image;
+uniform vec2 u_image_size;
+uniform vec2 u_resolution;
+uniform bool u_cover;
+
+void main() {
+    float screen = u_resolution.x / u_resolution.y;
+    float image = u_image_size.x / u_image_size.y;
+    /* Scale the image along whichever axis makes it fit inside (or cover) the viewport */
+    vec2 scale = ((image > screen) == u_cover) ? vec2(image / screen, 1.0) : vec2(1.0, screen / image);
+    vec2 st = (uv - 0.5) / scale + 0.5;
+    if (any(lessThan(st, vec2(0.0))) || any(greaterThan(st, vec2(1.0)))) {
+        FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+    } else {
+        FragColor = texture(u_image, st);
+    }
+}
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFit {
+    /* Show the whole image, letterboxed or pillarboxed in black */
+    #[default]
+    Contain,
+    /* Fill the viewport, cropping whatever overhangs */
+    Cover,
+}
+
+/* A slide showing a static image through the built-in program from `ImageSlide::program` */
+pub struct ImageSlide {
+    pub texture: Texture2D,
+    pub fit: ImageFit,
+}
+
+impl ImageSlide {
+    pub fn from_file(path: &Path, fit: ImageFit) -> Result<ImageSlide, TextureError> {
+        Ok(ImageSlide {
+            texture: Texture2D::from_file(path)?,
+            fit,
+        })
+    }
+
+    pub fn program() -> Result<ShaderProgram, ShaderError> {
+        let program = ShaderProgram::new(
+            ShaderSource::Inline(IMAGE_VERTEX_SHADER.to_string()),
+            ShaderSource::Inline(IMAGE_FRAGMENT_SHADER.to_string()),
+            None,
+            None,
+            None,
+        )?;
+        program.set_uniform_sampler2d("u_image", 0);
+        Ok(program)
+    }
+
+    /* Binds the image and sets the uniforms for the size of the current viewport, which may be
+     * the window or an offscreen framebuffer */
+    pub fn bind(&self, program: &ShaderProgram) {
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        self.texture.bind_to_unit(0);
+        program.set_uniform_vec2(
+            "u_image_size",
+            [self.texture.width() as f32, self.texture.height() as f32],
+        );
+        program.set_uniform_vec2("u_resolution", [viewport[2] as f32, viewport[3] as f32]);
+        program.set_uniform_bool("u_cover", self.fit == ImageFit::Cover);
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use glfw::Key;
+use glfw::modifiers::Modifiers;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavigationAction {
+    Next,
+    Prev,
+    Goto(usize),
+    /* Through the slides visited, rather than the deck's order */
+    Back,
+    Forward,
+    Reload,
+    Quit,
+    ToggleFullscreen,
+    ToggleTimer,
+    ToggleUniformEditor,
+    ToggleStats,
+    /* The current slide's fragment shader, includes and all */
+    CopyShader,
+    /* Keep or drop a shader file dropped onto the window */
+    AdoptShader,
+    DiscardShader,
+}
+
+/* The actions key bindings can be configured for, by the names used in the user config. Goto
+ * stays on the number keys and Home. */
+pub const CONFIGURABLE_ACTIONS: &[(&str, NavigationAction)] = &[
+    ("next", NavigationAction::Next),
+    ("prev", NavigationAction::Prev),
+    ("back", NavigationAction::Back),
+    ("forward", NavigationAction::Forward),
+    ("reload", NavigationAction::Reload),
+    ("quit", NavigationAction::Quit),
+    ("fullscreen", NavigationAction::ToggleFullscreen),
+    ("timer", NavigationAction::ToggleTimer),
+    ("uniform_editor", NavigationAction::ToggleUniformEditor),
+    ("stats", NavigationAction::ToggleStats),
+    ("copy_shader", NavigationAction::CopyShader),
+    ("adopt_shader", NavigationAction::AdoptShader),
+    ("discard_shader", NavigationAction::DiscardShader),
+];
+
+pub struct InputHandler {
+    bindings: HashMap<(Key, Modifiers), NavigationAction>,
+}
+
+impl InputHandler {
+    pub fn new() -> InputHandler {
+        InputHandler { bindings: HashMap::new() }
+    }
+
+    pub fn bind(&mut self, key: Key, modifiers: Modifiers, action: NavigationAction) {
+        self.bindings.insert((key, modifiers), action);
+    }
+
+    pub fn unbind(&mut self, key: Key, modifiers: Modifiers) {
+        self.bindings.remove(&(key, modifiers));
+    }
+
+    /* Replaces every binding for `action` with `bindings` */
+    pub fn rebind(&mut self, action: NavigationAction, bindings: &[(Key, Modifiers)]) {
+        self.bindings.retain(|_, bound| *bound != action);
+        for &(key, modifiers) in bindings {
+            self.bind(key, modifiers, action);
+        }
+    }
+
+    /* Sorted by key, so the order is the same every time */
+    pub fn bindings_for(&self, action: NavigationAction) -> Vec<(Key, Modifiers)> {
+        let mut bindings: Vec<(Key, Modifiers)> = self
+            .bindings
+            .iter()
+            .filter(|&(_, &bound)| bound == action)
+            .map(|(&binding, _)| binding)
+            .collect();
+        bindings.sort_by_key(|&(key, modifiers)| (key as i32, modifiers.bits()));
+        bindings
+    }
+
+    pub fn handle_key(&self, key: Key, modifiers: Modifiers) -> Option<NavigationAction> {
+        self.bindings.get(&(key, modifiers)).cloned()
+    }
+}
+
+impl Default for InputHandler {
+    fn default() -> InputHandler {
+        let none = Modifiers::empty();
+        let mut handler = InputHandler::new();
+        for &key in &[Key::Right, Key::Down, Key::Space, Key::PageDown] {
+            handler.bind(key, none, NavigationAction::Next);
+        }
+        for &key in &[Key::Left, Key::Up, Key::Backspace, Key::PageUp] {
+            handler.bind(key, none, NavigationAction::Prev);
+        }
+        handler.bind(Key::Home, none, NavigationAction::Goto(0));
+        handler.bind(Key::Left, Modifiers::Alt, NavigationAction::Back);
+        handler.bind(Key::Right, Modifiers::Alt, NavigationAction::Forward);
+
+        let digits = [
+            Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5,
+            Key::Num6, Key::Num7, Key::Num8, Key::Num9,
+        ];
+        for (i, &key) in digits.iter().enumerate() {
+            handler.bind(key, none, NavigationAction::Goto(i));
+        }
+
+        handler.bind(Key::R, none, NavigationAction::Reload);
+        handler.bind(Key::F, none, NavigationAction::ToggleFullscreen);
+        handler.bind(Key::T, none, NavigationAction::ToggleTimer);
+        handler.bind(Key::U, none, NavigationAction::ToggleUniformEditor);
+        handler.bind(Key::S, none, NavigationAction::ToggleStats);
+        handler.bind(Key::C, Modifiers::Control, NavigationAction::CopyShader);
+        handler.bind(Key::Enter, none, NavigationAction::AdoptShader);
+        handler.bind(Key::Delete, none, NavigationAction::DiscardShader);
+        handler.bind(Key::Escape, none, NavigationAction::Quit);
+        handler.bind(Key::Q, none, NavigationAction::Quit);
+        handler
+    }
+}
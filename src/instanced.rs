@@ -0,0 +1,172 @@
+extern crate gl;
+use gl::types::*;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+use bytemuck::{self, Pod};
+
+/* The same two triangles as FullscreenQuad, which each instance can scale and move */
+const VERTICES: [f32; 18] = [
+    -1.0, -1.0, 0.0,
+    1.0, -1.0, 0.0,
+    1.0, 1.0, 0.0,
+    -1.0, -1.0, 0.0,
+    1.0, 1.0, 0.0,
+    -1.0, 1.0, 0.0,
+];
+
+/* One float, vec2, vec3 or vec4 field of the instance type, read by the vertex shader at
+ * `location`. `offset` is in bytes from the start of the instance. */
+#[derive(Debug, Clone, Copy)]
+pub struct InstanceAttribute {
+    pub location: u32,
+    pub components: u32,
+    pub offset: usize,
+}
+
+/* Draws many quads in one call, e.g. the particles or confetti of a transition. The quad's
+ * corners are bound to attribute location 0 as a vec3, as for FullscreenQuad, and each instance
+ * of `T` supplies the attributes it was created with, advancing once per quad rather than once
+ * per vertex. */
+pub struct InstancedQuad<T: Pod> {
+    vao: GLuint,
+    vbo: GLuint,
+    instance_vbo: GLuint,
+    /* Instances the buffer has room for, and the number last uploaded */
+    capacity: usize,
+    count: usize,
+    _data: PhantomData<T>,
+}
+
+impl<T: Pod> InstancedQuad<T> {
+    /* Allocates room for `capacity` instances up front; update_instances grows it if needed */
+    pub fn new(capacity: usize, attributes: &[InstanceAttribute]) -> InstancedQuad<T> {
+        for attribute in attributes {
+            assert!(
+                (1..=4).contains(&attribute.components),
+                "Instance attribute {} must have 1 to 4 components",
+                attribute.location
+            );
+            assert!(
+                attribute.offset + attribute.components as usize * mem::size_of::<GLfloat>()
+                    <= mem::size_of::<T>(),
+                "Instance attribute {} runs past the end of the instance",
+                attribute.location
+            );
+        }
+
+        let (mut vao, mut vbo, mut instance_vbo) = (0, 0, 0);
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::GenBuffers(1, &mut instance_vbo);
+
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(&VERTICES) as GLsizeiptr,
+                VERTICES.as_ptr() as *const c_void,
+                gl::STATIC_DRAW,
+            );
+            gl::VertexAttribPointer(
+                0,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                3 * mem::size_of::<GLfloat>() as GLsizei,
+                ptr::null(),
+            );
+            gl::EnableVertexAttribArray(0);
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, instance_vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (capacity * mem::size_of::<T>()) as GLsizeiptr,
+                ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            for attribute in attributes {
+                gl::VertexAttribPointer(
+                    attribute.location,
+                    attribute.components as GLint,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    mem::size_of::<T>() as GLsizei,
+                    attribute.offset as *const c_void,
+                );
+                gl::EnableVertexAttribArray(attribute.location);
+                gl::VertexAttribDivisor(attribute.location, 1);
+            }
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+
+        InstancedQuad {
+            vao,
+            vbo,
+            instance_vbo,
+            capacity,
+            count: 0,
+            _data: PhantomData,
+        }
+    }
+
+    /* Replaces the instances drawn, e.g. after moving the particles on the CPU each frame */
+    pub fn update_instances(&mut self, data: &[T]) {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.instance_vbo);
+            if data.len() > self.capacity {
+                self.capacity = data.len().next_power_of_two();
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    (self.capacity * mem::size_of::<T>()) as GLsizeiptr,
+                    ptr::null(),
+                    gl::DYNAMIC_DRAW,
+                );
+            }
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                bytes.len() as GLsizeiptr,
+                bytes.as_ptr() as *const c_void,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        self.count = data.len();
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.count
+    }
+
+    pub fn draw(&self) {
+        if self.count == 0 {
+            return;
+        }
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArraysInstanced(
+                gl::TRIANGLES,
+                0,
+                (VERTICES.len() / 3) as GLsizei,
+                self.count as GLsizei,
+            );
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl<T: Pod> Drop for InstancedQuad<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.instance_vbo);
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+extern crate gl;
+use gl::types::*;
+use std::path::Path;
+
+use errors::{GlError, LutError};
+use framebuffer::Framebuffer;
+use quad::FullscreenQuad;
+use shader::{ShaderProgram, ShaderSource};
+use texture::{Texture2D, Texture3D};
+
+/* GLSL for grading a colour through a 3D LUT, for any fragment shader to paste in. The colour is
+ * scaled so 0 and 1 land on the centres of the first and last texels, otherwise the ends of the
+ * table would be blended with the clamped edge. */
+pub const APPLY_LUT: &str = "
+vec3 apply_lut(sampler3D lut, vec3 color) {
+    float size = float(textureSize(lut, 0).x);
+    vec3 coord = clamp(color, 0.0, 1.0) * ((size - 1.0) / size) + 0.5 / size;
+    return texture(lut, coord).rgb;
+}
+";
+
+const LUT_VERTEX_SHADER: &str = "#version 330 core
+layout (location = 0) in vec3 aPos;
+out vec2 uv;
+
+void main() {
+    uv = aPos.xy * 0.5 + 0.5;
+    gl_Position = vec4(aPos, 1.0);
+}
+";
+
+const LUT_FRAGMENT_HEADER: &str = "#version 330 core
+in vec2 uv;
+out vec4 FragColor;
+uniform sampler2D u_frame;
+uniform sampler3D u_lut;
+";
+
+const LUT_FRAGMENT_MAIN: &str = "
+void main() {
+    vec4 color = texture(u_frame, uv);
+    FragColor = vec4(apply_lut(u_lut, color.rgb), color.a);
+}
+";
+
+/* Grades the slides through a LUT as the last thing before the overlays are drawn, e.g. to make
+ * up for a projector or the lighting of a venue. The slide area of the window is copied out and
+ * drawn back through the LUT. */
+pub struct LutPass {
+    lut: Texture3D,
+    program: ShaderProgram,
+    /* What was drawn, at the size of the area being graded */
+    frame: Framebuffer,
+    quad: FullscreenQuad,
+}
+
+impl LutPass {
+    pub fn from_file(path: &Path) -> Result<LutPass, LutError> {
+        let lut = Texture3D::from_cube_lut(path)?;
+        let program = ShaderProgram::new(
+            ShaderSource::Inline(LUT_VERTEX_SHADER.to_string()),
+            ShaderSource::Inline(format!("{}{}{}", LUT_FRAGMENT_HEADER, APPLY_LUT, LUT_FRAGMENT_MAIN)),
+            None,
+            None,
+            None,
+        )?;
+        program.set_uniform_sampler2d("u_frame", 0);
+        program.set_uniform_i32("u_lut", 1);
+        Ok(LutPass {
+            lut,
+            program,
+            frame: Framebuffer::new(1, 1).expect("Cannot create framebuffer"),
+            quad: FullscreenQuad::new(),
+        })
+    }
+
+    /* Grades `rect` (x, y, width and height) of the window in place */
+    pub fn apply(&mut self, rect: [GLint; 4]) -> Result<(), GlError> {
+        self.frame.resize(rect[2].max(1) as u32, rect[3].max(1) as u32)?;
+        self.frame.blit_from_window(rect);
+
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            gl::Viewport(rect[0], rect[1], rect[2], rect[3]);
+        }
+        self.draw(self.frame.color_texture());
+        unsafe {
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+        Ok(())
+    }
+
+    /* Draws `frame` graded through the LUT over the viewport of whatever is bound, e.g. for
+     * exports, which render offscreen rather than into the window */
+    pub fn draw(&self, frame: &Texture2D) {
+        frame.bind_to_unit(0);
+        self.lut.bind_to_unit(1);
+        let quad = &self.quad;
+        self.program.activate(|_| quad.draw());
+    }
+}
@@ -1,8 +1,22 @@
 #![allow(dead_code, unused_imports, unused_extern_crates,
          unused_variables, unused_mut, non_upper_case_globals, non_snake_case)]
+extern crate bytemuck;
+extern crate cgmath;
+extern crate chrono;
+extern crate clap;
+extern crate env_logger;
 extern crate glfw;
 extern crate gl;
+extern crate image;
+#[macro_use]
+extern crate log;
 extern crate notify;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate toml;
+extern crate rand;
 
 use glfw::{Context, Key, Action};
 use std::sync::mpsc::{channel, Receiver};
@@ -14,68 +28,654 @@ use std::sync::Arc;
 use std::mem;
 use std::thread;
 use std::os::raw::c_void;
+use std::env;
+use std::path::Path;
+use std::process;
+use std::time::{Duration, Instant};
 use notify::{Watcher, RecursiveMode, RawEvent, raw_watcher};
 use notify::op;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
+#[macro_use]
 mod shader;
-use shader::ShaderProgram;
+use shader::{ShaderProgram, ShaderStage};
 
-mod geometry;
-use geometry::Geometry;
+mod quad;
+use quad::FullscreenQuad;
+
+mod instanced;
+
+mod lut;
+use lut::LutPass;
+
+mod drop_preview;
+use drop_preview::DroppedShader;
 
 mod hotloader;
 use hotloader::Hotloader;
 
+mod slide;
+use slide::{Slide, SlideManager, ERROR_TEXT_COLOR};
+
+mod renderer;
+
+mod backend;
+
+#[cfg(feature = "vulkan")]
+mod vulkan;
+
+mod serve;
+
+mod pdf;
+use renderer::Renderer;
+
+mod preprocess;
+
+mod spirv;
+
+mod uniform_buffer;
+
+mod transform_feedback;
+
+mod texture;
+
+mod noise;
+
+mod animated_texture;
+
+mod channels;
+
+mod image_slide;
+
+mod code_slide;
+
+#[cfg(feature = "video")]
+mod video;
+
+mod framebuffer;
+use framebuffer::Framebuffer;
+
+mod render_graph;
+
+mod transition;
+
+mod easing;
+
+mod animation;
+
+mod input;
+use input::{InputHandler, NavigationAction};
+
+mod history;
+use history::NavigationHistory;
+
+mod cli;
+use cli::{Options, TransitionPreview};
+
+mod present;
+use present::FramePacer;
+
+mod notes;
+use notes::NotesWindow;
+
+mod presenter;
+use presenter::PresenterWindow;
+
+mod multi_display;
+use multi_display::{DisplayConfig, MultiDisplay};
+
+mod shadertoy;
+use shadertoy::ShaderToyUniforms;
+
+mod resize;
+use resize::ResizeHandler;
+
+mod cursor;
+use cursor::{CursorManager, DEFAULT_IDLE_TIMEOUT};
+
+mod aspect;
+use aspect::{Aspect, AspectMode};
+
+mod font;
+
+#[cfg(feature = "audio")]
+mod audio;
+
+#[cfg(feature = "remote")]
+mod remote;
+
+#[cfg(feature = "midi")]
+mod midi;
+
+mod timer;
+use timer::PresentationTimer;
+
+mod text;
+use text::TextRenderer;
+
+mod uniform_editor;
+use uniform_editor::UniformEditor;
+
+mod stats;
+use stats::StatsOverlay;
+
+mod rehearsal;
+use rehearsal::Rehearsal;
+
+mod event_log;
+use event_log::{Event, EventLog};
+
+mod preload;
+use preload::Preloader;
+
+mod uploader;
+use uploader::BackgroundUploader;
+
+mod recorder;
+use recorder::Recorder;
+
+mod deck;
+use deck::{Deck, SlideSelection};
+
+mod debug;
+
+mod context;
+
+mod window;
+use window::{GlProfile, WindowBuilder, WindowEvents};
+
+mod config;
+use config::Config;
+
 mod errors;
+use errors::{ExportError, NavigationError};
 
-const SCR_WIDTH: u32 = 800;
-const SCR_HEIGHT: u32 = 600;
+/* How long --loop holds the end of a transition before playing it again */
+const PREVIEW_LOOP_PAUSE_SECS: f32 = 0.5;
 
-const vertices: [f32; 12] = [
-    0.5, 0.5, 0.0,
-    0.5, -0.5, 0.0,
-    -0.5, -0.5, 0.0,
-    -0.5, 0.5, 0.0,
-];
-const indices: [u32; 6] = [0, 1, 3, 1, 2, 3];
+/* Slides after the current one whose programs are drawn in the background, and how many threads
+ * do it */
+const PRELOAD_AHEAD: usize = 2;
+const PRELOAD_THREADS: usize = 2;
 
 fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).expect("Failed to initialize glfw");
-    glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
-    glfw.window_hint(glfw::WindowHint::OpenGlProfile(
-        glfw::OpenGlProfileHint::Core,
-    ));
+    env_logger::init();
 
-    #[cfg(target_os = "macos")] glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
+    let user_config = Config::load_user();
+    let options = Options::from_args(&user_config).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
 
-    let (mut window, events) = glfw.create_window(
-        SCR_WIDTH,
-        SCR_HEIGHT,
-        "LearnOpenGL",
-        glfw::WindowMode::Windowed,
-    ).expect("Failed to create GLFW window");
+    /* The deck is optional here, so the config can be checked from anywhere */
+    if options.dump_config {
+        let deck = Deck::from_file(&options.deck).ok();
+        print!("{}", user_config.merged(&options, deck.as_ref()).to_toml());
+        return;
+    }
+
+    let deck = match Deck::from_file(&options.deck) {
+        Ok(deck) => deck,
+        Err(e) => {
+            eprintln!("Cannot load deck: {}", e);
+            process::exit(1);
+        }
+    };
+    let deck = match options.spirv_cache {
+        Some(ref dir) => deck.with_spirv_cache(dir.clone()),
+        None => deck,
+    };
+
+    if options.info {
+        print_info(&deck);
+        return;
+    }
+
+    if let Err(e) = options.validate(&deck) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+
+    /* Compiling needs no GL context, as the SPIR-V is only loaded later */
+    if let Some(ref output_dir) = options.compile_shaders {
+        compile_shaders(&deck, output_dir);
+        return;
+    }
+
+    /* Serving is meant for machines without a display, so it always renders through EGL */
+    if let Some(port) = options.serve {
+        let _context = headless_context(&options);
+        let lut = load_lut(&options);
+        if let Err(e) = serve::run(&options, deck, port, lut.as_ref()) {
+            eprintln!("Cannot serve slides: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(ref settings) = options.export_pdf {
+        export_headless(&options, &deck, &settings.output, |slides, renderer| {
+            pdf::export_pdf(slides, renderer, options.width, options.height, settings)
+        });
+        return;
+    }
+    if let Some(ref animation) = options.export_gif {
+        export_headless(&options, &deck, &animation.output, |_, renderer| {
+            renderer.export_gif(animation.slide, animation.duration, animation.fps, &animation.output)
+        });
+        return;
+    }
+    if let Some(ref animation) = options.export_video {
+        export_headless(&options, &deck, &animation.output, |_, renderer| {
+            renderer.export_video(animation.slide, animation.duration, animation.fps, &animation.output)
+        });
+        return;
+    }
+
+    if options.headless {
+        let _context = headless_context(&options);
+        let slides = SlideManager::from_deck(&deck, options.slide_selection()).unwrap_or_else(|e| {
+            eprintln!("Cannot create slides: {}", e);
+            process::exit(1);
+        });
+        render_offscreen(&options, &slides);
+        return;
+    }
+
+    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).expect("Failed to initialize glfw");
+    let (mut window, events) = WindowBuilder::new()
+        .title("LearnOpenGL")
+        .size(options.width, options.height)
+        .gl_version(3, 3)
+        .profile(options.gl_profile)
+        .samples(options.msaa)
+        .visible(options.export.is_none() && options.bench.is_none())
+        .debug(options.gl_debug || cfg!(debug_assertions))
+        .build(&mut glfw)
+        .expect("Failed to create GLFW window");
 
     window.make_current();
+    shader::use_glsl_es(options.gl_profile == GlProfile::Es3);
     window.set_key_polling(true);
     window.set_framebuffer_size_polling(true);
+    window.set_cursor_pos_polling(true);
+    window.set_mouse_button_polling(true);
+    window.set_drag_and_drop_polling(true);
+
+    glfw.set_swap_interval(options.present_mode.swap_interval());
+
+    load_gl(&options, |symbol| window.get_proc_address(symbol) as *const _);
+
+    let offscreen = options.export.is_some() || options.bench.is_some();
+    /* Kept for as long as textures may still be arriving, so for the whole presentation */
+    let uploader = if offscreen {
+        None
+    } else {
+        BackgroundUploader::new(&mut glfw, &window)
+            .map_err(|e| println!("Warning: loading textures in the foreground: {}", e))
+            .ok()
+    };
+    /* A recording should not be made of error text either */
+    let slides = if offscreen || options.record.is_some() {
+        SlideManager::from_deck(&deck, options.slide_selection())
+    } else {
+        SlideManager::from_deck_allowing_errors(&deck, options.slide_selection(), uploader.as_ref())
+    };
+    let mut slides = slides.unwrap_or_else(|e| {
+        eprintln!("Cannot create slides: {}", e);
+        process::exit(1);
+    });
+    if offscreen {
+        render_offscreen(&options, &slides);
+        return;
+    }
+    if let Some(ref preview) = options.preview_transition {
+        preview_transition(&mut glfw, &mut window, &events, &slides, preview, options.msaa);
+        return;
+    }
+    if options.shuffle {
+        /* Print the seed so a good order can be had again */
+        let seed = options.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        println!("Shuffling slides with --seed {}", seed);
+        slides.shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+    let start = match options.start_at {
+        Some(ref name) => slides.index_named(name).unwrap_or_else(|| {
+            eprintln!("{}", NavigationError::UnknownName(name.clone()));
+            process::exit(1);
+        }),
+        None => options.start_slide,
+    };
+    slides.start_at(start);
+    slides.set_history(NavigationHistory::with_capacity(user_config.history));
+    slides.set_auto_advance(options.auto_advance);
+    if options.timed {
+        let path = Deck::timings_path(&deck.path);
+        match deck::load_timings(&path, deck.config.slides.len()) {
+            Ok(timings) => slides.set_timings(&timings),
+            Err(e) => {
+                eprintln!("Cannot load timings from {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+    }
+
+    /* Offscreen targets for the outgoing and incoming slides during a transition */
+    let mut transition_targets = if slides.transition().is_some() {
+        Some((
+            Framebuffer::with_samples(options.width, options.height, options.msaa)
+                .expect("Cannot create framebuffer"),
+            Framebuffer::with_samples(options.width, options.height, options.msaa)
+                .expect("Cannot create framebuffer"),
+        ))
+    } else {
+        None
+    };
+
+    let quad = FullscreenQuad::new();
+
+    let (reload_tx, reload_events) = channel();
+    for slide in slides.slides() {
+        slide.program.watch(reload_tx.clone()).expect("Cannot watch shader files");
+    }
+
+    let input = user_config.input_handler();
+    let mut windowed_geometry = None;
+    /* The audience sees the slides full screen, the presenter window stays windowed */
+    if options.fullscreen || options.presenter_window {
+        toggle_fullscreen(&mut glfw, &mut window, &mut windowed_geometry);
+    }
+
+    let mut notes_window = if options.notes {
+        Some(NotesWindow::new(&mut window, options.width, options.height)
+             .expect("Failed to create notes window"))
+    } else {
+        None
+    };
+
+    let (width, height) = window.get_framebuffer_size();
+    let mut shadertoy = ShaderToyUniforms::new(width as u32, height as u32);
+    let aspect = Aspect::new(
+        options.aspect.or(deck.config.aspect).unwrap_or_default(),
+        deck.aspect_ratio(),
+    );
+    let mut resize = ResizeHandler::new(aspect, width, height);
+    /* Slides are drawn here at their own size and then copied into their area of the window, so
+     * gl_FragCoord still starts from the corner of the slide */
+    let mut slide_target = if aspect.mode != AspectMode::Stretch {
+        Some(Framebuffer::with_samples(width as u32, height as u32, options.msaa)
+             .expect("Cannot create framebuffer"))
+    } else {
+        None
+    };
+
+    #[cfg(feature = "audio")]
+    let audio_input = if options.audio {
+        Some(audio::AudioInput::new().expect("Cannot open audio input"))
+    } else {
+        None
+    };
+
+    #[cfg(feature = "midi")]
+    let midi_input = deck.config.midi.as_ref().map(|midi| {
+        midi::MidiInput::new(options.midi_device.as_deref(), &midi.mappings)
+            .expect("Cannot open MIDI input")
+    });
+
+    #[cfg(feature = "remote")]
+    let mut remote_control = options.remote_port.or(options.leader).map(|port| {
+        remote::RemoteControl::start(port).expect("Cannot start remote control server")
+    });
+    #[cfg(feature = "remote")]
+    let follower = options.follow.as_ref().map(|leader| remote::Follower::start(leader));
+
+    let mut timer = PresentationTimer::new(true);
+    let timer_program = PresentationTimer::overlay_program().expect("Cannot create timer overlay");
+
+    /* The deck's font, or the user's default */
+    let font = user_config.merged(&options, Some(&deck)).font;
+    let text = font.as_ref().map(|font| {
+        TextRenderer::from_file(font).expect("Cannot load font")
+    });
+
+    let mut presenter_window = if options.presenter_window {
+        if font.is_none() {
+            println!("Warning: set a font in the deck to see notes and times in the presenter window");
+        }
+        Some(PresenterWindow::new(
+            &mut glfw,
+            &mut window,
+            options.width,
+            options.height,
+            deck.aspect_ratio(),
+            deck.config.metadata.as_ref().map(|metadata| metadata.title.as_str()),
+            font.as_deref(),
+        ).and_then(|mut presenter_window| {
+            presenter_window.render_thumbnails(&slides, |_| quad.draw())?;
+            Ok(presenter_window)
+        }).expect("Failed to create presenter window"))
+    } else {
+        None
+    };
 
-    gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+    let mut multi_display = if options.displays.is_empty() {
+        None
+    } else {
+        Some(MultiDisplay::new(
+            &mut glfw,
+            &mut window,
+            DisplayConfig::columns(&options.displays, options.width, options.height),
+        ).expect("Failed to create displays"))
+    };
 
-    let shader_program = ShaderProgram::new("shaders/basic.vert", "shaders/basic.frag")
-        .expect("Cannot create shader program");
+    let mut stats = StatsOverlay::new();
 
-    let geometry = Geometry::new()
-        .add_vertices(&vertices)
-        .add_indices(&indices)
-        .build()
-        .expect("Cannot create geometry");
+    let mut lut = load_lut(&options);
 
-    let hotloader = Hotloader::watch("shaders").expect("Cannot create hotloader");
+    match Preloader::new(&mut glfw, &window, PRELOAD_THREADS) {
+        Ok(preloader) => slides.set_preloader(Some(preloader)),
+        Err(e) => println!("Warning: cannot preload slides: {}", e),
+    }
+
+    let mut editor = UniformEditor::new(Deck::overrides_path(&deck.path), deck.overrides.clone());
+
+    let mut pacer = FramePacer::new(options.present_mode);
+
+    let mut recorder = options.record.as_ref().map(|record| {
+        Recorder::start(&record.output, width as u32, height as u32, record.fps)
+            .expect("Cannot start recording")
+    });
+
+    let idle_timeout = options.cursor_idle
+        .or(deck.cursor_idle())
+        .unwrap_or(DEFAULT_IDLE_TIMEOUT);
+    let mut cursor = CursorManager::new(idle_timeout, Instant::now());
+
+    let mut rehearsal = if options.rehearsal {
+        Some(Rehearsal::new(slides.current().manifest_index, Instant::now()))
+    } else {
+        None
+    };
+
+    let event_log = options.event_log.as_ref().map(|path| {
+        EventLog::create(path).expect("Cannot create event log")
+    });
+    let mut logged_slide = slides.current_index();
+    if let Some(ref event_log) = event_log {
+        event_log.log(Event::Header {
+            deck: deck.path.display().to_string(),
+            metadata: deck.config.metadata.clone(),
+        });
+        event_log.log(Event::Start { slide: logged_slide + 1 });
+    }
+
+    /* A shader file dropped onto the window, shown instead of the current slide */
+    let mut dropped_shader: Option<DroppedShader> = None;
 
     let mut previous_time = glfw.get_time() as f32;
     while !window.should_close() {
-        process_events(&mut window, &events);
+        let now = Instant::now();
+        let mut dropped_files = Vec::new();
+        let window_events = glfw::flush_messages(&events).map(|(_, event)| event).inspect(|event| {
+            match *event {
+                glfw::WindowEvent::CursorPos(..) => cursor.on_mouse_move(now),
+                glfw::WindowEvent::FileDrop(ref paths) => dropped_files.extend(paths.iter().cloned()),
+                _ => {}
+            }
+        });
+        let mut actions = process_events(window_events, &input, &mut shadertoy, &mut resize, &mut editor, &mut slides);
+        if let Some(ref mut presenter_window) = presenter_window {
+            let (presenter_events, clicked) = presenter_window.flush_events();
+            actions.extend(process_events(
+                presenter_events,
+                &input,
+                &mut shadertoy,
+                &mut resize,
+                &mut editor,
+                &mut slides,
+            ));
+            actions.extend(clicked.map(NavigationAction::Goto));
+        }
+        if let Some(ref mut multi_display) = multi_display {
+            let display_events = multi_display.flush_events();
+            actions.extend(process_events(
+                display_events,
+                &input,
+                &mut shadertoy,
+                &mut resize,
+                &mut editor,
+                &mut slides,
+            ));
+            if multi_display.should_close() {
+                window.set_should_close(true);
+            }
+        }
+        #[cfg(feature = "remote")]
+        {
+            if let Some(ref remote_control) = remote_control {
+                actions.extend(remote_control.poll(&slides));
+            }
+            if let Some(ref follower) = follower {
+                actions.extend(follower.poll());
+            }
+        }
+        for action in actions {
+            match action {
+                NavigationAction::Next => {
+                    slides.next();
+                }
+                NavigationAction::Prev => {
+                    slides.prev();
+                }
+                NavigationAction::Goto(index) => {
+                    slides.jump_to(index);
+                }
+                NavigationAction::Back => {
+                    slides.back();
+                }
+                NavigationAction::Forward => {
+                    slides.forward();
+                }
+                NavigationAction::Reload => {
+                    let ok = reload_slide(slides.current());
+                    if let Some(ref event_log) = event_log {
+                        let shader = slides.current().program.filenames().last().map_or_else(
+                            || slides.current().title.clone(),
+                            |path| path.display().to_string(),
+                        );
+                        event_log.log(Event::Reload { shader, ok });
+                    }
+                }
+                NavigationAction::Quit => window.set_should_close(true),
+                NavigationAction::ToggleFullscreen => {
+                    toggle_fullscreen(&mut glfw, &mut window, &mut windowed_geometry)
+                }
+                NavigationAction::ToggleTimer => timer.toggle(),
+                NavigationAction::ToggleStats => {
+                    stats.toggle();
+                    if text.is_none() {
+                        println!("Warning: set a font in the deck to see render statistics");
+                    }
+                }
+                /* Keys would go to an editor that can't be seen, so it stays shut without a font */
+                NavigationAction::ToggleUniformEditor => {
+                    if text.is_some() {
+                        editor.toggle();
+                    } else {
+                        println!("Warning: set a font in the deck to use the uniform editor");
+                    }
+                }
+                /* Through GLFW, so the clipboard is the system's without another dependency */
+                NavigationAction::CopyShader => {
+                    match slides.current().program.expanded_source(ShaderStage::Fragment) {
+                        Some(Ok(source)) => {
+                            window.set_clipboard_string(&source);
+                            println!("Copied the shader of {} to the clipboard", slides.current().title);
+                        }
+                        Some(Err(e)) => println!("Warning: cannot copy the shader: {}", e),
+                        None => println!("Warning: this slide has no fragment shader to copy"),
+                    }
+                }
+                NavigationAction::AdoptShader => {
+                    if let Some(dropped) = dropped_shader.take() {
+                        println!("{} is now the shader of {}", dropped.path.display(), slides.current().title);
+                        dropped.adopt(slides.current_mut());
+                    }
+                }
+                NavigationAction::DiscardShader => {
+                    if let Some(dropped) = dropped_shader.take() {
+                        println!("Discarded {}", dropped.path.display());
+                    }
+                }
+            }
+        }
 
+        /* Only the last of several files dropped at once is kept */
+        for path in dropped_files {
+            if !drop_preview::is_shader_file(&path) {
+                println!("Warning: {} is not a .frag or .vert file", path.display());
+                continue;
+            }
+            match DroppedShader::load(&path, slides.current()) {
+                Ok(dropped) => {
+                    if let Err(e) = dropped.program.watch(reload_tx.clone()) {
+                        println!("Warning: cannot watch {}: {}", path.display(), e);
+                    }
+                    println!(
+                        "Previewing {}, press Enter to use it for this slide or Delete to discard it",
+                        path.display()
+                    );
+                    dropped_shader = Some(dropped);
+                }
+                Err(e) => println!("Cannot preview {}: {}", path.display(), e),
+            }
+        }
+
+        cursor.on_frame(&mut window, now);
+        if let Some(ref mut rehearsal) = rehearsal {
+            rehearsal.update(slides.current().manifest_index, now);
+        }
+
+        let targets = transition_targets
+            .iter_mut()
+            .flat_map(|(from, to)| vec![from, to])
+            .chain(slide_target.iter_mut());
+        resize.apply(targets, &mut shadertoy, &slides.current().program);
+
+        if let Some(ref mut notes_window) = notes_window {
+            notes_window.sync(&mut window, &slides);
+        }
+        #[cfg(feature = "remote")]
+        {
+            if let Some(ref mut remote_control) = remote_control {
+                remote_control.sync(&slides);
+            }
+        }
+
+        if let Some(ref target) = slide_target {
+            target.bind();
+        }
         unsafe {
             gl::ClearColor(0.2, 0.3, 0.3, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
@@ -85,36 +685,485 @@ fn main() {
         let dt = time - previous_time;
         let mix_value = time.sin() / 2.0 + 0.5;
 
-        shader_program.activate(|s| {
-            s.set_float("mixValue", mix_value).unwrap();
-            geometry.render(&s);
-        });
+        shadertoy.advance(time);
+        shadertoy.upload_to(&slides.current().program);
+        if let Some(from) = slides.transitioning_from() {
+            shadertoy.upload_to(&slides.slides()[from].program);
+        }
+        #[cfg(feature = "audio")]
+        {
+            if let Some(ref audio_input) = audio_input {
+                audio_input.upload_to(&slides.current().program);
+            }
+        }
+        #[cfg(feature = "midi")]
+        {
+            if let Some(ref midi_input) = midi_input {
+                midi_input.upload_to(&slides.current().program);
+            }
+        }
+
+        let draw = |s: &shader::ActivatedShader| {
+            s.set_float("mixValue", mix_value).ok();
+            quad.draw();
+        };
+
+        slides.advance(Duration::from_millis((dt * 1000.0) as u64));
+        if slides.current_index() != logged_slide {
+            if let Some(ref event_log) = event_log {
+                event_log.log(Event::SlideAdvance {
+                    from: logged_slide + 1,
+                    to: slides.current_index() + 1,
+                });
+            }
+            logged_slide = slides.current_index();
+        }
+        slides.preload_ahead(PRELOAD_AHEAD);
+        stats.record_frame(Duration::from_secs_f32(dt.max(0.0)));
+        match (slides.transitioning_from(), slides.transition(), transition_targets.as_ref()) {
+            (Some(from), Some(transition), Some((from_target, to_target))) => {
+                for &(index, target) in &[(from, from_target), (slides.current_index(), to_target)] {
+                    target.bind();
+                    unsafe {
+                        gl::Clear(gl::COLOR_BUFFER_BIT);
+                    }
+                    slides.activate_slide(index, draw);
+                    target.unbind();
+                }
+                transition.render(from_target.color_texture(), to_target.color_texture(), |_| {
+                    quad.draw()
+                });
+            }
+            _ => match dropped_shader {
+                Some(ref dropped) => dropped.render(slides.current(), &shadertoy, draw),
+                None => slides.activate(draw),
+            },
+        }
+
+        if let Some(ref target) = slide_target {
+            target.unbind();
+            unsafe {
+                gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+            }
+            target.blit_to_window(resize.slide_rect());
+        }
+        /* Before the overlays, which are for the presenter rather than part of the slide */
+        if let Some(ref mut lut) = lut {
+            if let Err(e) = lut.apply(resize.slide_rect()) {
+                println!("Warning: cannot apply the LUT: {}", e);
+            }
+        }
+
+        if let Some(ref text) = text {
+            text.draw_string(&slides.current().title, 20.0, 20.0, 32.0, [1.0, 1.0, 1.0, 1.0]);
+            /* Over the last good build, if there was one, until the error is fixed */
+            if let Some(error) = slides.current().program.build_error() {
+                text.draw_string(&error, 20.0, 70.0, 18.0, ERROR_TEXT_COLOR);
+            }
+            editor.render(text, slides.current().manifest_index, slides.current());
+            stats.render(text);
+        }
+
+        timer.update(slides.current_index());
+        timer.render_overlay(&timer_program);
 
+        if let Some(ref mut recorder) = recorder {
+            let (width, height) = window.get_framebuffer_size();
+            if let Err(e) = recorder.capture([0, 0, width, height]) {
+                println!("Warning: stopped recording: {}", e);
+                window.set_should_close(true);
+            }
+        }
+
+        if let Some(ref mut presenter_window) = presenter_window {
+            presenter_window.render_previews(&slides, &shadertoy, draw);
+            presenter_window.present(&mut window, &slides, &timer);
+        }
+        if let Some(ref mut multi_display) = multi_display {
+            multi_display.render(&mut window, slides.current());
+        }
+
+        pacer.wait();
         window.swap_buffers();
+        shadertoy.mouse.end_frame();
         glfw.poll_events();
 
-        /* Handle hotloader events */
-        if let Some(path) = hotloader.has_event() {
-            if let Some(ext) = path.extension() {
-                if ext == "frag" || ext == "vert" {
-                    shader_program.reload();
+        /* Handle hotloader events, reloading each affected program at most once per frame */
+        let changed: Vec<_> = reload_events.try_iter().map(|e| e.path).collect();
+        let mut reloaded = false;
+        if let Some(ref dropped) = dropped_shader {
+            if changed.iter().any(|path| dropped.program.depends_on(path)) {
+                if let Err(e) = dropped.program.reload() {
+                    println!("Error reloading shader: {}", e);
+                }
+            }
+        }
+        for slide in slides.slides() {
+            if let Some(path) = changed.iter().find(|path| slide.program.depends_on(path)) {
+                let ok = reload_slide(slide);
+                if let Some(ref event_log) = event_log {
+                    event_log.log(Event::Reload { shader: path.display().to_string(), ok });
+                }
+                reloaded = true;
+            }
+        }
+        if reloaded {
+            if let Some(ref mut presenter_window) = presenter_window {
+                if let Err(e) = presenter_window.render_thumbnails(&slides, |_| quad.draw()) {
+                    println!("Warning: cannot render thumbnails: {}", e);
                 }
             }
         }
         previous_time = time;
     }
+
+    if let Some(ref event_log) = event_log {
+        event_log.log(Event::Quit { slide: slides.current_index() + 1 });
+    }
+    /* Flushes the log before the recording, which can take a while, is finished */
+    drop(event_log);
+
+    if let Some(rehearsal) = rehearsal {
+        let timings = rehearsal.finish(Instant::now());
+        let total: Duration = timings.values().sum();
+        let path = Deck::timings_path(&deck.path);
+        match deck::save_timings(&path, &timings) {
+            Ok(()) => println!("Rehearsal took {}s, saved timings to {}", total.as_secs(), path.display()),
+            Err(e) => eprintln!("Cannot save timings to {}: {}", path.display(), e),
+        }
+    }
+
+    if let Some(recorder) = recorder {
+        println!("Finishing the recording");
+        if let Err(e) = recorder.finish() {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
 }
 
-fn process_events(window: &mut glfw::Window, events: &Receiver<(f64, glfw::WindowEvent)>) {
-    for (_, event) in glfw::flush_messages(events) {
-        match event {
-            glfw::WindowEvent::FramebufferSize(width, height) => unsafe {
-                gl::Viewport(0, 0, width, height)
-            },
-            glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
-                window.set_should_close(true)
+/* For --info: the deck's [metadata] and how many slides it has */
+fn print_info(deck: &Deck) {
+    match deck.config.metadata {
+        Some(ref metadata) => {
+            println!("Title:   {}", metadata.title);
+            println!("Author:  {}", metadata.author);
+            println!("Version: {}", metadata.version);
+            println!("Date:    {}", metadata.date);
+        }
+        None => println!("{} has no [metadata]", deck.path.display()),
+    }
+    println!("Slides:  {}", deck.config.slides.len());
+}
+
+/* Writes every program of the deck to `output_dir` as SPIR-V, carrying on past failures so
+ * they are all reported at once */
+#[cfg(feature = "spirv")]
+fn compile_shaders(deck: &Deck, output_dir: &Path) {
+    if let Err(e) = std::fs::create_dir_all(output_dir) {
+        eprintln!("Cannot create {}: {}", output_dir.display(), e);
+        process::exit(1);
+    }
+    let mut failed = 0;
+    for program in deck.shader_programs() {
+        match ShaderProgram::precompile_spirv(&program.stages, &program.defines, output_dir) {
+            Ok(files) => println!("Compiled {}: {} SPIR-V modules", program.name, files.len()),
+            Err(e) => {
+                eprintln!("Cannot compile {}: {}", program.name, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "spirv"))]
+fn compile_shaders(_deck: &Deck, _output_dir: &Path) {
+    unreachable!("Options::validate rejects compile-shaders without the spirv feature")
+}
+
+/* Creates a context without a window and loads the GL function pointers into it */
+fn headless_context(options: &Options) -> context::Context {
+    let context = context::Context::new_headless(options.width, options.height).unwrap_or_else(|e| {
+        eprintln!("Cannot create headless context: {}", e);
+        process::exit(1);
+    });
+    load_gl(options, |symbol| context.get_proc_address(symbol));
+    context
+}
+
+/* Runs one of the export subcommands through a headless context, exiting on failure */
+fn export_headless<F>(options: &Options, deck: &Deck, output: &Path, export: F)
+    where F: FnOnce(&SlideManager, &Renderer) -> Result<(), ExportError> {
+    let _context = headless_context(options);
+    let slides = SlideManager::from_deck(deck, options.slide_selection()).unwrap_or_else(|e| {
+        eprintln!("Cannot create slides: {}", e);
+        process::exit(1);
+    });
+    let lut = load_lut(options);
+    let renderer = Renderer::new(&slides, options.width, options.height)
+        .with_samples(options.msaa)
+        .with_lut(lut.as_ref());
+    if let Err(e) = export(&slides, &renderer) {
+        eprintln!("Cannot export {}: {}", output.display(), e);
+        process::exit(1);
+    }
+    println!("Exported {}", output.display());
+}
+
+/* The --lut pass, if one was given, exiting when it can't be loaded. Needs a current context. */
+fn load_lut(options: &Options) -> Option<LutPass> {
+    options.lut.as_ref().map(|path| {
+        LutPass::from_file(path).unwrap_or_else(|e| {
+            eprintln!("Cannot load LUT: {}", e);
+            process::exit(1);
+        })
+    })
+}
+
+/* Loads the GL function pointers into the context that is current */
+fn load_gl<F>(options: &Options, mut loader: F)
+    where F: FnMut(&str) -> *const c_void {
+    gl::load_with(&mut loader);
+    spirv::load_with(&mut loader);
+
+    debug::install_debug_callback();
+    if options.gl_debug {
+        /* Report messages from inside the offending call, so a breakpoint in the callback has a
+         * useful backtrace */
+        unsafe {
+            gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        }
+    }
+}
+
+/* Runs --export or --bench, which only need a current context, not a visible window */
+fn render_offscreen(options: &Options, slides: &SlideManager) {
+    if let Some(ref dir) = options.export {
+        let lut = load_lut(options);
+        let renderer = Renderer::new(slides, options.width, options.height)
+            .with_samples(options.msaa)
+            .with_lut(lut.as_ref());
+        for index in 0..slides.len() {
+            let path = dir.join(format!("slide-{:02}.png", index + 1));
+            if let Err(e) = renderer.export_slide(index, &path) {
+                eprintln!("Cannot export slide {}: {}", index + 1, e);
+                process::exit(1);
+            }
+            println!("Exported {}", path.display());
+        }
+    } else if let Some(frames) = options.bench {
+        let renderer = Renderer::new(slides, options.width, options.height)
+            .with_samples(options.msaa);
+        let mut results = Vec::with_capacity(slides.len());
+        for index in 0..slides.len() {
+            match renderer.bench_slide(index, frames) {
+                Ok(stats) => results.push(stats),
+                Err(e) => {
+                    eprintln!("Cannot benchmark slide {}: {}", index + 1, e);
+                    process::exit(1);
+                }
+            }
+        }
+        println!("{}", serde_json::to_string_pretty(&results).expect("Cannot serialise results"));
+    }
+}
+
+/* Plays the deck's transition between two slides until the window is closed, rebuilding either
+ * slide or the transition itself when their shaders change and playing it again from the start */
+fn preview_transition(
+    glfw: &mut glfw::Glfw,
+    window: &mut glfw::Window,
+    events: &WindowEvents,
+    slides: &SlideManager,
+    preview: &TransitionPreview,
+    samples: u32,
+) {
+    /* Options::validate has checked the deck has one */
+    let transition = slides.transition().expect("The deck has no transition");
+    let (width, height) = window.get_framebuffer_size();
+    let mut targets = [
+        Framebuffer::with_samples(width as u32, height as u32, samples).expect("Cannot create framebuffer"),
+        Framebuffer::with_samples(width as u32, height as u32, samples).expect("Cannot create framebuffer"),
+    ];
+    let quad = FullscreenQuad::new();
+    let mut shadertoy = ShaderToyUniforms::new(width as u32, height as u32);
+
+    let (reload_tx, reload_events) = channel();
+    for &index in &[preview.from, preview.to] {
+        slides.slides()[index].program.watch(reload_tx.clone()).expect("Cannot watch shader files");
+    }
+    for program in &[&transition.enter, &transition.exit] {
+        program.watch(reload_tx.clone()).expect("Cannot watch shader files");
+    }
+
+    println!(
+        "Previewing the transition from slide {} to {}, press Space to play it again",
+        preview.from + 1,
+        preview.to + 1
+    );
+    transition.start();
+    let mut finished_at = None;
+    let mut previous_time = glfw.get_time() as f32;
+    while !window.should_close() {
+        for (_, event) in glfw::flush_messages(events) {
+            shadertoy.handle_event(&event);
+            match event {
+                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _)
+                | glfw::WindowEvent::Key(Key::Q, _, Action::Press, _) => window.set_should_close(true),
+                glfw::WindowEvent::Key(Key::Space, _, Action::Press, _) => transition.start(),
+                glfw::WindowEvent::FramebufferSize(width, height) => {
+                    for target in targets.iter_mut() {
+                        target.resize(width as u32, height as u32).expect("Cannot resize framebuffer");
+                    }
+                    shadertoy.resolution = [width as f32, height as f32];
+                    unsafe {
+                        gl::Viewport(0, 0, width, height);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let time = glfw.get_time() as f32;
+        transition.advance(Duration::from_secs_f32((time - previous_time).max(0.0)));
+        previous_time = time;
+        if !transition.is_complete() {
+            finished_at = None;
+        } else if preview.looping {
+            let finished = *finished_at.get_or_insert(time);
+            if time - finished >= PREVIEW_LOOP_PAUSE_SECS {
+                transition.start();
+            }
+        }
+
+        shadertoy.advance(time);
+        for (&index, target) in [preview.from, preview.to].iter().zip(targets.iter()) {
+            shadertoy.upload_to(&slides.slides()[index].program);
+            target.bind();
+            unsafe {
+                gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+            }
+            slides.activate_slide(index, |_| quad.draw());
+            target.unbind();
+        }
+        unsafe {
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+        transition.render(targets[0].color_texture(), targets[1].color_texture(), |_| quad.draw());
+
+        window.swap_buffers();
+        shadertoy.mouse.end_frame();
+        glfw.poll_events();
+
+        let changed: Vec<_> = reload_events.try_iter().map(|e| e.path).collect();
+        if changed.is_empty() {
+            continue;
+        }
+        let mut reloaded = false;
+        for &index in &[preview.from, preview.to] {
+            let slide = &slides.slides()[index];
+            if changed.iter().any(|path| slide.program.depends_on(path)) {
+                reload_slide(slide);
+                reloaded = true;
+            }
+        }
+        for program in &[&transition.enter, &transition.exit] {
+            if changed.iter().any(|path| program.depends_on(path)) {
+                match program.reload() {
+                    Ok(()) => {
+                        println!("Reloaded transition in {} ms", program.last_compile_duration().as_millis())
+                    }
+                    Err(e) => println!("Error reloading transition: {}", e),
+                }
+                reloaded = true;
+            }
+        }
+        if reloaded {
+            transition.start();
+        }
+    }
+}
+
+/* Returns whether the new program built */
+fn reload_slide(slide: &Slide) -> bool {
+    match slide.program.reload() {
+        Ok(()) => {
+            slide.apply_uniforms();
+            let duration = slide.program.last_compile_duration();
+            println!("Reloaded in {} ms", duration.as_millis());
+            true
+        }
+        Err(e) => {
+            println!("Error reloading shader: {}", e);
+            false
+        }
+    }
+}
+
+/* Switches between windowed mode and fullscreen on the primary monitor, remembering the windowed
+ * position and size so they can be restored */
+fn toggle_fullscreen(
+    glfw: &mut glfw::Glfw,
+    window: &mut glfw::Window,
+    windowed_geometry: &mut Option<(i32, i32, i32, i32)>,
+) {
+    match windowed_geometry.take() {
+        Some((x, y, width, height)) => {
+            window.set_monitor(glfw::WindowMode::Windowed, x, y, width as u32, height as u32, None);
+        }
+        None => {
+            let ((x, y), (width, height)) = (window.get_pos(), window.get_size());
+            glfw.with_primary_monitor_mut(|_, monitor| {
+                if let Some(monitor) = monitor {
+                    if let Some(mode) = monitor.get_video_mode() {
+                        window.set_monitor(
+                            glfw::WindowMode::FullScreen(monitor),
+                            0,
+                            0,
+                            mode.width,
+                            mode.height,
+                            Some(mode.refresh_rate),
+                        );
+                        *windowed_geometry = Some((x, y, width, height));
+                    }
+                }
+            });
+        }
+    }
+}
+
+fn process_events<I>(
+    events: I,
+    input: &InputHandler,
+    shadertoy: &mut ShaderToyUniforms,
+    resize: &mut ResizeHandler,
+    editor: &mut UniformEditor,
+    slides: &mut SlideManager,
+) -> Vec<NavigationAction>
+    where I: IntoIterator<Item = glfw::WindowEvent> {
+    let mut actions = Vec::new();
+    for event in events {
+        shadertoy.handle_event(&event);
+        resize.handle_event(&event);
+        if let glfw::WindowEvent::Key(key, _, action, modifiers) = event {
+            /* Held keys repeat in the editor, so values can be swept smoothly */
+            if action != Action::Release {
+                /* Overrides are saved against the slide's place in the manifest */
+                let index = slides.current().manifest_index;
+                if editor.handle_key(key, modifiers, index, slides.current_mut()) {
+                    continue;
+                }
+            }
+            if action == Action::Press {
+                actions.extend(input.handle_key(key, modifiers));
             }
-            _ => {}
         }
     }
+    actions
 }
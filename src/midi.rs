@@ -0,0 +1,92 @@
+extern crate midir;
+
+use self::midir::{Ignore, MidiInputConnection};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use deck::MidiMapping;
+use errors::MidiError;
+use shader::ShaderProgram;
+
+/* Control change messages on any channel: status 0xB0 to 0xBF, controller, value 0 to 127 */
+const CONTROL_CHANGE: u8 = 0xB0;
+
+/* A controller's latest value, already mapped into its uniform's range. Written by the MIDI
+ * thread and read by the render loop as f32 bits, so neither side blocks. NaN until the first
+ * message, so the uniform keeps its deck value until the control is touched. */
+struct Control {
+    cc: u8,
+    uniform: String,
+    range: [f32; 2],
+    value: AtomicU32,
+}
+
+/* Sets uniforms from the control change messages of one MIDI input port */
+pub struct MidiInput {
+    controls: Arc<Vec<Control>>,
+    _connection: MidiInputConnection<()>,
+}
+
+impl MidiInput {
+    /* Opens the first port whose name contains `device`, or the first port when it is None */
+    pub fn new(device: Option<&str>, mappings: &[MidiMapping]) -> Result<MidiInput, MidiError> {
+        let mut input = midir::MidiInput::new("slideshow").map_err(|e| MidiError::Init(e.to_string()))?;
+        input.ignore(Ignore::All);
+
+        let ports = input.ports();
+        let names: Vec<String> = ports.iter().filter_map(|p| input.port_name(p).ok()).collect();
+        let index = match device {
+            Some(device) => names.iter().position(|name| name.contains(device)),
+            None if !ports.is_empty() => Some(0),
+            None => None,
+        };
+        let index = index.ok_or_else(|| MidiError::NoDevice {
+            requested: device.map(str::to_string),
+            available: names.clone(),
+        })?;
+        info!("Reading MIDI controls from {}", names[index]);
+
+        let controls: Arc<Vec<Control>> = Arc::new(
+            mappings
+                .iter()
+                .map(|mapping| Control {
+                    cc: mapping.cc,
+                    uniform: mapping.uniform.clone(),
+                    range: mapping.range,
+                    value: AtomicU32::new(f32::NAN.to_bits()),
+                })
+                .collect(),
+        );
+        let written = controls.clone();
+        let connection = input
+            .connect(
+                &ports[index],
+                "slideshow-controls",
+                move |_, message, _| {
+                    if let [status, cc, value] = *message {
+                        if status & 0xF0 == CONTROL_CHANGE {
+                            for control in written.iter().filter(|c| c.cc == cc) {
+                                let t = f32::from(value) / 127.0;
+                                let mapped = control.range[0] + (control.range[1] - control.range[0]) * t;
+                                control.value.store(mapped.to_bits(), Ordering::Relaxed);
+                            }
+                        }
+                    }
+                },
+                (),
+            )
+            .map_err(|e| MidiError::Connect(e.to_string()))?;
+
+        Ok(MidiInput { controls, _connection: connection })
+    }
+
+    /* Sets the uniform of every control that has moved since the input was opened */
+    pub fn upload_to(&self, program: &ShaderProgram) {
+        for control in self.controls.iter() {
+            let value = f32::from_bits(control.value.load(Ordering::Relaxed));
+            if !value.is_nan() && program.has_uniform(&control.uniform) {
+                program.set_uniform_f32(&control.uniform, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+extern crate gl;
+
+use glfw::{self, Context};
+
+use errors::WindowError;
+use quad::FullscreenQuad;
+use slide::Slide;
+use window::{WindowBuilder, WindowEvents};
+
+/* The part of the slide a display shows, as fractions of the slide's width and height from its
+ * bottom left corner */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlideRegion {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl SlideRegion {
+    pub const WHOLE: SlideRegion = SlideRegion { x: 0.0, y: 0.0, width: 1.0, height: 1.0 };
+
+    /* Column `index` of `count` equal, full height columns, e.g. left, center and right for 3 */
+    pub fn column(index: usize, count: usize) -> SlideRegion {
+        assert!(index < count, "Column {} is outside {} columns", index, count);
+        let width = 1.0 / count as f32;
+        SlideRegion { x: index as f32 * width, y: 0.0, width, height: 1.0 }
+    }
+}
+
+/* One window of a MultiDisplay. `monitor` is an index into the connected monitors, as for
+ * --fullscreen, or None for a window on the desktop of `width` x `height`. Fullscreen windows
+ * take the monitor's current video mode instead. */
+#[derive(Debug, Clone)]
+pub struct DisplayConfig {
+    pub monitor: Option<usize>,
+    pub width: u32,
+    pub height: u32,
+    pub region: SlideRegion,
+}
+
+impl DisplayConfig {
+    /* One display per monitor, splitting the slide into as many columns, left to right */
+    pub fn columns(monitors: &[usize], width: u32, height: u32) -> Vec<DisplayConfig> {
+        monitors
+            .iter()
+            .enumerate()
+            .map(|(index, &monitor)| DisplayConfig {
+                monitor: Some(monitor),
+                width,
+                height,
+                region: SlideRegion::column(index, monitors.len()),
+            })
+            .collect()
+    }
+}
+
+struct Display {
+    window: glfw::Window,
+    events: WindowEvents,
+    /* Vertex arrays are not shared between contexts, so each display has its own quad */
+    quad: FullscreenQuad,
+    region: SlideRegion,
+}
+
+/* A slide spread over several windows, e.g. one per projector for a stage wider than any one
+ * screen. The windows share the presentation's context, so the slide's program and textures draw
+ * into all of them. Each display sets the viewport to the size the whole slide would be at its scale,
+ * offset so that only its region lands in the window, and sets iResolution and iViewport to
+ * match, so a shader using gl_FragCoord sees the same slide on every display. */
+pub struct MultiDisplay {
+    displays: Vec<Display>,
+}
+
+impl MultiDisplay {
+    pub fn new(
+        glfw: &mut glfw::Glfw,
+        parent: &mut glfw::Window,
+        configs: Vec<DisplayConfig>,
+    ) -> Result<MultiDisplay, WindowError> {
+        let mut displays = Vec::with_capacity(configs.len());
+        for (index, config) in configs.into_iter().enumerate() {
+            let (mut window, events) = WindowBuilder::new()
+                .title(&format!("Display {}", index + 1))
+                .size(config.width, config.height)
+                .fullscreen(config.monitor)
+                .shared_with(parent)
+                .build(glfw)?;
+            window.set_key_polling(true);
+
+            window.make_current();
+            /* Only the presentation window waits for vsync, or every frame would wait once per
+             * display */
+            glfw.set_swap_interval(glfw::SwapInterval::None);
+            let quad = FullscreenQuad::new();
+            displays.push(Display { window, events, quad, region: config.region });
+        }
+        parent.make_current();
+
+        Ok(MultiDisplay { displays })
+    }
+
+    pub fn len(&self) -> usize {
+        self.displays.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.displays.is_empty()
+    }
+
+    pub fn should_close(&self) -> bool {
+        self.displays.iter().any(|display| display.window.should_close())
+    }
+
+    /* Key events from every display, for the usual input handling */
+    pub fn flush_events(&mut self) -> Vec<glfw::WindowEvent> {
+        let mut events = Vec::new();
+        for display in &self.displays {
+            events.extend(glfw::flush_messages(&display.events).map(|(_, event)| event));
+        }
+        events
+    }
+
+    /* Draws `slide` on every display and swaps their buffers. Uniforms other than iResolution
+     * and iViewport are left as they are, so set the frame's time before calling. Textures are
+     * bound to units per context, so the slide's channels are bound again in each display's.
+     * Call with the presentation's context current; it is current again afterwards. */
+    pub fn render(&mut self, parent: &mut glfw::Window, slide: &Slide) {
+        let program = &slide.program;
+        for display in &mut self.displays {
+            display.window.make_current();
+            let (width, height) = display.window.get_framebuffer_size();
+            let viewport = slide_viewport(display.region, width, height);
+            unsafe {
+                gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+                gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+            }
+            /* A slide that has never built is left black */
+            if program.is_linked() {
+                if program.has_uniform("iResolution") {
+                    program.set_uniform_vec3(
+                        "iResolution",
+                        [viewport[2] as f32, viewport[3] as f32, 1.0],
+                    );
+                }
+                if program.has_uniform("iViewport") {
+                    program.set_uniform_vec4(
+                        "iViewport",
+                        [viewport[0] as f32, viewport[1] as f32, viewport[2] as f32, viewport[3] as f32],
+                    );
+                }
+                let quad = &display.quad;
+                slide.activate(|_| quad.draw());
+            }
+            display.window.swap_buffers();
+        }
+        parent.make_current();
+    }
+}
+
+/* The viewport that puts `region` of the slide exactly over a width x height window. It is
+ * larger than the window and starts left of and below it for any region but the whole slide. */
+fn slide_viewport(region: SlideRegion, width: i32, height: i32) -> [i32; 4] {
+    let full_width = (width as f32 / region.width).round();
+    let full_height = (height as f32 / region.height).round();
+    [
+        -(region.x * full_width).round() as i32,
+        -(region.y * full_height).round() as i32,
+        full_width as i32,
+        full_height as i32,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{slide_viewport, DisplayConfig, SlideRegion};
+
+    #[test]
+    fn whole_slide_fills_the_window() {
+        assert_eq!(slide_viewport(SlideRegion::WHOLE, 1920, 1080), [0, 0, 1920, 1080]);
+    }
+
+    #[test]
+    fn columns_shift_the_slide_left() {
+        let viewports: Vec<_> = (0..3)
+            .map(|index| slide_viewport(SlideRegion::column(index, 3), 1920, 1080))
+            .collect();
+        assert_eq!(
+            viewports,
+            vec![[0, 0, 5760, 1080], [-1920, 0, 5760, 1080], [-3840, 0, 5760, 1080]]
+        );
+    }
+
+    #[test]
+    fn regions_can_be_offset_vertically() {
+        let top_right = SlideRegion { x: 0.5, y: 0.5, width: 0.5, height: 0.5 };
+        assert_eq!(slide_viewport(top_right, 800, 600), [-800, -600, 1600, 1200]);
+    }
+
+    #[test]
+    fn columns_follow_the_monitors() {
+        let configs = DisplayConfig::columns(&[2, 0], 1280, 720);
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[0].monitor, Some(2));
+        assert_eq!(configs[1].region, SlideRegion { x: 0.5, y: 0.0, width: 0.5, height: 1.0 });
+    }
+}
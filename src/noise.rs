@@ -0,0 +1,152 @@
+/* CPU-side generation of tileable RGBA noise. Each channel is an independent field, and every
+ * kind wraps at the texture edges so it can be sampled with GL_REPEAT. */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoiseKind {
+    /* Independent uniform values per texel */
+    #[default]
+    White,
+    /* White noise with the low frequencies filtered out, so values are evenly spread */
+    Blue,
+    /* Smoothly interpolated random values on a lattice */
+    Value,
+    /* Gradient noise on a lattice */
+    Perlin,
+}
+
+/* Lattice cells across the texture for value and Perlin noise */
+const CELLS: u32 = 8;
+
+/* Returns width * height RGBA8 texels, rows bottom first */
+pub fn generate(kind: NoiseKind, width: u32, height: u32, seed: u64) -> Vec<u8> {
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    for channel in 0..4u64 {
+        let seed = hash(seed, channel, 0x6e6f697365);
+        let values = match kind {
+            NoiseKind::White => white(width, height, seed),
+            NoiseKind::Blue => blue(width, height, seed),
+            NoiseKind::Value => lattice(width, height, |x, y| value(x, y, seed)),
+            NoiseKind::Perlin => lattice(width, height, |x, y| perlin(x, y, seed)),
+        };
+        for (i, v) in values.iter().enumerate() {
+            pixels[i * 4 + channel as usize] = (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    }
+    pixels
+}
+
+/* SplitMix64 finaliser over the inputs, which mixes well enough that neighbouring coordinates
+ * give unrelated values */
+fn hash(seed: u64, x: u64, y: u64) -> u64 {
+    let mut z = seed
+        .wrapping_add(x.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+        .wrapping_add(y.wrapping_mul(0xc2b2_ae3d_27d4_eb4f));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/* Uniform in [0, 1) */
+fn unit(seed: u64, x: u64, y: u64) -> f32 {
+    (hash(seed, x, y) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn white(width: u32, height: u32, seed: u64) -> Vec<f32> {
+    let mut values = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            values.push(unit(seed, x as u64, y as u64));
+        }
+    }
+    values
+}
+
+/* An approximation of blue noise: subtract a wrapping blur from white noise to leave the high
+ * frequencies, then replace each value by its rank so the result is uniformly distributed */
+fn blue(width: u32, height: u32, seed: u64) -> Vec<f32> {
+    let white = white(width, height, seed);
+    let (w, h) = (width as i64, height as i64);
+    let at = |x: i64, y: i64| white[(y.rem_euclid(h) * w + x.rem_euclid(w)) as usize];
+
+    let mut filtered = Vec::with_capacity(white.len());
+    for y in 0..h {
+        for x in 0..w {
+            let mut sum = 0.0;
+            for dy in -2..=2 {
+                for dx in -2..=2 {
+                    sum += at(x + dx, y + dy);
+                }
+            }
+            filtered.push(at(x, y) - sum / 25.0);
+        }
+    }
+
+    let mut order: Vec<usize> = (0..filtered.len()).collect();
+    order.sort_by(|&a, &b| filtered[a].partial_cmp(&filtered[b]).unwrap());
+    let mut ranked = vec![0.0; filtered.len()];
+    let last = (filtered.len().max(2) - 1) as f32;
+    for (rank, &i) in order.iter().enumerate() {
+        ranked[i] = rank as f32 / last;
+    }
+    ranked
+}
+
+/* Samples `f` in lattice space, where the texture spans CELLS cells in each direction */
+fn lattice<F>(width: u32, height: u32, f: F) -> Vec<f32>
+    where F: Fn(f32, f32) -> f32 {
+    let mut values = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let u = (x as f32 + 0.5) * CELLS as f32 / width as f32;
+            let v = (y as f32 + 0.5) * CELLS as f32 / height as f32;
+            values.push(f(u, v));
+        }
+    }
+    values
+}
+
+/* Lattice coordinates wrapped to the period, for tiling */
+fn cell(i: f32, offset: u32) -> u64 {
+    ((i as u32 + offset) % CELLS) as u64
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn value(x: f32, y: f32, seed: u64) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (smoothstep(x - x0), smoothstep(y - y0));
+    let corner = |dx, dy| unit(seed, cell(x0, dx), cell(y0, dy));
+    lerp(
+        lerp(corner(0, 0), corner(1, 0), tx),
+        lerp(corner(0, 1), corner(1, 1), tx),
+        ty,
+    )
+}
+
+fn perlin(x: f32, y: f32, seed: u64) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let gradient = |dx: u32, dy: u32| {
+        let angle = unit(seed, cell(x0, dx), cell(y0, dy)) * 2.0 * ::std::f32::consts::PI;
+        angle.cos() * (fx - dx as f32) + angle.sin() * (fy - dy as f32)
+    };
+    let (tx, ty) = (fade(fx), fade(fy));
+    let n = lerp(
+        lerp(gradient(0, 0), gradient(1, 0), tx),
+        lerp(gradient(0, 1), gradient(1, 1), tx),
+        ty,
+    );
+    /* Unit gradients keep n within +-sqrt(1/2) */
+    0.5 + n * ::std::f32::consts::FRAC_1_SQRT_2
+}
@@ -0,0 +1,72 @@
+extern crate gl;
+use std::sync::mpsc::Receiver;
+
+use glfw::{self, Context};
+
+use slide::SlideManager;
+use window::WindowBuilder;
+
+/* A second window for the presenter, created in the presentation window's share group so slide
+ * resources can be drawn in it too. Until there is a text renderer the notes are shown in the
+ * window title and echoed to the terminal. */
+pub struct NotesWindow {
+    window: glfw::Window,
+    events: Receiver<(f64, glfw::WindowEvent)>,
+    shown: Option<usize>,
+}
+
+impl NotesWindow {
+    pub fn new(parent: &mut glfw::Window, width: u32, height: u32) -> Option<NotesWindow> {
+        let mut glfw = parent.glfw;
+        let (window, events) = WindowBuilder::new()
+            .title("Speaker notes")
+            .size(width, height)
+            .shared_with(parent)
+            .build(&mut glfw)
+            .ok()?;
+        /* Creating a window does not change the current context, but be explicit about which
+         * window the main loop renders into */
+        parent.make_current();
+        Some(NotesWindow {
+            window,
+            events,
+            shown: None,
+        })
+    }
+
+    /* Updates the notes if the current slide has changed since the last call */
+    pub fn sync(&mut self, parent: &mut glfw::Window, slides: &SlideManager) {
+        /* Closing the notes window only hides it, the presentation carries on */
+        for _ in glfw::flush_messages(&self.events) {}
+        if self.window.should_close() {
+            self.window.set_should_close(false);
+            self.window.hide();
+        }
+
+        let index = slides.current_index();
+        if self.shown == Some(index) {
+            return;
+        }
+        self.shown = Some(index);
+
+        let slide = slides.current();
+        let first_line = slide.notes.lines().next().unwrap_or("");
+        self.window.set_title(&format!(
+            "{} {} - {}",
+            slides.position_label(),
+            slide.title,
+            first_line
+        ));
+        if !slide.notes.is_empty() {
+            println!("Notes for {}:\n{}", slide.title, slide.notes);
+        }
+
+        self.window.make_current();
+        unsafe {
+            gl::ClearColor(0.1, 0.1, 0.1, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+        self.window.swap_buffers();
+        parent.make_current();
+    }
+}
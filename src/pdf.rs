@@ -0,0 +1,154 @@
+extern crate printpdf;
+
+use self::printpdf::{
+    BuiltinFont, ColorBits, ColorSpace, Image, ImageTransform, ImageXObject, IndirectFontRef,
+    Mm, PdfDocument, PdfLayerReference, Px,
+};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use errors::ExportError;
+use renderer::Renderer;
+use slide::SlideManager;
+
+const MARGIN_MM: f64 = 10.0;
+const NOTES_FONT_SIZE: f64 = 11.0;
+/* Helvetica has no metrics here, so lines are wrapped at this average glyph width in ems */
+const AVERAGE_GLYPH_WIDTH: f64 = 0.5;
+const MM_PER_PT: f64 = 25.4 / 72.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageSize {
+    /* Portrait, with room for the notes under the slide */
+    A4,
+    #[default]
+    Widescreen,
+}
+
+impl PageSize {
+    /* Width and height in millimetres */
+    fn dimensions(self) -> (f64, f64) {
+        match self {
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::Widescreen => (297.0, 297.0 * 9.0 / 16.0),
+        }
+    }
+}
+
+impl FromStr for PageSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PageSize, String> {
+        match s {
+            "a4" => Ok(PageSize::A4),
+            "16:9" => Ok(PageSize::Widescreen),
+            _ => Err(format!("unknown page size {:?}, expected a4 or 16:9", s)),
+        }
+    }
+}
+
+/* Settings for the export-pdf subcommand */
+#[derive(Debug, Clone)]
+pub struct PdfExport {
+    pub output: PathBuf,
+    pub page_size: PageSize,
+    /* Print each slide's speaker notes under it */
+    pub notes: bool,
+}
+
+/* Renders every slide and writes them to a PDF, one page each, with the slide titles as
+ * bookmarks. printpdf cannot make text annotations, so the notes are printed on the page. */
+pub fn export_pdf(slides: &SlideManager, renderer: &Renderer, width: u32, height: u32,
+                  settings: &PdfExport) -> Result<(), ExportError> {
+    let document = PdfDocument::empty("Slides");
+    let font = document
+        .add_builtin_font(BuiltinFont::Helvetica)
+        .map_err(|e| ExportError::Pdf(e.to_string()))?;
+    let (page_width, page_height) = settings.page_size.dimensions();
+
+    for (index, slide) in slides.slides().iter().enumerate() {
+        let pixels = renderer.render_slide(index)?;
+        let (page, layer) = document.add_page(Mm(page_width), Mm(page_height), "Slide");
+        document.add_bookmark(slide.title.as_str(), page);
+        let layer = document.get_page(page).get_layer(layer);
+
+        let notes = if settings.notes { slide.notes.trim() } else { "" };
+        /* The slide fills a widescreen page unless there are notes to fit in */
+        let margin = if notes.is_empty() && settings.page_size == PageSize::Widescreen {
+            0.0
+        } else {
+            MARGIN_MM
+        };
+        let area_height = if notes.is_empty() {
+            page_height - margin * 2.0
+        } else {
+            (page_height - margin * 2.0) * 0.7
+        };
+
+        let ratio = f64::from(width) / f64::from(height);
+        let image_width = (page_width - margin * 2.0).min(area_height * ratio);
+        let image_height = image_width / ratio;
+        let image_x = (page_width - image_width) / 2.0;
+        let image_y = page_height - margin - image_height;
+        /* PDF images have no alpha here, and slides are opaque anyway */
+        let rgb = pixels.chunks(4).flat_map(|p| p[..3].iter().cloned()).collect();
+        Image::from(ImageXObject {
+            width: Px(width as usize),
+            height: Px(height as usize),
+            color_space: ColorSpace::Rgb,
+            bits_per_component: ColorBits::Bit8,
+            interpolate: true,
+            image_data: rgb,
+            image_filter: None,
+            clipping_bbox: None,
+        }).add_to_layer(layer.clone(), ImageTransform {
+            translate_x: Some(Mm(image_x)),
+            translate_y: Some(Mm(image_y)),
+            dpi: Some(f64::from(width) * 25.4 / image_width),
+            ..Default::default()
+        });
+
+        if !notes.is_empty() {
+            let top = image_y - MARGIN_MM;
+            write_notes(&layer, &font, notes, page_width - MARGIN_MM * 2.0, top);
+        }
+    }
+
+    let file = File::create(&settings.output)?;
+    document
+        .save(&mut BufWriter::new(file))
+        .map_err(|e| ExportError::Pdf(e.to_string()))
+}
+
+/* Writes `notes` downwards from `top`, wrapped to `width` millimetres */
+fn write_notes(layer: &PdfLayerReference, font: &IndirectFontRef, notes: &str, width: f64, top: f64) {
+    let line_height = NOTES_FONT_SIZE * 1.3 * MM_PER_PT;
+    let max_chars = (width / (NOTES_FONT_SIZE * AVERAGE_GLYPH_WIDTH * MM_PER_PT)) as usize;
+
+    let mut y = top - NOTES_FONT_SIZE * MM_PER_PT;
+    for line in notes.lines().flat_map(|line| wrap(line, max_chars)) {
+        if y < MARGIN_MM / 2.0 {
+            break;
+        }
+        layer.use_text(line, NOTES_FONT_SIZE, Mm(MARGIN_MM), Mm(y), font);
+        y -= line_height;
+    }
+}
+
+fn wrap(line: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for word in line.split_whitespace() {
+        let current = lines.last_mut().unwrap();
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > max_chars {
+            lines.push(word.to_string());
+        } else {
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+    }
+    lines
+}
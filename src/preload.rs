@@ -0,0 +1,165 @@
+extern crate gl;
+use gl::types::*;
+use glfw::{self, Context, Glfw, RenderContext, Window};
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use errors::WindowError;
+use framebuffer::Framebuffer;
+use window::{WindowBuilder, WindowEvents};
+
+/* Draws slide programs once on background threads before they are shown. Drivers put off much
+ * of the work of compiling a program until its first draw, which can stall a frame for 50-200ms
+ * when a slide comes up. Programs are shared between the contexts of a share group, so drawing
+ * one in a worker's context, into a 1x1 framebuffer, leaves it ready for the window. */
+pub struct Preloader {
+    jobs: Option<Sender<(usize, GLuint)>>,
+    ready: Receiver<(usize, GLuint)>,
+    /* By program id rather than slide, as a reload gives the slide a new program */
+    warmed: HashSet<GLuint>,
+    /* Slide index to the program sent to a worker */
+    in_flight: HashMap<usize, GLuint>,
+    workers: Vec<JoinHandle<()>>,
+    /* Hidden windows owning the workers' contexts. GLFW can only destroy them on this thread,
+     * once the workers have finished with them. */
+    windows: Vec<(Window, WindowEvents)>,
+}
+
+impl Preloader {
+    /* Starts `threads` workers, each with a hidden window sharing `parent`'s context */
+    pub fn new(glfw: &mut Glfw, parent: &Window, threads: usize) -> Result<Preloader, WindowError> {
+        let (jobs, job_queue) = channel();
+        let job_queue = Arc::new(Mutex::new(job_queue));
+        let (ready_tx, ready) = channel();
+
+        let mut windows = Vec::with_capacity(threads);
+        let mut workers = Vec::with_capacity(threads);
+        for _ in 0..threads {
+            let (mut window, events) = WindowBuilder::new()
+                .title("Preload")
+                .size(1, 1)
+                .visible(false)
+                .shared_with(parent)
+                .build(glfw)?;
+            let context = window.render_context();
+            let job_queue = job_queue.clone();
+            let ready_tx = ready_tx.clone();
+            workers.push(thread::spawn(move || run_worker(context, job_queue, ready_tx)));
+            windows.push((window, events));
+        }
+
+        Ok(Preloader {
+            jobs: Some(jobs),
+            ready,
+            warmed: HashSet::new(),
+            in_flight: HashMap::new(),
+            workers,
+            windows,
+        })
+    }
+
+    /* Queues slide `index`'s program unless it has been drawn already or is on its way */
+    pub fn request(&mut self, index: usize, program: GLuint) {
+        self.collect();
+        if self.warmed.contains(&program) || self.in_flight.get(&index) == Some(&program) {
+            return;
+        }
+        let sent = self.jobs.as_ref().map_or(false, |jobs| jobs.send((index, program)).is_ok());
+        if sent {
+            self.in_flight.insert(index, program);
+        }
+    }
+
+    /* Takes in whatever the workers have finished, without waiting */
+    pub fn collect(&mut self) {
+        while let Ok((index, program)) = self.ready.try_recv() {
+            self.finished(index, program);
+        }
+    }
+
+    /* Blocks until slide `index`'s program is ready, if it was sent to a worker, so it is swapped
+     * in before the slide is shown rather than halfway through a draw */
+    pub fn wait_for(&mut self, index: usize) {
+        self.collect();
+        while self.in_flight.contains_key(&index) {
+            match self.ready.recv() {
+                Ok((finished, program)) => self.finished(finished, program),
+                /* The workers have all gone, so nothing else will arrive */
+                Err(_) => {
+                    self.in_flight.clear();
+                    return;
+                }
+            }
+        }
+    }
+
+    fn finished(&mut self, index: usize, program: GLuint) {
+        if self.in_flight.get(&index) == Some(&program) {
+            self.in_flight.remove(&index);
+        }
+        self.warmed.insert(program);
+    }
+}
+
+impl Drop for Preloader {
+    fn drop(&mut self) {
+        /* Closing the queue ends each worker's loop */
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            worker.join().ok();
+        }
+        self.windows.clear();
+    }
+}
+
+fn run_worker(
+    mut context: RenderContext,
+    job_queue: Arc<Mutex<Receiver<(usize, GLuint)>>>,
+    ready: Sender<(usize, GLuint)>,
+) {
+    context.make_current();
+    let target = match Framebuffer::new(1, 1) {
+        Ok(target) => target,
+        Err(e) => {
+            println!("Warning: cannot preload slides: {}", e);
+            glfw::make_context_current(None);
+            return;
+        }
+    };
+    /* Vertex array objects are not shared, so each context needs its own */
+    let mut vao = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+    }
+
+    loop {
+        /* The lock is only held while waiting, so the other workers can take the next job */
+        let job = job_queue.lock().map(|queue| queue.recv());
+        let (index, program) = match job {
+            Ok(Ok(job)) => job,
+            _ => break,
+        };
+        target.bind();
+        unsafe {
+            gl::UseProgram(program);
+            gl::BindVertexArray(vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::BindVertexArray(0);
+            gl::UseProgram(0);
+            /* The work is only certain to be done once the draw has finished */
+            gl::Finish();
+        }
+        target.unbind();
+        if ready.send((index, program)).is_err() {
+            break;
+        }
+    }
+
+    unsafe {
+        gl::DeleteVertexArrays(1, &vao);
+    }
+    drop(target);
+    glfw::make_context_current(None);
+}
@@ -0,0 +1,407 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use errors::PreprocessError;
+
+/* Expands `#include "file"` and `#include <file>` directives. Includes are looked up relative to
+ * the including file first, then in each of `search_paths` in order. A file containing
+ * `#pragma once` is only expanded the first time it is included. */
+pub fn preprocess(src: &str, search_paths: &[&Path]) -> Result<String, PreprocessError> {
+    preprocess_with_includes(src, None, search_paths).map(|(src, _)| src)
+}
+
+/* Where each line of preprocessed source came from: the file, or None for source that was not
+ * read from a file, and the line in it counting from 1 */
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    lines: Vec<(Option<PathBuf>, usize)>,
+}
+
+impl SourceMap {
+    /* `line` counts from 1, as in compiler logs */
+    pub fn lookup(&self, line: usize) -> Option<(Option<&Path>, usize)> {
+        line.checked_sub(1)
+            .and_then(|i| self.lines.get(i))
+            .map(|&(ref file, line)| (file.as_deref(), line))
+    }
+}
+
+/* As `preprocess`, for source read from `file` if given, and also returning every file that was
+ * included, directly or transitively, so that they can be watched */
+pub fn preprocess_with_includes(src: &str, file: Option<&Path>, search_paths: &[&Path])
+    -> Result<(String, Vec<PathBuf>), PreprocessError> {
+    preprocess_with_source_map(src, file, search_paths).map(|(src, included, _)| (src, included))
+}
+
+/* As `preprocess_with_includes`, also returning the map from output lines back to the files and
+ * lines they were expanded from */
+pub fn preprocess_with_source_map(src: &str, file: Option<&Path>, search_paths: &[&Path])
+    -> Result<(String, Vec<PathBuf>, SourceMap), PreprocessError> {
+    let file = file.map(|f| fs::canonicalize(f).unwrap_or_else(|_| f.to_path_buf()));
+    let mut stack: Vec<PathBuf> = file.iter().cloned().collect();
+    let mut output = String::with_capacity(src.len());
+    let mut included = Vec::new();
+    let mut map = SourceMap::default();
+    let mut expansion = Expansion {
+        search_paths,
+        stack: &mut stack,
+        included: &mut included,
+        once: HashSet::new(),
+        output: &mut output,
+        map: &mut map,
+    };
+    expansion.expand(src, file.as_deref())?;
+    Ok((output, included, map))
+}
+
+/* Adds `#define NAME VALUE` for each define just after the #version line, which has to come
+ * first, or at the top if there isn't one. The new lines map to the #version line, the nearest
+ * thing the user wrote. */
+pub fn inject_defines(src: &str, map: &SourceMap, defines: &[(String, String)]) -> (String, SourceMap) {
+    if defines.is_empty() {
+        return (src.to_string(), map.clone());
+    }
+
+    let lines: Vec<&str> = src.lines().collect();
+    let at = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with("#version"))
+        .map_or(0, |i| i + 1);
+    let origin = at
+        .checked_sub(1)
+        .and_then(|i| map.lines.get(i).cloned())
+        .unwrap_or((None, 1));
+
+    let mut output = String::with_capacity(src.len());
+    let mut injected = SourceMap::default();
+    for (i, line) in lines.iter().enumerate() {
+        if i == at {
+            for &(ref name, ref value) in defines {
+                output.push_str(&format!("#define {} {}\n", name, value));
+                injected.lines.push(origin.clone());
+            }
+        }
+        output.push_str(line);
+        output.push('\n');
+        injected.lines.extend(map.lines.get(i).cloned());
+    }
+    if at >= lines.len() {
+        for &(ref name, ref value) in defines {
+            output.push_str(&format!("#define {} {}\n", name, value));
+            injected.lines.push(origin.clone());
+        }
+    }
+    (output, injected)
+}
+
+/* Makes desktop GLSL compile as GLSL ES 3.00: the #version line becomes `#version 300 es`, and
+ * default precisions, which ES fragment shaders must have, are declared after it. The lines added
+ * map to the #version line, and source without one gets both at the top. */
+pub fn to_glsl_es(src: &str, map: &SourceMap) -> (String, SourceMap) {
+    const HEADER: &[&str] = &[
+        "#version 300 es",
+        "precision highp float;",
+        "precision highp int;",
+        "precision highp sampler2D;",
+        "precision highp sampler3D;",
+        "precision highp sampler2DArray;",
+    ];
+
+    let lines: Vec<&str> = src.lines().collect();
+    let version = lines.iter().position(|line| line.trim_start().starts_with("#version"));
+    let origin = version
+        .and_then(|i| map.lines.get(i).cloned())
+        .unwrap_or((None, 1));
+
+    let mut output = String::with_capacity(src.len() + 100);
+    let mut rewritten = SourceMap::default();
+    for header in HEADER {
+        output.push_str(header);
+        output.push('\n');
+        rewritten.lines.push(origin.clone());
+    }
+    for (i, line) in lines.iter().enumerate() {
+        if Some(i) == version {
+            continue;
+        }
+        output.push_str(line);
+        output.push('\n');
+        rewritten.lines.extend(map.lines.get(i).cloned());
+    }
+    (output, rewritten)
+}
+
+/* Rewrites the line numbers in a compiler log, which count lines of the preprocessed source, to
+ * the file and line of the original source. Drivers prefix messages with "0:12(5): " (Mesa),
+ * "0(12) : " (NVIDIA) or "ERROR: 0:12: " (AMD and others); lines in other formats are kept as
+ * they are. */
+pub fn annotate_error_log(raw_log: &str, source_map: &SourceMap) -> String {
+    raw_log
+        .lines()
+        .map(|line| annotate_line(line, source_map).unwrap_or_else(|| line.to_string()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn annotate_line(line: &str, source_map: &SourceMap) -> Option<String> {
+    let start = ["ERROR: ", "WARNING: "]
+        .iter()
+        .find(|prefix| line.starts_with(*prefix))
+        .map_or(0, |prefix| prefix.len());
+    let rest = &line[start..];
+
+    let string_len = rest.find(|c: char| !c.is_ascii_digit())?;
+    if string_len == 0 {
+        return None;
+    }
+    let (open, close) = match rest[string_len..].chars().next()? {
+        ':' => (":", ""),
+        '(' => ("(", ")"),
+        _ => return None,
+    };
+    let number_start = string_len + 1;
+    let number_len = rest[number_start..].find(|c: char| !c.is_ascii_digit())?;
+    let number_end = number_start + number_len;
+    if number_len == 0 || !rest[number_end..].starts_with(close) {
+        return None;
+    }
+
+    let number: usize = rest[number_start..number_end].parse().ok()?;
+    let (file, original) = source_map.lookup(number)?;
+    let source = match file {
+        Some(file) => file.display().to_string(),
+        None => rest[..string_len].to_string(),
+    };
+    Some(format!(
+        "{}{}{}{}{}",
+        &line[..start],
+        source,
+        open,
+        original,
+        &rest[number_end..]
+    ))
+}
+
+/* The state of one preprocess call, shared by the files it expands */
+struct Expansion<'a> {
+    search_paths: &'a [&'a Path],
+    /* The chain of files being expanded, to catch circular includes */
+    stack: &'a mut Vec<PathBuf>,
+    included: &'a mut Vec<PathBuf>,
+    /* Files that have said #pragma once, which later includes of skip */
+    once: HashSet<PathBuf>,
+    output: &'a mut String,
+    map: &'a mut SourceMap,
+}
+
+impl<'a> Expansion<'a> {
+    fn expand(&mut self, src: &str, file: Option<&Path>) -> Result<(), PreprocessError> {
+        for (i, line) in src.lines().enumerate() {
+            if is_pragma_once(line) {
+                /* Outside a file there is nothing to include twice, so it is just dropped */
+                if let Some(file) = file {
+                    self.once.insert(file.to_path_buf());
+                }
+                continue;
+            }
+            let name = match include_name(line) {
+                Some(Ok(name)) => name,
+                Some(Err(())) => {
+                    return Err(PreprocessError::Malformed {
+                        file: file.map(Path::to_path_buf),
+                        line: i + 1,
+                        text: line.to_string(),
+                    })
+                }
+                None => {
+                    self.output.push_str(line);
+                    self.output.push('\n');
+                    self.map.lines.push((file.map(Path::to_path_buf), i + 1));
+                    continue;
+                }
+            };
+
+            let path = find_include(name, file, self.search_paths).ok_or_else(|| {
+                PreprocessError::NotFound {
+                    file: file.map(Path::to_path_buf),
+                    line: i + 1,
+                    name: name.to_string(),
+                }
+            })?;
+
+            /* Checked before the stack, so a file guarded this way may include itself */
+            if self.once.contains(&path) {
+                continue;
+            }
+            if self.stack.contains(&path) {
+                let mut chain = self.stack.clone();
+                chain.push(path);
+                return Err(PreprocessError::Circular(chain));
+            }
+
+            let contents = fs::read_to_string(&path)
+                .map_err(|error| PreprocessError::Io { path: path.clone(), error })?;
+            if !self.included.contains(&path) {
+                self.included.push(path.clone());
+            }
+
+            /* Compiler line numbers count lines of the output, and the map takes them back to
+             * the included file */
+            self.stack.push(path.clone());
+            self.expand(&contents, Some(&path))?;
+            self.stack.pop();
+        }
+        Ok(())
+    }
+}
+
+fn is_pragma_once(line: &str) -> bool {
+    let line = line.trim();
+    if !line.starts_with('#') {
+        return false;
+    }
+    let mut words = line[1..].split_whitespace();
+    words.next() == Some("pragma") && words.next() == Some("once") && words.next().is_none()
+}
+
+/* None if the line is not an include directive, Err if it is one but has no valid file name */
+fn include_name(line: &str) -> Option<Result<&str, ()>> {
+    let line = line.trim();
+    if !line.starts_with('#') {
+        return None;
+    }
+    let directive = line[1..].trim_start();
+    if !directive.starts_with("include") {
+        return None;
+    }
+
+    let rest = directive["include".len()..].trim();
+    let quoted = rest.starts_with('"') && rest.ends_with('"');
+    let bracketed = rest.starts_with('<') && rest.ends_with('>');
+    if rest.len() < 2 || !(quoted || bracketed) {
+        return Some(Err(()));
+    }
+    let name = &rest[1..rest.len() - 1];
+
+    if name.is_empty() {
+        Some(Err(()))
+    } else {
+        Some(Ok(name))
+    }
+}
+
+fn find_include(name: &str, file: Option<&Path>, search_paths: &[&Path]) -> Option<PathBuf> {
+    let including_dir = file.and_then(Path::parent);
+    including_dir
+        .into_iter()
+        .chain(search_paths.iter().cloned())
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+        .and_then(|path| fs::canonicalize(path).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    /* A fresh directory holding `files`, each a (name, contents) pair */
+    fn include_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("slideshow-preprocess-{}-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        for &(file, contents) in files {
+            fs::write(dir.join(file), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn recognises_pragma_once() {
+        assert!(is_pragma_once("#pragma once"));
+        assert!(is_pragma_once("  #  pragma   once  "));
+        assert!(!is_pragma_once("#pragma optimize(off)"));
+        assert!(!is_pragma_once("#pragma once more"));
+        assert!(!is_pragma_once("// #pragma once"));
+    }
+
+    #[test]
+    fn pragma_once_files_are_expanded_once() {
+        let dir = include_dir("once", &[("common.glsl", "#pragma once\nfloat common;\n")]);
+        let src = "#include \"common.glsl\"\n#include <common.glsl>\nvoid main() {}\n";
+        let output = preprocess(src, &[&dir]).unwrap();
+        fs::remove_dir_all(&dir).ok();
+        assert_eq!(output, "float common;\nvoid main() {}\n");
+    }
+
+    #[test]
+    fn files_without_pragma_once_are_expanded_every_time() {
+        let dir = include_dir("twice", &[("common.glsl", "float common;\n")]);
+        let src = "#include \"common.glsl\"\n#include \"common.glsl\"\n";
+        let output = preprocess(src, &[&dir]).unwrap();
+        fs::remove_dir_all(&dir).ok();
+        assert_eq!(output, "float common;\nfloat common;\n");
+    }
+
+    #[test]
+    fn pragma_once_files_may_include_themselves() {
+        let dir = include_dir("self", &[
+            ("guarded.glsl", "#pragma once\n#include \"guarded.glsl\"\nfloat guarded;\n"),
+            ("unguarded.glsl", "#include \"unguarded.glsl\"\n"),
+        ]);
+        let guarded = preprocess("#include \"guarded.glsl\"\n", &[&dir]);
+        let unguarded = preprocess("#include \"unguarded.glsl\"\n", &[&dir]);
+        fs::remove_dir_all(&dir).ok();
+        assert_eq!(guarded.unwrap(), "float guarded;\n");
+        match unguarded {
+            Err(PreprocessError::Circular(_)) => {}
+            other => panic!("expected a circular include, got {:?}", other),
+        }
+    }
+
+    fn defines(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|&(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    fn map_of(src: &str) -> SourceMap {
+        SourceMap { lines: (1..=src.lines().count()).map(|line| (None, line)).collect() }
+    }
+
+    #[test]
+    fn defines_go_after_the_version_line() {
+        let src = "// header\n#version 330 core\nvoid main() {}\n";
+        let defines = defines(&[("STEPS", "8"), ("FAST", "")]);
+        let (output, map) = inject_defines(src, &map_of(src), &defines);
+        assert_eq!(
+            output,
+            "// header\n#version 330 core\n#define STEPS 8\n#define FAST \nvoid main() {}\n"
+        );
+        /* The defines map to the #version line, and what follows keeps its own line */
+        assert_eq!(map.lookup(3), Some((None, 2)));
+        assert_eq!(map.lookup(4), Some((None, 2)));
+        assert_eq!(map.lookup(5), Some((None, 3)));
+    }
+
+    #[test]
+    fn defines_go_at_the_top_without_a_version_line() {
+        let src = "void main() {}\n";
+        let (output, map) = inject_defines(src, &map_of(src), &defines(&[("STEPS", "8")]));
+        assert_eq!(output, "#define STEPS 8\nvoid main() {}\n");
+        assert_eq!(map.lookup(2), Some((None, 1)));
+    }
+
+    #[test]
+    fn defines_follow_a_version_line_at_the_end() {
+        let src = "#version 330 core";
+        let (output, _) = inject_defines(src, &map_of(src), &defines(&[("STEPS", "8")]));
+        assert_eq!(output, "#version 330 core\n#define STEPS 8\n");
+    }
+
+    #[test]
+    fn no_defines_leaves_the_source_alone() {
+        let src = "#version 330 core\nvoid main() {}";
+        let (output, _) = inject_defines(src, &map_of(src), &[]);
+        assert_eq!(output, src);
+    }
+}
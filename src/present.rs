@@ -0,0 +1,83 @@
+extern crate spin_sleep;
+
+use glfw;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentMode {
+    /* Wait for the display's vertical blank before each swap */
+    #[default]
+    Vsync,
+    /* Swap as soon as each frame is done */
+    Immediate,
+    /* Swap immediately, but never more often than this many frames per second */
+    FrameCap(u32),
+}
+
+impl PresentMode {
+    pub fn swap_interval(&self) -> glfw::SwapInterval {
+        match *self {
+            PresentMode::Vsync => glfw::SwapInterval::Sync(1),
+            PresentMode::Immediate | PresentMode::FrameCap(_) => glfw::SwapInterval::None,
+        }
+    }
+}
+
+/* Accepts `vsync`, `immediate`, or a frame rate cap such as `30` */
+impl FromStr for PresentMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PresentMode, String> {
+        match s {
+            "vsync" => Ok(PresentMode::Vsync),
+            "immediate" => Ok(PresentMode::Immediate),
+            _ => match s.parse::<u32>() {
+                Ok(0) => Err("the frame rate cap must be greater than zero".to_string()),
+                Ok(fps) => Ok(PresentMode::FrameCap(fps)),
+                Err(_) => Err(format!("expected vsync, immediate or a frame rate, not {}", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for PresentMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PresentMode::Vsync => write!(f, "vsync"),
+            PresentMode::Immediate => write!(f, "immediate"),
+            PresentMode::FrameCap(fps) => write!(f, "{}", fps),
+        }
+    }
+}
+
+/* Holds each frame back until its slot under a frame rate cap. Sleeping alone can overshoot by a
+ * millisecond or more, so spin_sleep spins through the last part of the wait. */
+pub struct FramePacer {
+    frame_time: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new(mode: PresentMode) -> FramePacer {
+        let frame_time = match mode {
+            PresentMode::FrameCap(fps) => Some(Duration::from_secs(1) / fps),
+            _ => None,
+        };
+        FramePacer {
+            frame_time,
+            next_frame: Instant::now(),
+        }
+    }
+
+    /* Call just before swapping buffers */
+    pub fn wait(&mut self) {
+        if let Some(frame_time) = self.frame_time {
+            let now = Instant::now();
+            /* After a slow frame, start counting again from now rather than rushing to catch up */
+            self.next_frame = (self.next_frame + frame_time).max(now);
+            spin_sleep::sleep_until(self.next_frame);
+        }
+    }
+}
@@ -0,0 +1,310 @@
+extern crate gl;
+use gl::types::*;
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+
+use glfw::{self, Context};
+
+use errors::Result;
+use framebuffer::Framebuffer;
+use image_slide::ImageSlide;
+use quad::FullscreenQuad;
+use shader::{ActivatedShader, ShaderProgram};
+use shadertoy::ShaderToyUniforms;
+use slide::{self, SlideManager};
+use text::TextRenderer;
+use texture::Texture2D;
+use timer::{self, PresentationTimer};
+use window::WindowBuilder;
+
+/* Width of the offscreen previews; their height follows the deck's aspect ratio */
+const PREVIEW_WIDTH: u32 = 960;
+const MARGIN: f32 = 20.0;
+const FONT_SIZE: f32 = 24.0;
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const DIM_TEXT_COLOR: [f32; 4] = [0.7, 0.7, 0.7, 1.0];
+const THUMBNAIL_STRIP_HEIGHT: f32 = 90.0;
+/* Width of the outline around the current slide's thumbnail */
+const HIGHLIGHT_WIDTH: f32 = 3.0;
+const HIGHLIGHT_COLOR: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+
+/* A second window for the speaker, showing the current slide, a preview of the next one, the
+ * speaker notes, the time and a strip of thumbnails of every slide. Its key presses drive the
+ * presentation like the main window's, and clicking a thumbnail jumps to that slide.
+ *
+ * The window shares the presentation's context, but vertex arrays and framebuffers are not
+ * shared between contexts. The previews are rendered in the presentation's context and only
+ * their textures are drawn here, with a quad and text renderer of this window's own. */
+pub struct PresenterWindow {
+    window: glfw::Window,
+    events: Receiver<(f64, glfw::WindowEvent)>,
+    current: Framebuffer,
+    next: Framebuffer,
+    quad: FullscreenQuad,
+    program: ShaderProgram,
+    text: Option<TextRenderer>,
+    thumbnails: Vec<Texture2D>,
+    /* Where each thumbnail was last drawn, from the top left in window coordinates */
+    thumbnail_rects: Vec<(usize, [f32; 4])>,
+}
+
+impl PresenterWindow {
+    pub fn new(
+        glfw: &mut glfw::Glfw,
+        parent: &mut glfw::Window,
+        width: u32,
+        height: u32,
+        aspect_ratio: f32,
+        deck_title: Option<&str>,
+        font: Option<&Path>,
+    ) -> Result<PresenterWindow> {
+        let preview_height = (PREVIEW_WIDTH as f32 / aspect_ratio).round().max(1.0) as u32;
+        let current = Framebuffer::new(PREVIEW_WIDTH, preview_height)?;
+        let next = Framebuffer::new(PREVIEW_WIDTH, preview_height)?;
+
+        let title = match deck_title {
+            Some(deck_title) => format!("{} - Presenter", deck_title),
+            None => "Presenter".to_string(),
+        };
+        let (mut window, events) = WindowBuilder::new()
+            .title(&title)
+            .size(width, height)
+            .shared_with(parent)
+            .build(glfw)?;
+        window.set_key_polling(true);
+        window.set_mouse_button_polling(true);
+
+        window.make_current();
+        /* Only the presentation window waits for vsync, or every frame would wait twice */
+        glfw.set_swap_interval(glfw::SwapInterval::None);
+        let objects = (|| -> Result<_> {
+            let text = match font {
+                Some(font) => Some(TextRenderer::from_file(font)?),
+                None => None,
+            };
+            Ok((FullscreenQuad::new(), ImageSlide::program()?, text))
+        })();
+        parent.make_current();
+        let (quad, program, text) = objects?;
+
+        Ok(PresenterWindow {
+            window,
+            events,
+            current,
+            next,
+            quad,
+            program,
+            text,
+            thumbnails: Vec::new(),
+            thumbnail_rects: Vec::new(),
+        })
+    }
+
+    /* Renders the thumbnail strip. Call with the presentation's context current, and again
+     * whenever a slide is reloaded. */
+    pub fn render_thumbnails<F>(&mut self, slides: &SlideManager, draw: F) -> Result<()>
+        where F: Fn(&ActivatedShader) {
+        let mut thumbnails = Vec::with_capacity(slides.len());
+        for index in 0..slides.len() {
+            thumbnails.push(slides.render_thumbnail(index, &draw)?);
+        }
+        self.thumbnails = thumbnails;
+        Ok(())
+    }
+
+    /* Returns the key events for the usual input handling, and the slide whose thumbnail was
+     * clicked, if any */
+    pub fn flush_events(&mut self) -> (Vec<glfw::WindowEvent>, Option<usize>) {
+        let mut keys = Vec::new();
+        let mut clicked = None;
+        for (_, event) in glfw::flush_messages(&self.events) {
+            match event {
+                glfw::WindowEvent::MouseButton(glfw::MouseButtonLeft, glfw::Action::Press, _) => {
+                    /* Thumbnails are laid out in framebuffer pixels, which may not be the
+                     * window's screen coordinates on high DPI displays */
+                    let (x, y) = self.window.get_cursor_pos();
+                    let (width, _) = self.window.get_size();
+                    let (framebuffer_width, _) = self.window.get_framebuffer_size();
+                    let scale = framebuffer_width as f32 / width.max(1) as f32;
+                    let (x, y) = (x as f32 * scale, y as f32 * scale);
+                    clicked = self
+                        .thumbnail_rects
+                        .iter()
+                        .find(|&&(_, r)| x >= r[0] && x < r[0] + r[2] && y >= r[1] && y < r[1] + r[3])
+                        .map(|&(index, _)| index)
+                        .or(clicked);
+                }
+                glfw::WindowEvent::Key(..) => keys.push(event),
+                _ => {}
+            }
+        }
+        (keys, clicked)
+    }
+
+    /* Renders the current and next slides into the previews. Call with the presentation's
+     * context current, and with `shadertoy` already advanced for this frame. */
+    pub fn render_previews<F>(&self, slides: &SlideManager, shadertoy: &ShaderToyUniforms, draw: F)
+        where F: Fn(&ActivatedShader) {
+        let mut uniforms = shadertoy.clone();
+        uniforms.resolution = [self.current.width() as f32, self.current.height() as f32];
+
+        let index = slides.current_index();
+        for &(target, index) in &[(&self.current, index), (&self.next, index + 1)] {
+            target.bind();
+            unsafe {
+                gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+            }
+            if let Some(slide) = slides.slides().get(index) {
+                uniforms.upload_to(&slide.program);
+                slides.activate_slide(index, &draw);
+            }
+            target.unbind();
+        }
+    }
+
+    /* Draws the presenter view and makes `parent` current again afterwards */
+    pub fn present(&mut self, parent: &mut glfw::Window, slides: &SlideManager, timer: &PresentationTimer) {
+        /* Closing the presenter window only hides it, the presentation carries on */
+        if self.window.should_close() {
+            self.window.set_should_close(false);
+            self.window.hide();
+        }
+        if !self.window.is_visible() {
+            return;
+        }
+
+        self.window.make_current();
+        let (width, height) = self.window.get_framebuffer_size();
+        let (width, height) = (width as f32, height as f32);
+        unsafe {
+            gl::Viewport(0, 0, width as GLint, height as GLint);
+            gl::ClearColor(0.1, 0.1, 0.1, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+
+        /* The current slide takes the left 60% of the window, the next slide the rest; both are
+         * placed from the top left corner */
+        let ratio = self.current.width() as f32 / self.current.height() as f32;
+        let current_width = width * 0.6 - MARGIN * 1.5;
+        let next_width = width * 0.4 - MARGIN * 1.5;
+        let current_height = current_width / ratio;
+        let current_rect = [MARGIN, MARGIN, current_width, current_height];
+        self.draw_texture(self.current.color_texture(), current_rect, height);
+        if slides.current_index() + 1 < slides.len() {
+            let x = MARGIN * 2.0 + current_width;
+            let next_rect = [x, MARGIN, next_width, next_width / ratio];
+            self.draw_texture(self.next.color_texture(), next_rect, height);
+        }
+        unsafe {
+            gl::Viewport(0, 0, width as GLint, height as GLint);
+        }
+
+        if let Some(ref text) = self.text {
+            let slide = slides.current();
+            let line_height = text.line_height(FONT_SIZE);
+            let mut y = MARGIN * 2.0 + current_height;
+            let status = format!(
+                "{}  {}    {} / {}",
+                slides.position_label(),
+                slide.title,
+                timer::format_duration(timer.elapsed()),
+                timer::format_duration(timer.slide_elapsed()),
+            );
+            text.draw_string(&status, MARGIN, y, FONT_SIZE, TEXT_COLOR);
+            y += line_height * 1.5;
+            text.draw_string(&slide.notes, MARGIN, y, FONT_SIZE, DIM_TEXT_COLOR);
+
+            if let Some(next) = slides.slides().get(slides.current_index() + 1) {
+                let x = MARGIN * 2.0 + current_width;
+                let next_y = MARGIN * 2.0 + next_width / ratio;
+                text.draw_string(&format!("Next: {}", next.title), x, next_y, FONT_SIZE, DIM_TEXT_COLOR);
+            }
+        }
+
+        self.draw_thumbnail_strip(slides.current_index(), width, height);
+
+        self.window.swap_buffers();
+        parent.make_current();
+    }
+
+    /* Draws every thumbnail along the bottom of the window, scrolled so the current slide is in
+     * view, over whatever notes ran that far down */
+    fn draw_thumbnail_strip(&mut self, current: usize, width: f32, height: f32) {
+        let strip_top = height - THUMBNAIL_STRIP_HEIGHT - MARGIN;
+        fill_rect([0.0, strip_top - MARGIN / 2.0, width, height - strip_top + MARGIN / 2.0], height,
+                  [0.1, 0.1, 0.1, 1.0]);
+
+        let thumbnail_height = THUMBNAIL_STRIP_HEIGHT;
+        let thumbnail_width = thumbnail_height * slide::THUMBNAIL_WIDTH as f32
+            / slide::THUMBNAIL_HEIGHT as f32;
+        let step = thumbnail_width + MARGIN / 2.0;
+        let total = step * self.thumbnails.len() as f32 - MARGIN / 2.0;
+        let available = width - MARGIN * 2.0;
+        let offset = if total <= available {
+            0.0
+        } else {
+            let centred = step * current as f32 + thumbnail_width / 2.0 - available / 2.0;
+            centred.max(0.0).min(total - available)
+        };
+
+        self.thumbnail_rects.clear();
+        for (index, thumbnail) in self.thumbnails.iter().enumerate() {
+            let x = MARGIN + step * index as f32 - offset;
+            if x + thumbnail_width < 0.0 || x > width {
+                continue;
+            }
+            let rect = [x, strip_top, thumbnail_width, thumbnail_height];
+            if index == current {
+                let h = HIGHLIGHT_WIDTH;
+                fill_rect([x - h, strip_top - h, thumbnail_width + h * 2.0, thumbnail_height + h * 2.0],
+                          height, HIGHLIGHT_COLOR);
+            }
+            self.draw_texture(thumbnail, rect, height);
+            self.thumbnail_rects.push((index, rect));
+        }
+        unsafe {
+            gl::Viewport(0, 0, width as GLint, height as GLint);
+        }
+    }
+
+    /* `rect` is x, y, width and height in pixels from the top left of the window. Leaves the
+     * viewport set to `rect`. */
+    fn draw_texture(&self, texture: &Texture2D, rect: [f32; 4], window_height: f32) {
+        if rect[2] <= 0.0 || rect[3] <= 0.0 {
+            return;
+        }
+        unsafe {
+            gl::Viewport(
+                rect[0] as GLint,
+                (window_height - rect[1] - rect[3]) as GLint,
+                rect[2] as GLsizei,
+                rect[3] as GLsizei,
+            );
+        }
+        texture.bind_to_unit(0);
+        self.program.set_uniform_vec2(
+            "u_image_size",
+            [texture.width() as f32, texture.height() as f32],
+        );
+        self.program.set_uniform_vec2("u_resolution", [rect[2], rect[3]]);
+        self.program.set_uniform_bool("u_cover", false);
+        self.program.activate(|_| self.quad.draw());
+    }
+}
+
+/* Fills `rect`, from the top left like draw_texture, with a solid colour */
+fn fill_rect(rect: [f32; 4], window_height: f32, color: [f32; 4]) {
+    unsafe {
+        gl::Enable(gl::SCISSOR_TEST);
+        gl::Scissor(
+            rect[0] as GLint,
+            (window_height - rect[1] - rect[3]) as GLint,
+            rect[2] as GLsizei,
+            rect[3] as GLsizei,
+        );
+        gl::ClearColor(color[0], color[1], color[2], color[3]);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+        gl::Disable(gl::SCISSOR_TEST);
+    }
+}
@@ -0,0 +1,96 @@
+extern crate gl;
+use gl::types::*;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+/* Two triangles covering the whole of normalised device coordinates */
+const VERTICES: [f32; 18] = [
+    -1.0, -1.0, 0.0,
+    1.0, -1.0, 0.0,
+    1.0, 1.0, 0.0,
+    -1.0, -1.0, 0.0,
+    1.0, 1.0, 0.0,
+    -1.0, 1.0, 0.0,
+];
+
+/* Vertex shader for drawing the quad as it is, for fragment shaders that only need
+ * gl_FragCoord or nothing at all */
+pub const PASSTHROUGH_VERTEX_SHADER: &str = "#version 330 core
+layout (location = 0) in vec3 aPos;
+
+void main() {
+    gl_Position = vec4(aPos, 1.0);
+}
+";
+
+/* Fills whatever is drawn with the u_color uniform */
+pub const SOLID_COLOR_FRAGMENT_SHADER: &str = "#version 330 core
+out vec4 FragColor;
+uniform vec4 u_color;
+
+void main() {
+    FragColor = u_color;
+}
+";
+
+/* The positions are bound to attribute location 0 as a vec3 */
+pub struct FullscreenQuad {
+    vao: GLuint,
+    vbo: GLuint,
+}
+
+impl FullscreenQuad {
+    pub fn new() -> FullscreenQuad {
+        let (mut vao, mut vbo) = (0, 0);
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(&VERTICES) as GLsizeiptr,
+                VERTICES.as_ptr() as *const c_void,
+                gl::STATIC_DRAW,
+            );
+            gl::VertexAttribPointer(
+                0,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                3 * mem::size_of::<GLfloat>() as GLsizei,
+                ptr::null(),
+            );
+            gl::EnableVertexAttribArray(0);
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+        FullscreenQuad { vao, vbo }
+    }
+
+    pub fn draw(&self) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, (VERTICES.len() / 3) as GLsizei);
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl Default for FullscreenQuad {
+    fn default() -> FullscreenQuad {
+        FullscreenQuad::new()
+    }
+}
+
+impl Drop for FullscreenQuad {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+extern crate gl;
+use gl::types::*;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, Command, Stdio};
+use std::ptr;
+use std::slice;
+use std::time::Instant;
+
+#[cfg(feature = "audio")]
+use audio::AudioRecording;
+use errors::ExportError;
+use framebuffer::Framebuffer;
+
+/* Frames are read back into this many pixel buffers in turn, so a buffer is only mapped two
+ * frames after its read was started, by which time the GPU has long finished it */
+const PBO_COUNT: usize = 3;
+
+/* Records the window, as the audience sees it, to a video file while presenting. Frames are
+ * copied into a fixed size target, read back asynchronously through a ring of pixel buffers and
+ * piped to ffmpeg; the default audio input is recorded alongside to a WAV file when the audio
+ * feature is enabled, and the two are muxed together in finish. */
+pub struct Recorder {
+    output: PathBuf,
+    fps: u32,
+    target: Framebuffer,
+    pbos: [GLuint; PBO_COUNT],
+    next_pbo: usize,
+    /* Buffers with a read in flight, oldest first, and how many frames each one stands for */
+    pending: VecDeque<(GLuint, u32)>,
+    ffmpeg: Child,
+    video_path: PathBuf,
+    #[cfg(feature = "audio")]
+    audio: Option<(AudioRecording, PathBuf)>,
+    started: Instant,
+    frames_written: u64,
+}
+
+impl Recorder {
+    pub fn start(output: &Path, width: u32, height: u32, fps: u32) -> Result<Recorder, ExportError> {
+        let target = Framebuffer::new(width, height)?;
+        let video_path = temporary_path(output, "video.mkv");
+
+        /* Rows come back from GL bottom first */
+        let ffmpeg = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+            .arg("-s").arg(format!("{}x{}", width, height))
+            .arg("-r").arg(fps.to_string())
+            .args(["-i", "-", "-vf", "vflip", "-c:v", "libx264", "-preset", "veryfast"])
+            .args(["-pix_fmt", "yuv420p"])
+            .arg(&video_path)
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| ExportError::Video(format!("cannot run ffmpeg: {}", e)))?;
+
+        let mut pbos = [0; PBO_COUNT];
+        let size = width as usize * height as usize * 4;
+        unsafe {
+            gl::GenBuffers(PBO_COUNT as GLsizei, pbos.as_mut_ptr());
+            for &pbo in &pbos {
+                gl::BindBuffer(gl::PIXEL_PACK_BUFFER, pbo);
+                gl::BufferData(gl::PIXEL_PACK_BUFFER, size as GLsizeiptr, ptr::null(), gl::STREAM_READ);
+            }
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+
+        #[cfg(feature = "audio")]
+        let audio = {
+            let audio_path = temporary_path(output, "audio.wav");
+            match AudioRecording::start(&audio_path) {
+                Ok(recording) => Some((recording, audio_path)),
+                Err(e) => {
+                    println!("Warning: recording without audio: {}", e);
+                    None
+                }
+            }
+        };
+
+        Ok(Recorder {
+            output: output.to_path_buf(),
+            fps,
+            target,
+            pbos,
+            next_pbo: 0,
+            pending: VecDeque::with_capacity(PBO_COUNT),
+            ffmpeg,
+            video_path,
+            #[cfg(feature = "audio")]
+            audio,
+            started: Instant::now(),
+            frames_written: 0,
+        })
+    }
+
+    /* Captures `rect` of the window. Call this once the frame has been drawn and before buffers
+     * are swapped. The video runs at a fixed frame rate, so a frame is repeated when drawing falls
+     * behind and dropped when it runs ahead. A multisampled window can only be copied at the size the
+     * recording started at, so with --msaa frames are lost while the window is another size. */
+    pub fn capture(&mut self, rect: [GLint; 4]) -> Result<(), ExportError> {
+        let due = (self.started.elapsed().as_secs_f64() * self.fps as f64) as u64 + 1;
+        let queued: u64 = self.pending.iter().map(|&(_, count)| u64::from(count)).sum();
+        let count = due.saturating_sub(self.frames_written + queued);
+        if count == 0 {
+            return Ok(());
+        }
+
+        if self.pending.len() == PBO_COUNT {
+            self.write_oldest()?;
+        }
+        let pbo = self.pbos[self.next_pbo];
+        self.next_pbo = (self.next_pbo + 1) % PBO_COUNT;
+        self.target.blit_from_window(rect);
+        self.target.read_pixels_to_buffer(pbo);
+        self.pending.push_back((pbo, count as u32));
+        Ok(())
+    }
+
+    /* Writes out the frames still in flight and waits for the video, and then the audio, to be
+     * encoded into the output file */
+    pub fn finish(mut self) -> Result<(), ExportError> {
+        let mut written = Ok(());
+        while written.is_ok() && !self.pending.is_empty() {
+            written = self.write_oldest();
+        }
+        /* A write fails because ffmpeg has exited, so what ffmpeg says comes first */
+        self.wait_for_ffmpeg()?;
+        written?;
+
+        #[cfg(feature = "audio")]
+        {
+            if let Some((recording, audio_path)) = self.audio.take() {
+                let result = recording
+                    .finish()
+                    .map_err(ExportError::Audio)
+                    .and_then(|_| mux(&self.video_path, Some(audio_path.as_path()), &self.output));
+                fs::remove_file(&audio_path).ok();
+                return result;
+            }
+        }
+        /* Drop removes the video once it has been copied */
+        mux(&self.video_path, None, &self.output)
+    }
+
+    /* Closes ffmpeg's stdin, which tells it there are no more frames, and waits for it to exit */
+    fn wait_for_ffmpeg(&mut self) -> Result<(), ExportError> {
+        drop(self.ffmpeg.stdin.take());
+        let mut stderr = String::new();
+        if let Some(mut pipe) = self.ffmpeg.stderr.take() {
+            pipe.read_to_string(&mut stderr).ok();
+        }
+        let status = self.ffmpeg.wait()?;
+        if !status.success() {
+            return Err(ExportError::Video(format!("ffmpeg failed with {}: {}", status, stderr.trim())));
+        }
+        Ok(())
+    }
+
+    fn write_oldest(&mut self) -> Result<(), ExportError> {
+        let (pbo, count) = match self.pending.pop_front() {
+            Some(oldest) => oldest,
+            None => return Ok(()),
+        };
+        let size = self.target.width() as usize * self.target.height() as usize * 4;
+        let stdin = self.ffmpeg.stdin.as_mut().expect("ffmpeg stdin is piped");
+        let mut result = Ok(());
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, pbo);
+            let mapped = gl::MapBuffer(gl::PIXEL_PACK_BUFFER, gl::READ_ONLY) as *const u8;
+            if mapped.is_null() {
+                result = Err(ExportError::Video("cannot map a frame's pixel buffer".to_string()));
+            } else {
+                let pixels = slice::from_raw_parts(mapped, size);
+                for _ in 0..count {
+                    /* A write error means ffmpeg has exited, and finish reports why */
+                    if let Err(e) = stdin.write_all(pixels) {
+                        result = Err(ExportError::Video(format!("cannot write to ffmpeg: {}", e)));
+                        break;
+                    }
+                }
+                gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+            }
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        if result.is_ok() {
+            self.frames_written += u64::from(count);
+        }
+        result
+    }
+}
+
+/* Also after a failed finish, or none at all, so the intermediate files never outlive the
+ * recording */
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(PBO_COUNT as GLsizei, self.pbos.as_ptr());
+        }
+        if self.ffmpeg.stdin.take().is_some() {
+            self.ffmpeg.wait().ok();
+        }
+        fs::remove_file(&self.video_path).ok();
+        #[cfg(feature = "audio")]
+        {
+            if let Some((recording, audio_path)) = self.audio.take() {
+                recording.finish().ok();
+                fs::remove_file(&audio_path).ok();
+            }
+        }
+    }
+}
+
+/* Copies the video into `output`, adding the audio track if there is one. The extension of
+ * `output` picks the container. */
+fn mux(video: &Path, audio: Option<&Path>, output: &Path) -> Result<(), ExportError> {
+    let mut command = Command::new("ffmpeg");
+    command.args(["-y", "-loglevel", "error", "-i"]).arg(video);
+    match audio {
+        Some(audio) => {
+            command.arg("-i").arg(audio).args(["-c:v", "copy", "-c:a", "aac", "-shortest"]);
+        }
+        None => {
+            command.args(["-c", "copy"]);
+        }
+    }
+    let status = command
+        .arg(output)
+        .status()
+        .map_err(|e| ExportError::Video(format!("cannot run ffmpeg: {}", e)))?;
+    if !status.success() {
+        return Err(ExportError::Video(format!("ffmpeg failed with {}", status)));
+    }
+    Ok(())
+}
+
+/* Somewhere for the intermediate files, named after the output so that recordings made at the
+ * same time don't collide */
+fn temporary_path(output: &Path, suffix: &str) -> PathBuf {
+    let stem = output.file_stem().and_then(|s| s.to_str()).unwrap_or("recording");
+    env::temp_dir().join(format!("{}-{}-{}", stem, process::id(), suffix))
+}
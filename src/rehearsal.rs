@@ -0,0 +1,37 @@
+use std::time::Instant;
+
+use deck::SlideTimings;
+
+/* Adds up the wall clock time spent on each slide during a rehearsal. Going back to a slide adds
+ * to its time, so the total is the time the talk took. */
+pub struct Rehearsal {
+    timings: SlideTimings,
+    /* Manifest index of the slide being timed, and when it was shown */
+    slide: usize,
+    since: Instant,
+}
+
+impl Rehearsal {
+    pub fn new(slide: usize, now: Instant) -> Rehearsal {
+        Rehearsal { timings: SlideTimings::new(), slide, since: now }
+    }
+
+    /* Call every frame with the manifest index of the slide on screen */
+    pub fn update(&mut self, slide: usize, now: Instant) {
+        if slide != self.slide {
+            self.record(now);
+            self.slide = slide;
+        }
+    }
+
+    pub fn finish(mut self, now: Instant) -> SlideTimings {
+        self.record(now);
+        self.timings
+    }
+
+    fn record(&mut self, now: Instant) {
+        let spent = now.duration_since(self.since);
+        *self.timings.entry(self.slide).or_default() += spent;
+        self.since = now;
+    }
+}
@@ -0,0 +1,334 @@
+extern crate tungstenite;
+
+use self::tungstenite::{Error as WsError, Message, WebSocket};
+use serde_json;
+use std::io::{self, ErrorKind};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use input::NavigationAction;
+use slide::SlideManager;
+
+/* How long a client thread waits for a message before checking for slide changes to send */
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/* How long a follower waits before trying its leader again after losing the connection */
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+/* A follower pings its leader this often, and gives up on the connection when nothing, not even
+ * a pong, has come back for LEADER_TIMEOUT, as a dropped network doesn't close the socket */
+const PING_INTERVAL: Duration = Duration::from_secs(2);
+const LEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/* Messages from clients, e.g. {"action": "next"}, {"action": "goto", "slide": 3} or
+ * {"action": "goto", "slide": "architecture_overview"} */
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum Command {
+    Next,
+    Prev,
+    Goto { slide: SlideTarget },
+    Reload,
+    ToggleTimer,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SlideTarget {
+    /* Counting from 1, like the rest of the UI */
+    Number(usize),
+    /* A slide's name in the deck */
+    Name(String),
+}
+
+/* What a client thread passes on to the render loop. Names are looked up there, as only the
+ * render loop has the slides. */
+enum Request {
+    Action(NavigationAction),
+    GotoNamed(String),
+}
+
+/* Sent to every client when the slide changes, and to new clients when they connect */
+#[derive(Debug, Serialize)]
+struct SlideEvent<'a> {
+    event: &'static str,
+    slide: usize,
+    count: usize,
+    title: &'a str,
+}
+
+/* The part of a SlideEvent a follower needs */
+#[derive(Debug, Deserialize)]
+struct LeaderEvent {
+    event: String,
+    slide: usize,
+}
+
+#[derive(Default)]
+struct Clients {
+    senders: Vec<Sender<String>>,
+    /* The last slide event, so new clients start in sync */
+    last: Option<String>,
+}
+
+/* A WebSocket server that lets a phone browser or another machine drive the presentation. Each
+ * client gets its own thread; commands reach the render loop through a channel, like the
+ * hotloader's events, and slide changes go back out to every client. */
+pub struct RemoteControl {
+    requests: Receiver<Request>,
+    clients: Arc<Mutex<Clients>>,
+    shown: Option<usize>,
+}
+
+impl RemoteControl {
+    pub fn start(port: u16) -> io::Result<RemoteControl> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        info!("Remote control listening on {}", listener.local_addr()?);
+
+        let (requests_tx, requests) = channel();
+        let clients = Arc::new(Mutex::new(Clients::default()));
+        let accept_clients = clients.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => accept(stream, requests_tx.clone(), &accept_clients),
+                    Err(e) => println!("Warning: cannot accept remote control connection: {}", e),
+                }
+            }
+        });
+
+        Ok(RemoteControl {
+            requests,
+            clients,
+            shown: None,
+        })
+    }
+
+    /* The commands received since the last call, with slide names looked up in `slides` */
+    pub fn poll(&self, slides: &SlideManager) -> Vec<NavigationAction> {
+        self.requests
+            .try_iter()
+            .filter_map(|request| match request {
+                Request::Action(action) => Some(action),
+                Request::GotoNamed(name) => {
+                    let index = slides.index_named(&name);
+                    if index.is_none() {
+                        println!("Warning: remote control asked for unknown slide {}", name);
+                    }
+                    index.map(NavigationAction::Goto)
+                }
+            })
+            .collect()
+    }
+
+    /* Tells the clients about the current slide if it has changed since the last call */
+    pub fn sync(&mut self, slides: &SlideManager) {
+        let index = slides.current_index();
+        if self.shown == Some(index) {
+            return;
+        }
+        self.shown = Some(index);
+
+        let event = SlideEvent {
+            event: "slide",
+            slide: index + 1,
+            count: slides.len(),
+            title: &slides.current().title,
+        };
+        let message = serde_json::to_string(&event).expect("Cannot serialise slide event");
+
+        let mut clients = self.clients.lock().unwrap();
+        /* Clients that have gone away have dropped their receiver */
+        clients.senders.retain(|sender| sender.send(message.clone()).is_ok());
+        clients.last = Some(message);
+    }
+}
+
+/* Mirrors another instance started with --leader, which is just a remote control server whose
+ * clients are other slideshows. Every slide change the leader sends becomes a Goto here. The
+ * connection is retried until the render loop finishes, and as the leader sends its current slide
+ * to each new client, a follower that drops out catches up as soon as it is back. */
+pub struct Follower {
+    actions: Receiver<NavigationAction>,
+}
+
+impl Follower {
+    /* `leader` is the host and port the leader listens on, e.g. 192.168.1.10:9000 */
+    pub fn start(leader: &str) -> Follower {
+        let leader = leader.to_string();
+        let (actions_tx, actions) = channel();
+        thread::spawn(move || follow(&leader, &actions_tx));
+        Follower { actions }
+    }
+
+    /* The slides the leader moved to since the last call */
+    pub fn poll(&self) -> Vec<NavigationAction> {
+        self.actions.try_iter().collect()
+    }
+}
+
+fn follow(leader: &str, actions: &Sender<NavigationAction>) {
+    /* The leader's slide as of the last message, zero-based, kept across reconnections */
+    let mut last_slide = None;
+    let mut warned = false;
+    loop {
+        let result = TcpStream::connect(leader)
+            .map_err(WsError::Io)
+            .and_then(|stream| {
+                tungstenite::client(format!("ws://{}/", leader), stream).map_err(|e| match e {
+                    tungstenite::HandshakeError::Failure(e) => e,
+                    tungstenite::HandshakeError::Interrupted(_) => {
+                        WsError::Io(io::Error::new(ErrorKind::WouldBlock, "handshake interrupted"))
+                    }
+                })
+            });
+        match result {
+            Ok((socket, _)) => {
+                info!("Following {}", leader);
+                warned = false;
+                match mirror(socket, actions, &mut last_slide) {
+                    /* Nobody is left to send the slides to */
+                    Ok(false) => return,
+                    Ok(true) | Err(WsError::ConnectionClosed) | Err(WsError::AlreadyClosed) => {
+                        println!("Warning: lost the connection to {}, reconnecting", leader)
+                    }
+                    Err(e) => println!("Warning: lost the connection to {}: {}, reconnecting", leader, e),
+                }
+            }
+            /* Only warn once per outage rather than every second */
+            Err(e) => {
+                if !warned {
+                    println!("Warning: cannot connect to {}: {}, retrying", leader, e);
+                    warned = true;
+                }
+            }
+        }
+        thread::sleep(RECONNECT_INTERVAL);
+    }
+}
+
+/* Passes the leader's slide changes on until the connection closes, returning false once the
+ * render loop has gone. The first message after connecting is always passed on, to re-sync any
+ * navigation made here while disconnected. */
+fn mirror(
+    mut socket: WebSocket<TcpStream>,
+    actions: &Sender<NavigationAction>,
+    last_slide: &mut Option<usize>,
+) -> Result<bool, WsError> {
+    socket.get_ref().set_read_timeout(Some(PING_INTERVAL))?;
+    let mut synced = false;
+    let mut last_heard = Instant::now();
+    loop {
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => return Ok(true),
+            Ok(_) => {
+                last_heard = Instant::now();
+                continue;
+            }
+            Err(WsError::Io(ref e))
+                if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                if last_heard.elapsed() > LEADER_TIMEOUT {
+                    return Ok(true);
+                }
+                socket.send(Message::Ping(Vec::new()))?;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        last_heard = Instant::now();
+        let slide = match serde_json::from_str::<LeaderEvent>(&text) {
+            Ok(ref event) if event.event == "slide" && event.slide > 0 => event.slide - 1,
+            Ok(_) => continue,
+            Err(e) => {
+                println!("Warning: ignoring message from leader {:?}: {}", text, e);
+                continue;
+            }
+        };
+        if synced && *last_slide == Some(slide) {
+            continue;
+        }
+        synced = true;
+        *last_slide = Some(slide);
+        if actions.send(NavigationAction::Goto(slide)).is_err() {
+            return Ok(false);
+        }
+    }
+}
+
+fn accept(stream: TcpStream, requests: Sender<Request>, clients: &Arc<Mutex<Clients>>) {
+    let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+    let (events_tx, events) = channel();
+    {
+        let mut clients = clients.lock().unwrap();
+        if let Some(ref last) = clients.last {
+            events_tx.send(last.clone()).ok();
+        }
+        clients.senders.push(events_tx);
+    }
+
+    thread::spawn(move || {
+        /* The handshake blocks, so do it on the client's thread rather than the listener's */
+        let socket = match tungstenite::accept(stream) {
+            Ok(socket) => socket,
+            Err(e) => {
+                println!("Warning: remote control handshake with {} failed: {}", peer, e);
+                return;
+            }
+        };
+        info!("Remote control client {} connected", peer);
+        match serve(socket, &requests, &events) {
+            Ok(()) | Err(WsError::ConnectionClosed) | Err(WsError::AlreadyClosed) => {}
+            Err(e) => println!("Warning: remote control client {}: {}", peer, e),
+        }
+        info!("Remote control client {} disconnected", peer);
+    });
+}
+
+fn serve(
+    mut socket: WebSocket<TcpStream>,
+    requests: &Sender<Request>,
+    events: &Receiver<String>,
+) -> Result<(), WsError> {
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+    loop {
+        for event in events.try_iter() {
+            socket.send(Message::Text(event))?;
+        }
+
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => continue,
+            Err(WsError::Io(ref e))
+                if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                continue
+            }
+            Err(e) => return Err(e),
+        };
+
+        let request = match serde_json::from_str(&text) {
+            Ok(Command::Next) => Request::Action(NavigationAction::Next),
+            Ok(Command::Prev) => Request::Action(NavigationAction::Prev),
+            Ok(Command::Goto { slide: SlideTarget::Number(slide) }) if slide > 0 => {
+                Request::Action(NavigationAction::Goto(slide - 1))
+            }
+            Ok(Command::Goto { slide: SlideTarget::Number(_) }) => {
+                println!("Warning: remote control slides count from 1");
+                continue;
+            }
+            Ok(Command::Goto { slide: SlideTarget::Name(name) }) => Request::GotoNamed(name),
+            Ok(Command::Reload) => Request::Action(NavigationAction::Reload),
+            Ok(Command::ToggleTimer) => Request::Action(NavigationAction::ToggleTimer),
+            Err(e) => {
+                println!("Warning: ignoring remote control message {:?}: {}", text, e);
+                continue;
+            }
+        };
+        /* The render loop has finished, so there is nobody left to control */
+        if requests.send(request).is_err() {
+            return Ok(());
+        }
+    }
+}
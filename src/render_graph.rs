@@ -0,0 +1,211 @@
+extern crate gl;
+use std::collections::HashMap;
+
+use channels::{self, ChannelBinding, CHANNEL_COUNT};
+use errors::RenderGraphError;
+use framebuffer::Framebuffer;
+use quad::FullscreenQuad;
+use shader::ShaderProgram;
+
+/* One full screen draw of `program`. A pass with no framebuffer draws into whatever is bound when
+ * the graph runs, normally the window. */
+pub struct RenderPass {
+    pub name: String,
+    pub framebuffer: Option<Framebuffer>,
+    pub program: ShaderProgram,
+    pub channels: ChannelBinding,
+    /* (channel, pass) pairs: the colour texture of that pass is bound as iChannelN */
+    inputs: Vec<(usize, String)>,
+}
+
+impl RenderPass {
+    pub fn new(name: &str, framebuffer: Option<Framebuffer>, program: ShaderProgram) -> RenderPass {
+        RenderPass {
+            name: name.to_string(),
+            framebuffer,
+            program,
+            channels: ChannelBinding::new(),
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn with_channels(mut self, channels: ChannelBinding) -> RenderPass {
+        self.channels = channels;
+        self
+    }
+
+    /* Samples the output of the pass called `pass` as iChannel`channel`, in place of anything
+     * the channel binding has there */
+    pub fn reading(mut self, channel: usize, pass: &str) -> RenderPass {
+        assert!(channel < CHANNEL_COUNT, "there are only {} channels", CHANNEL_COUNT);
+        self.inputs.push((channel, pass.to_string()));
+        self
+    }
+}
+
+/* Passes run so that each one comes after the passes it reads from, and otherwise in the order
+ * they were given, e.g. shadow map, scene, post-process, UI */
+pub struct RenderGraph {
+    passes: Vec<RenderPass>,
+    /* Indices into `passes` in the order they run */
+    order: Vec<usize>,
+    /* For each pass, (channel, index of the pass it reads) */
+    inputs: Vec<Vec<(usize, usize)>>,
+    quad: FullscreenQuad,
+}
+
+impl RenderGraph {
+    pub fn new(passes: Vec<RenderPass>) -> Result<RenderGraph, RenderGraphError> {
+        let mut indices = HashMap::new();
+        for (i, pass) in passes.iter().enumerate() {
+            if indices.insert(pass.name.as_str(), i).is_some() {
+                return Err(RenderGraphError::DuplicatePass(pass.name.clone()));
+            }
+        }
+
+        let mut inputs = Vec::with_capacity(passes.len());
+        for pass in &passes {
+            let mut resolved = Vec::with_capacity(pass.inputs.len());
+            for &(channel, ref input) in &pass.inputs {
+                let index = match indices.get(input.as_str()) {
+                    Some(&index) => index,
+                    None => {
+                        return Err(RenderGraphError::UnknownInput {
+                            pass: pass.name.clone(),
+                            input: input.clone(),
+                        })
+                    }
+                };
+                if passes[index].framebuffer.is_none() {
+                    return Err(RenderGraphError::NoOutput {
+                        pass: pass.name.clone(),
+                        input: input.clone(),
+                    });
+                }
+                resolved.push((channel, index));
+            }
+            inputs.push(resolved);
+        }
+
+        let order = execution_order(&inputs).map_err(|cycle| {
+            RenderGraphError::Cycle(cycle.into_iter().map(|i| passes[i].name.clone()).collect())
+        })?;
+
+        Ok(RenderGraph { passes, order, inputs, quad: FullscreenQuad::new() })
+    }
+
+    pub fn passes(&self) -> &[RenderPass] {
+        &self.passes
+    }
+
+    pub fn pass(&self, name: &str) -> Option<&RenderPass> {
+        self.passes.iter().find(|pass| pass.name == name)
+    }
+
+    /* Draws every pass once. Offscreen framebuffers are cleared first; the window is left for the
+     * caller to clear. */
+    pub fn execute(&self) {
+        for &index in &self.order {
+            let pass = &self.passes[index];
+            if let Some(ref framebuffer) = pass.framebuffer {
+                framebuffer.bind();
+                unsafe {
+                    gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+                    gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+                }
+            }
+
+            pass.channels.bind_all(&pass.program);
+            for &(channel, input) in &self.inputs[index] {
+                let output = self.passes[input].framebuffer.as_ref().expect("inputs have framebuffers");
+                channels::bind_channel(&pass.program, channel, output.color_texture());
+            }
+            pass.program.activate(|_| self.quad.draw());
+
+            if let Some(ref framebuffer) = pass.framebuffer {
+                framebuffer.unbind();
+            }
+        }
+    }
+}
+
+/* Depth first, visiting passes in declaration order, so a pass runs as early as its inputs allow.
+ * A cycle is returned as the passes along it, with the first repeated at the end. */
+fn execution_order(inputs: &[Vec<(usize, usize)>]) -> Result<Vec<usize>, Vec<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Unvisited,
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        pass: usize,
+        inputs: &[Vec<(usize, usize)>],
+        states: &mut [State],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), Vec<usize>> {
+        match states[pass] {
+            State::Done => return Ok(()),
+            State::Visiting => {
+                let start = path.iter().position(|&p| p == pass).expect("pass is on the path");
+                let mut cycle = path[start..].to_vec();
+                cycle.push(pass);
+                return Err(cycle);
+            }
+            State::Unvisited => {}
+        }
+
+        states[pass] = State::Visiting;
+        path.push(pass);
+        for &(_, input) in &inputs[pass] {
+            visit(input, inputs, states, path, order)?;
+        }
+        path.pop();
+        states[pass] = State::Done;
+        order.push(pass);
+        Ok(())
+    }
+
+    let mut states = vec![State::Unvisited; inputs.len()];
+    let mut order = Vec::with_capacity(inputs.len());
+    for pass in 0..inputs.len() {
+        visit(pass, inputs, &mut states, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::execution_order;
+
+    #[test]
+    fn independent_passes_keep_their_order() {
+        assert_eq!(execution_order(&[vec![], vec![], vec![]]), Ok(vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn inputs_run_before_their_readers() {
+        /* post reads scene, which reads shadow; they are given in the opposite order */
+        let inputs = vec![vec![(0, 1)], vec![(0, 2)], vec![]];
+        assert_eq!(execution_order(&inputs), Ok(vec![2, 1, 0]));
+    }
+
+    #[test]
+    fn shared_inputs_run_once() {
+        let inputs = vec![vec![], vec![(0, 0)], vec![(0, 0), (1, 1)]];
+        assert_eq!(execution_order(&inputs), Ok(vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn cycles_are_reported_along_their_path() {
+        let inputs = vec![vec![(0, 1)], vec![(0, 2)], vec![(0, 0)]];
+        assert_eq!(execution_order(&inputs), Err(vec![0, 1, 2, 0]));
+    }
+
+    #[test]
+    fn a_pass_reading_itself_is_a_cycle() {
+        assert_eq!(execution_order(&[vec![(0, 0)]]), Err(vec![0, 0]));
+    }
+}
@@ -0,0 +1,261 @@
+extern crate gif;
+extern crate gl;
+use gl::types::*;
+use image;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use backend::{GlBackend, RenderBackend};
+use errors::ExportError;
+use framebuffer::Framebuffer;
+use lut::LutPass;
+use shadertoy::ShaderToyUniforms;
+use slide::SlideManager;
+
+/* Frames drawn before timing starts, so shader compilation in the driver isn't counted */
+const WARMUP_FRAMES: u32 = 10;
+/* iTime moves on by this much every frame, whatever the real frame time */
+const BENCH_FRAME_SECS: f32 = 1.0 / 60.0;
+/* NeuQuant sampling for GIF palettes, from 1 (best) to 30 (fastest) */
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+/* Frame times for one slide, serialised as one entry of the --bench output */
+#[derive(Debug, Serialize)]
+pub struct FrameStats {
+    /* Counting from 1, like the rest of the UI */
+    pub slide: usize,
+    pub title: String,
+    pub frames: u32,
+    pub mean_ms: f64,
+    pub p99_ms: f64,
+}
+
+/* Draws slides offscreen through GlBackend, so it needs a current context */
+pub struct Renderer<'a> {
+    slides: &'a SlideManager,
+    backend: RefCell<GlBackend>,
+    width: u32,
+    height: u32,
+    samples: u32,
+    lut: Option<&'a LutPass>,
+}
+
+/* Where render_frame draws: the slide first, and then the graded slide when there is a LUT */
+struct FrameTargets {
+    slide: Framebuffer,
+    graded: Option<Framebuffer>,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(slides: &'a SlideManager, width: u32, height: u32) -> Renderer<'a> {
+        Renderer {
+            slides,
+            backend: RefCell::new(GlBackend::new()),
+            width,
+            height,
+            samples: 1,
+            lut: None,
+        }
+    }
+
+    /* Samples per pixel for the offscreen framebuffers */
+    pub fn with_samples(mut self, samples: u32) -> Renderer<'a> {
+        self.samples = samples;
+        self
+    }
+
+    /* Grades every exported frame through `lut`, as --lut does for the window. Benchmarks leave
+     * it out, as they time the slides. */
+    pub fn with_lut(mut self, lut: Option<&'a LutPass>) -> Renderer<'a> {
+        self.lut = lut;
+        self
+    }
+
+    fn frame_targets(&self) -> Result<FrameTargets, ExportError> {
+        let slide = Framebuffer::with_samples(self.width, self.height, self.samples)?;
+        let graded = match self.lut {
+            Some(_) => Some(Framebuffer::new(self.width, self.height)?),
+            None => None,
+        };
+        Ok(FrameTargets { slide, graded })
+    }
+
+    /* Renders into an offscreen framebuffer, so this does not need the window to be visible */
+    pub fn export_slide(&self, index: usize, output_path: &Path) -> Result<(), ExportError> {
+        let pixels = self.render_slide(index)?;
+        image::save_buffer(output_path, &pixels, self.width, self.height, image::RGBA(8))?;
+        Ok(())
+    }
+
+    /* The slide as RGBA rows from the top, the order image files use */
+    pub fn render_slide(&self, index: usize) -> Result<Vec<u8>, ExportError> {
+        if index >= self.slides.len() {
+            return Err(ExportError::NoSuchSlide(index));
+        }
+
+        let targets = self.frame_targets()?;
+        let shadertoy = ShaderToyUniforms::new(self.width, self.height);
+        self.render_frame(index, &targets, &shadertoy)
+    }
+
+    /* Renders `duration` of the slide at `fps` and saves it as a looping GIF. iTime steps by
+     * exactly one frame each time, so the result does not depend on how fast this machine is. */
+    pub fn export_gif(&self, index: usize, duration: Duration, fps: u32, output: &Path)
+        -> Result<(), ExportError> {
+        if index >= self.slides.len() {
+            return Err(ExportError::NoSuchSlide(index));
+        }
+        if self.width > u32::from(u16::MAX) || self.height > u32::from(u16::MAX) {
+            return Err(ExportError::Gif("GIFs are at most 65535 pixels across".to_string()));
+        }
+
+        let (width, height) = (self.width as u16, self.height as u16);
+        let file = BufWriter::new(File::create(output)?);
+        let gif_error = |e: gif::EncodingError| ExportError::Gif(e.to_string());
+        let mut encoder = gif::Encoder::new(file, width, height, &[]).map_err(gif_error)?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(gif_error)?;
+
+        let targets = self.frame_targets()?;
+        let mut shadertoy = ShaderToyUniforms::new(self.width, self.height);
+        let frames = frame_count(duration, fps);
+        /* GIF frame delays are in hundredths of a second */
+        let delay = (100.0 / fps as f32).round().max(1.0) as u16;
+        for frame in 0..frames {
+            shadertoy.advance(frame as f32 / fps as f32);
+            let mut pixels = self.render_frame(index, &targets, &shadertoy)?;
+            let mut gif_frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, GIF_QUANTIZE_SPEED);
+            gif_frame.delay = delay;
+            encoder.write_frame(&gif_frame).map_err(gif_error)?;
+        }
+        Ok(())
+    }
+
+    /* Renders `duration` of the slide at `fps` like export_gif, piping the raw frames to an
+     * ffmpeg process. The extension of `output` picks the codec: VP9 for .webm, H.264 for
+     * anything else. */
+    pub fn export_video(&self, index: usize, duration: Duration, fps: u32, output: &Path)
+        -> Result<(), ExportError> {
+        if index >= self.slides.len() {
+            return Err(ExportError::NoSuchSlide(index));
+        }
+
+        let codec = match output.extension().and_then(|e| e.to_str()) {
+            Some("webm") => "libvpx-vp9",
+            _ => "libx264",
+        };
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+            .arg("-s").arg(format!("{}x{}", self.width, self.height))
+            .arg("-r").arg(fps.to_string())
+            .args(["-i", "-", "-c:v", codec, "-pix_fmt", "yuv420p"])
+            .arg(output)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| ExportError::Video(format!("cannot run ffmpeg: {}", e)))?;
+
+        let targets = self.frame_targets()?;
+        let mut shadertoy = ShaderToyUniforms::new(self.width, self.height);
+        let frames = frame_count(duration, fps);
+        {
+            let stdin = ffmpeg.stdin.as_mut().expect("ffmpeg stdin is piped");
+            for frame in 0..frames {
+                shadertoy.advance(frame as f32 / fps as f32);
+                let pixels = self.render_frame(index, &targets, &shadertoy)?;
+                /* A write error means ffmpeg has exited, and wait reports why */
+                if stdin.write_all(&pixels).is_err() {
+                    break;
+                }
+            }
+        }
+        /* Closing stdin tells ffmpeg there are no more frames */
+        drop(ffmpeg.stdin.take());
+        let status = ffmpeg.wait()?;
+        if !status.success() {
+            return Err(ExportError::Video(format!("ffmpeg failed with {}", status)));
+        }
+        Ok(())
+    }
+
+    /* `shadertoy` should be at the export size, and at the time of the frame */
+    fn render_frame(&self, index: usize, targets: &FrameTargets, shadertoy: &ShaderToyUniforms)
+        -> Result<Vec<u8>, ExportError> {
+        let row_len = self.width as usize * 4;
+        shadertoy.upload_to(&self.slides.slides()[index].program);
+
+        let mut backend = self.backend.borrow_mut();
+        let target = &targets.slide;
+        target.bind();
+        backend.begin_frame(self.width, self.height)?;
+        self.slides.draw(index, &mut backend)?;
+        backend.end_frame()?;
+        target.unbind();
+        let pixels = match (self.lut, targets.graded.as_ref()) {
+            (Some(lut), Some(graded)) => {
+                graded.bind();
+                lut.draw(target.color_texture());
+                graded.unbind();
+                graded.read_pixels()
+            }
+            _ => target.read_pixels(),
+        };
+
+        /* GL puts the origin at the bottom left, image files at the top left */
+        Ok(pixels
+            .chunks(row_len)
+            .rev()
+            .flat_map(|row| row.iter().cloned())
+            .collect())
+    }
+
+    /* Draws the slide `frames` times into an offscreen framebuffer and times each frame up to
+     * glFinish, so the times include the GPU work rather than just submitting it */
+    pub fn bench_slide(&self, index: usize, frames: u32) -> Result<FrameStats, ExportError> {
+        if index >= self.slides.len() {
+            return Err(ExportError::NoSuchSlide(index));
+        }
+
+        let slide = &self.slides.slides()[index];
+        let target = Framebuffer::with_samples(self.width, self.height, self.samples)?;
+        let mut shadertoy = ShaderToyUniforms::new(self.width, self.height);
+        let mut times = Vec::with_capacity(frames as usize);
+
+        let mut backend = self.backend.borrow_mut();
+        target.bind();
+        for frame in 0..WARMUP_FRAMES + frames {
+            let start = Instant::now();
+            shadertoy.advance(frame as f32 * BENCH_FRAME_SECS);
+            shadertoy.upload_to(&slide.program);
+            backend.begin_frame(self.width, self.height)?;
+            self.slides.draw(index, &mut backend)?;
+            backend.end_frame()?;
+            unsafe {
+                gl::Finish();
+            }
+            if frame >= WARMUP_FRAMES {
+                times.push(start.elapsed());
+            }
+        }
+        target.unbind();
+
+        times.sort();
+        let millis = |d: Duration| d.as_secs() as f64 * 1000.0 + f64::from(d.subsec_nanos()) / 1e6;
+        let total: f64 = times.iter().cloned().map(millis).sum();
+        let p99 = ((times.len() as f64 * 0.99).ceil() as usize).max(1) - 1;
+        Ok(FrameStats {
+            slide: index + 1,
+            title: slide.title.clone(),
+            frames,
+            mean_ms: total / f64::from(frames),
+            p99_ms: millis(times[p99]),
+        })
+    }
+}
+
+fn frame_count(duration: Duration, fps: u32) -> u32 {
+    let secs = duration.as_secs() as f32 + duration.subsec_nanos() as f32 / 1e9;
+    (secs * fps as f32).round().max(1.0) as u32
+}
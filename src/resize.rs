@@ -0,0 +1,70 @@
+extern crate gl;
+use glfw::WindowEvent;
+
+use aspect::Aspect;
+use framebuffer::Framebuffer;
+use shader::ShaderProgram;
+use shadertoy::ShaderToyUniforms;
+
+/* Collects framebuffer size events and applies the latest one once per frame, keeping the
+ * viewport, offscreen render targets and iResolution in step with the window. Render targets and
+ * iResolution follow the slide's area of the window, as placed by `aspect`. */
+pub struct ResizeHandler {
+    pending: Option<(i32, i32)>,
+    aspect: Aspect,
+    slide_rect: [i32; 4],
+}
+
+impl ResizeHandler {
+    /* Starts with a pending resize to the window's current size, so the first `apply` sets
+     * everything up */
+    pub fn new(aspect: Aspect, width: i32, height: i32) -> ResizeHandler {
+        ResizeHandler {
+            pending: Some((width, height)),
+            aspect,
+            slide_rect: [0, 0, width, height],
+        }
+    }
+
+    /* The slide's area of the window, as x, y, width and height */
+    pub fn slide_rect(&self) -> [i32; 4] {
+        self.slide_rect
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::FramebufferSize(width, height) = *event {
+            self.pending = Some((width, height));
+        }
+    }
+
+    pub fn apply<'a, I>(
+        &mut self,
+        framebuffers: I,
+        uniforms: &mut ShaderToyUniforms,
+        program: &ShaderProgram,
+    ) where I: IntoIterator<Item = &'a mut Framebuffer> {
+        let (width, height) = match self.pending.take() {
+            Some(size) => size,
+            None => return,
+        };
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+        }
+
+        /* Minimising the window reports a zero size, which no framebuffer can have */
+        if width <= 0 || height <= 0 {
+            return;
+        }
+
+        self.slide_rect = self.aspect.rect(width, height);
+        let (width, height) = (self.slide_rect[2], self.slide_rect[3]);
+        for framebuffer in framebuffers {
+            if let Err(e) = framebuffer.resize(width as u32, height as u32) {
+                println!("Warning: cannot resize framebuffer: {}", e);
+            }
+        }
+
+        uniforms.resolution = [width as f32, height as f32];
+        uniforms.upload_to(program);
+    }
+}
@@ -0,0 +1,296 @@
+use serde_json;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use cli::Options;
+use deck::Deck;
+use hotloader::{self, ReloadEvent};
+use lut::LutPass;
+use renderer::Renderer;
+use slide::SlideManager;
+
+/* Editors often write a file in several steps, so wait for them to finish before re-exporting */
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/* What the HTTP thread serves, replaced by the render thread after each export */
+#[derive(Default)]
+struct Site {
+    titles: Vec<String>,
+    /* Bumped whenever a slide is re-exported, so pages know to reload */
+    versions: Vec<u64>,
+    generation: u64,
+}
+
+/* Exports every slide to a PNG and serves them as a web page, for reviewers without the
+ * binary. The deck and its shaders are watched, and only the slides whose images actually
+ * changed are written again. Needs a current context. */
+pub fn run(options: &Options, mut deck: Deck, port: u16, lut: Option<&LutPass>) -> io::Result<()> {
+    let dir = match options.export {
+        Some(ref dir) => dir.clone(),
+        None => ::std::env::temp_dir().join("slideshow-serve"),
+    };
+    fs::create_dir_all(&dir)?;
+
+    let site = Arc::new(Mutex::new(Site::default()));
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    println!("Serving {} on http://{}/", deck.path.display(), listener.local_addr()?);
+    {
+        let site = site.clone();
+        let dir = dir.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = respond(stream, &site, &dir) {
+                            println!("Warning: cannot answer HTTP request: {}", e);
+                        }
+                    }
+                    Err(e) => println!("Warning: cannot accept HTTP connection: {}", e),
+                }
+            }
+        });
+    }
+
+    let mut exported: Vec<Vec<u8>> = Vec::new();
+    loop {
+        /* A new channel each time drops the previous watchers once they next fire */
+        let (reload_tx, reload_events) = channel();
+        /* Broken shader slides are still created, so their files are watched and fixing them
+         * re-exports; export skips them until then */
+        match SlideManager::from_deck_allowing_errors(&deck, options.slide_selection(), None) {
+            Ok(slides) => {
+                for slide in slides.slides() {
+                    if let Err(e) = slide.program.watch(reload_tx.clone()) {
+                        println!("Warning: cannot watch shader files: {}", e);
+                    }
+                }
+                export(options, &slides, lut, &dir, &mut exported, &site);
+            }
+            Err(e) => println!("Warning: cannot create slides: {}", e),
+        }
+        if let Err(e) = hotloader::watch_files(&[&deck.path], reload_tx) {
+            println!("Warning: cannot watch {}: {}", deck.path.display(), e);
+        }
+
+        wait_for_change(&reload_events);
+        match Deck::from_file(&deck.path) {
+            Ok(reloaded) => {
+                deck = match options.spirv_cache {
+                    Some(ref dir) => reloaded.with_spirv_cache(dir.clone()),
+                    None => reloaded,
+                }
+            }
+            Err(e) => println!("Warning: cannot reload deck, keeping the old one: {}", e),
+        }
+    }
+}
+
+fn wait_for_change(events: &Receiver<ReloadEvent>) {
+    match events.recv() {
+        Ok(event) => info!("{} changed", event.path.display()),
+        /* Nothing could be watched, so nothing will ever change */
+        Err(_) => loop {
+            thread::park();
+        },
+    }
+    thread::sleep(SETTLE_TIME);
+    for _ in events.try_iter() {}
+}
+
+fn export(
+    options: &Options,
+    slides: &SlideManager,
+    lut: Option<&LutPass>,
+    dir: &Path,
+    exported: &mut Vec<Vec<u8>>,
+    site: &Mutex<Site>,
+) {
+    let renderer = Renderer::new(slides, options.width, options.height)
+        .with_samples(options.msaa)
+        .with_lut(lut);
+    /* Indexed by slide, with an empty entry for any slide not exported yet */
+    exported.resize(slides.len(), Vec::new());
+
+    let mut changed = Vec::new();
+    for index in 0..slides.len() {
+        if let Some(error) = slides.slides()[index].program.build_error() {
+            println!("Warning: not exporting slide {}, its shader does not build: {}", index + 1, error);
+            continue;
+        }
+        let pixels = match renderer.render_slide(index) {
+            Ok(pixels) => pixels,
+            Err(e) => {
+                println!("Warning: cannot export slide {}: {}", index + 1, e);
+                continue;
+            }
+        };
+        if exported[index] == pixels {
+            continue;
+        }
+
+        let path = slide_path(dir, index);
+        if let Err(e) = ::image::save_buffer(
+            &path,
+            &pixels,
+            options.width,
+            options.height,
+            ::image::RGBA(8),
+        ) {
+            println!("Warning: cannot write {}: {}", path.display(), e);
+            continue;
+        }
+        println!("Exported {}", path.display());
+        exported[index] = pixels;
+        changed.push(index);
+    }
+
+    let titles: Vec<String> = slides.slides().iter().map(|s| s.title.clone()).collect();
+    let mut site = site.lock().unwrap();
+    if changed.is_empty() && site.titles == titles {
+        return;
+    }
+    site.generation += 1;
+    site.titles = titles;
+    site.versions.resize(slides.len(), 0);
+    for index in changed {
+        site.versions[index] = site.generation;
+    }
+}
+
+fn slide_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("slide-{:02}.png", index + 1))
+}
+
+fn respond(stream: TcpStream, site: &Mutex<Site>, dir: &Path) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    /* The headers are not needed, but must be read before answering */
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let mut stream = reader.into_inner();
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return send(&mut stream, "400 Bad Request", "text/plain", b"Bad request"),
+    };
+    if method != "GET" {
+        return send(&mut stream, "405 Method Not Allowed", "text/plain", b"Only GET is supported");
+    }
+    let path = target.split('?').next().unwrap_or("");
+
+    if path == "/" || path == "/index.html" {
+        let page = index_page(&site.lock().unwrap());
+        return send(&mut stream, "200 OK", "text/html; charset=utf-8", page.as_bytes());
+    }
+    if path == "/generation" {
+        let generation = site.lock().unwrap().generation.to_string();
+        return send(&mut stream, "200 OK", "text/plain", generation.as_bytes());
+    }
+
+    let count = site.lock().unwrap().titles.len();
+    let image = (0..count).find(|&i| path == format!("/slide-{:02}.png", i + 1));
+    match image.map(|index| File::open(slide_path(dir, index))) {
+        Some(Ok(mut file)) => {
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            send(&mut stream, "200 OK", "image/png", &data)
+        }
+        _ => send(&mut stream, "404 Not Found", "text/plain", b"Not found"),
+    }
+}
+
+fn send(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\n\
+         Connection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)
+}
+
+/* One slide at a time, moved through with the arrow keys or a click; the page polls
+ * /generation and reloads itself after a re-export */
+const INDEX_PAGE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Slides</title>
+<style>
+  body { margin: 0; background: #000; color: #eee; font-family: sans-serif; }
+  img { display: block; width: 100vw; height: calc(100vh - 2em); object-fit: contain; cursor: pointer; }
+  p { margin: 0; height: 2em; line-height: 2em; text-align: center; }
+</style>
+</head>
+<body>
+<img id="slide" alt="">
+<p id="caption"></p>
+<script>
+  var slides = $SLIDES;
+  var generation = $GENERATION;
+  var current = Math.min(Math.max(parseInt(location.hash.slice(1), 10) - 1 || 0, 0), slides.length - 1);
+
+  function show(index) {
+    if (index < 0 || index >= slides.length) return;
+    current = index;
+    document.getElementById("slide").src = slides[current].src;
+    document.getElementById("caption").textContent =
+      (current + 1) + "/" + slides.length + "  " + slides[current].title;
+    history.replaceState(null, "", "#" + (current + 1));
+  }
+
+  document.addEventListener("keydown", function (e) {
+    if (e.key === "ArrowRight" || e.key === "ArrowDown" || e.key === " " || e.key === "PageDown") show(current + 1);
+    if (e.key === "ArrowLeft" || e.key === "ArrowUp" || e.key === "Backspace" || e.key === "PageUp") show(current - 1);
+  });
+  document.getElementById("slide").addEventListener("click", function () { show(current + 1); });
+
+  setInterval(function () {
+    fetch("/generation").then(function (r) { return r.text(); }).then(function (text) {
+      if (parseInt(text, 10) !== generation) location.reload();
+    }).catch(function () {});
+  }, 1000);
+
+  if (slides.length > 0) show(current);
+</script>
+</body>
+</html>
+"##;
+
+#[derive(Serialize)]
+struct PageSlide<'a> {
+    title: &'a str,
+    src: String,
+}
+
+fn index_page(site: &Site) -> String {
+    let slides: Vec<_> = site
+        .titles
+        .iter()
+        .zip(&site.versions)
+        .enumerate()
+        .map(|(index, (title, version))| PageSlide {
+            title,
+            src: format!("/slide-{:02}.png?v={}", index + 1, version),
+        })
+        .collect();
+    /* JSON is valid JavaScript, but a title must not be able to close the script element */
+    let slides = serde_json::to_string(&slides)
+        .expect("Cannot serialise slide list")
+        .replace("</", "<\\/");
+    INDEX_PAGE
+        .replace("$SLIDES", &slides)
+        .replace("$GENERATION", &site.generation.to_string())
+}
@@ -1,21 +1,217 @@
 extern crate gl;
+use cgmath::{Matrix, Matrix4};
 use std::error::Error;
 use gl::types::*;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
+use std::convert::TryInto;
+use std::os::raw::c_void;
 use std::ptr;
 use std::str;
 use std::fs::File;
 use std::io::Read;
-use std::time::Duration;
-use std::cell::Cell;
+use std::time::{Duration, Instant};
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::fmt;
+use std::sync::mpsc::Sender;
+use std::path::{Path, PathBuf};
+use std::io;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use errors::Result;
+use errors::ShaderError;
+use hotloader::{self, ReloadEvent};
+use preprocess::{self, SourceMap};
+use spirv;
+use transform_feedback::TransformFeedbackMode;
 
+type Result<T> = ::std::result::Result<T, ShaderError>;
+
+/* Set for an OpenGL ES context, see use_glsl_es */
+static GLSL_ES: AtomicBool = AtomicBool::new(false);
+
+/* Starts every file written by ShaderProgram::save_binary */
+const PROGRAM_BINARY_MAGIC: &[u8] = b"SLIDEPRG";
+
+/* Has every program built from now on rewritten as GLSL ES 3.00, for an OpenGL ES 3.0 context.
+ * Shaders are written for desktop GL 3.3, which is close enough for most slides. */
+pub fn use_glsl_es(es: bool) {
+    GLSL_ES.store(es, Ordering::SeqCst);
+}
+
+/* Builds a ShaderSource from a GLSL file compiled into the binary, with the path relative to the
+ * file using the macro, e.g. `include_shader!("../shaders/basic.frag")` */
+#[macro_export]
+macro_rules! include_shader {
+    ($path:expr) => {
+        $crate::shader::ShaderSource::Embedded(include_bytes!($path))
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Geometry,
+    TessControl,
+    TessEvaluation,
+    Compute,
+}
+
+impl ShaderStage {
+    fn gl_type(&self) -> GLenum {
+        match *self {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+            ShaderStage::Geometry => gl::GEOMETRY_SHADER,
+            ShaderStage::TessControl => gl::TESS_CONTROL_SHADER,
+            ShaderStage::TessEvaluation => gl::TESS_EVALUATION_SHADER,
+            ShaderStage::Compute => gl::COMPUTE_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            ShaderStage::Vertex => "vertex",
+            ShaderStage::Fragment => "fragment",
+            ShaderStage::Geometry => "geometry",
+            ShaderStage::TessControl => "tess_control",
+            ShaderStage::TessEvaluation => "tess_evaluation",
+            ShaderStage::Compute => "compute",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ShaderSource {
+    File(PathBuf),
+    Inline(String),
+    /* Usually from include_shader!, so the binary doesn't need the file at runtime */
+    Embedded(&'static [u8]),
+}
+
+impl fmt::Display for ShaderSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShaderSource::File(ref path) => write!(f, "{}", path.display()),
+            ShaderSource::Inline(_) => write!(f, "<inline>"),
+            ShaderSource::Embedded(_) => write!(f, "<embedded>"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UniformValue {
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Bool(bool),
+}
+
+/* An active uniform as reported by the driver. Arrays are reported once, as `name[0]`, with
+ * `size` elements. */
+#[derive(Debug, Clone, PartialEq)]
+pub struct UniformInfo {
+    pub name: String,
+    pub location: GLint,
+    pub kind: GLenum,
+    pub size: GLint,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeInfo {
+    pub name: String,
+    pub location: GLint,
+    pub kind: GLenum,
+    pub size: GLint,
+}
+
+impl fmt::Display for UniformInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_variable(f, &self.name, self.location, self.kind, self.size)
+    }
+}
+
+impl fmt::Display for AttributeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_variable(f, &self.name, self.location, self.kind, self.size)
+    }
+}
+
+fn write_variable(
+    f: &mut fmt::Formatter,
+    name: &str,
+    location: GLint,
+    kind: GLenum,
+    size: GLint,
+) -> fmt::Result {
+    write!(f, "{} {}", glsl_type_name(kind), name)?;
+    if size > 1 {
+        write!(f, " ({} elements)", size)?;
+    }
+    write!(f, " at location {}", location)
+}
+
+/* The GLSL name for a type returned by glGetActiveUniform or glGetActiveAttrib */
+pub fn glsl_type_name(kind: GLenum) -> &'static str {
+    match kind {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::BOOL => "bool",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        _ => "<unknown type>",
+    }
+}
+
+/* A compiled stage kept after linking, so a reload can reuse it if its source is unchanged */
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CompiledStage {
+    /* Hash of the stage and its preprocessed source */
+    hash: u64,
+    shader: GLuint,
+}
+
+/* Programs compare and hash by their stages and defines rather than their GL id, which changes
+ * on every reload. Two programs declared with the same files, text or embedded bytes and the same
+ * defines are equal, and as reloads change neither, a program can key a map across reloads. */
 #[derive(Debug)]
 pub struct ShaderProgram {
     id: Cell<GLuint>,
-    vertex_filename: String,
-    fragment_filename: String,
+    /* Hash of every stage and its preprocessed source, from the last successful build */
+    content_hash: Cell<u64>,
+    stages: Vec<(ShaderStage, ShaderSource)>,
+    last_compile_duration: Cell<Duration>,
+    /* Names and values #defined at the top of every stage */
+    defines: Vec<(String, String)>,
+    /* Files pulled in through #include by the last successful build */
+    includes: RefCell<Vec<PathBuf>>,
+    /* One per stage, in the same order; empty for programs loaded from SPIR-V */
+    compiled: RefCell<Vec<CompiledStage>>,
+    /* Fragment outputs and the colour attachments they write to, bound before every link */
+    output_locations: RefCell<Vec<(String, u32)>>,
+    /* Vertex shader outputs captured by transform feedback, set before every link */
+    transform_feedback: RefCell<Option<(Vec<String>, TransformFeedbackMode)>>,
+    /* Set when a binding was added since the program was last linked */
+    outputs_pending: Cell<bool>,
+    /* Why the last build failed, cleared by a successful one */
+    last_error: RefCell<Option<ShaderError>>,
 }
 
 #[derive(Debug)]
@@ -25,18 +221,272 @@ pub struct ActivatedShader {
 
 impl ShaderProgram {
     pub fn new(
-        vertex_filename: &str,
-        fragment_filename: &str,
+        vertex: ShaderSource,
+        fragment: ShaderSource,
+        geometry: Option<ShaderSource>,
+        tess_control: Option<ShaderSource>,
+        tess_eval: Option<ShaderSource>,
     ) -> Result<ShaderProgram> {
-        let vertex_src: &str = &read_from_file(vertex_filename);
-        let fragment_src: &str = &read_from_file(fragment_filename);
+        ShaderProgram::with_defines(vertex, fragment, geometry, tess_control, tess_eval, Vec::new())
+    }
 
-        let id = unsafe { create_shader_program(vertex_src, fragment_src)? };
-        Ok(ShaderProgram {
+    /* As `new`, with `#define NAME VALUE` added to each stage after its #version line */
+    pub fn with_defines(
+        vertex: ShaderSource,
+        fragment: ShaderSource,
+        geometry: Option<ShaderSource>,
+        tess_control: Option<ShaderSource>,
+        tess_eval: Option<ShaderSource>,
+        defines: Vec<(String, String)>,
+    ) -> Result<ShaderProgram> {
+        let mut stages = vec![
+            (ShaderStage::Vertex, vertex),
+            (ShaderStage::Fragment, fragment),
+        ];
+        if let Some(geometry) = geometry {
+            stages.push((ShaderStage::Geometry, geometry));
+        }
+        if let Some(tess_control) = tess_control {
+            stages.push((ShaderStage::TessControl, tess_control));
+        }
+        if let Some(tess_eval) = tess_eval {
+            stages.push((ShaderStage::TessEvaluation, tess_eval));
+        }
+        ShaderProgram::from_stages(stages, defines)
+    }
+
+    /* As `with_defines`, but a program that fails to build is still returned, unlinked, with the
+     * reason in build_error(), so that it can be watched and fixed by a reload */
+    pub fn allowing_errors(
+        vertex: ShaderSource,
+        fragment: ShaderSource,
+        geometry: Option<ShaderSource>,
+        tess_control: Option<ShaderSource>,
+        tess_eval: Option<ShaderSource>,
+        defines: Vec<(String, String)>,
+    ) -> ShaderProgram {
+        let mut stages = vec![
+            (ShaderStage::Vertex, vertex),
+            (ShaderStage::Fragment, fragment),
+        ];
+        stages.extend(geometry.map(|source| (ShaderStage::Geometry, source)));
+        stages.extend(tess_control.map(|source| (ShaderStage::TessControl, source)));
+        stages.extend(tess_eval.map(|source| (ShaderStage::TessEvaluation, source)));
+        let program = ShaderProgram::unlinked(stages, defines);
+        program.link().ok();
+        program
+    }
+
+    pub fn new_compute(path: &str) -> Result<ShaderProgram> {
+        ShaderProgram::from_stages(
+            vec![(ShaderStage::Compute, ShaderSource::File(PathBuf::from(path)))],
+            Vec::new(),
+        )
+    }
+
+    /* The program from the SPIR-V binaries in `cache_dir`, when the driver can load them and
+     * every stage has an entry for its current source and `defines`. Ok(None) means the GLSL
+     * has to be compiled instead. */
+    pub fn from_spirv_cache(
+        stages: &[(ShaderStage, ShaderSource)],
+        defines: &[(String, String)],
+        cache_dir: &Path,
+    ) -> Result<Option<ShaderProgram>> {
+        if !spirv::is_supported() {
+            return Ok(None);
+        }
+        let (sources, includes, _) = read_sources(stages, defines)?;
+        let (id, duration) = match unsafe { load_spirv_program(&sources, cache_dir)? } {
+            Some(loaded) => loaded,
+            None => return Ok(None),
+        };
+        Ok(Some(ShaderProgram {
             id: Cell::new(id),
-            vertex_filename: vertex_filename.to_string(),
-            fragment_filename: fragment_filename.to_string(),
-        })
+            content_hash: Cell::new(content_hash(&sources)),
+            stages: stages.to_vec(),
+            defines: defines.to_vec(),
+            last_compile_duration: Cell::new(duration),
+            includes: RefCell::new(includes),
+            compiled: RefCell::new(Vec::new()),
+            output_locations: RefCell::new(Vec::new()),
+            transform_feedback: RefCell::new(None),
+            outputs_pending: Cell::new(false),
+            last_error: RefCell::new(None),
+        }))
+    }
+
+    /* Uses SPIR-V binaries from `cache_dir` as from_spirv_cache does, and compiles the GLSL
+     * when they can't be used */
+    pub fn load_or_compile_spirv(
+        stages: Vec<(ShaderStage, ShaderSource)>,
+        defines: Vec<(String, String)>,
+        cache_dir: &Path,
+    ) -> Result<ShaderProgram> {
+        match ShaderProgram::from_spirv_cache(&stages, &defines, cache_dir) {
+            Ok(Some(program)) => return Ok(program),
+            Ok(None) => {}
+            Err(e) => println!("Warning: cannot load cached SPIR-V, compiling GLSL: {}", e),
+        }
+        ShaderProgram::from_stages(stages, defines)
+    }
+
+    /* Compiles one stage's GLSL, includes already expanded, to SPIR-V words */
+    #[cfg(feature = "spirv")]
+    pub fn compile_glsl_to_spirv(src: &str, stage: ShaderStage) -> Result<Vec<u32>> {
+        spirv::compile(src, stage).map_err(|log| ShaderError::Compile { stage, log })
+    }
+
+    /* Compiles each of `stages` to SPIR-V in `output_dir`, named as load_or_compile_spirv looks
+     * for them, so the directory can be its cache. Returns the files written. */
+    #[cfg(feature = "spirv")]
+    pub fn precompile_spirv(
+        stages: &[(ShaderStage, ShaderSource)],
+        defines: &[(String, String)],
+        output_dir: &Path,
+    ) -> Result<Vec<PathBuf>> {
+        let (sources, _, maps) = read_sources(stages, defines)?;
+        let mut written = Vec::with_capacity(sources.len());
+        for &(stage, ref src) in &sources {
+            let words = ShaderProgram::compile_glsl_to_spirv(src, stage)
+                .map_err(|e| annotate_compile_error(e, &sources, &maps))?;
+            let path = spirv::cache_path(output_dir, stage, src);
+            fs::write(&path, ::bytemuck::cast_slice::<u32, u8>(&words))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+
+    /* Writes the linked program as the driver's own binary, for load_binary to read back without
+     * compiling. The file starts with the binary's format and a hash of the driver that made it,
+     * as binaries are only valid for the exact driver that produced them. */
+    pub fn save_binary(&self, path: &Path) -> io::Result<()> {
+        if !program_binary_supported() {
+            return Err(io::Error::new(io::ErrorKind::Other, "program binaries are not supported"));
+        }
+        let id = self.id.get();
+        let mut length = 0;
+        let mut format = 0;
+        let mut binary;
+        unsafe {
+            gl::GetProgramiv(id, gl::PROGRAM_BINARY_LENGTH, &mut length);
+            if length <= 0 {
+                return Err(io::Error::new(io::ErrorKind::Other, "the driver returned no binary"));
+            }
+            binary = vec![0u8; length as usize];
+            gl::GetProgramBinary(
+                id,
+                length,
+                &mut length,
+                &mut format,
+                binary.as_mut_ptr() as *mut c_void,
+            );
+        }
+        binary.truncate(length.max(0) as usize);
+
+        let mut contents = Vec::with_capacity(PROGRAM_BINARY_MAGIC.len() + 12 + binary.len());
+        contents.extend_from_slice(PROGRAM_BINARY_MAGIC);
+        contents.extend_from_slice(&format.to_le_bytes());
+        contents.extend_from_slice(&driver_hash().to_le_bytes());
+        contents.extend_from_slice(&binary);
+        fs::write(path, contents)
+    }
+
+    /* Reads a program written by save_binary. Fails, so the caller can compile the GLSL instead,
+     * if the driver can't load binaries, the file came from another driver or the driver turns
+     * it down anyway, e.g. after an update that kept its version string. The program has no
+     * sources, so it is left alone by reload. */
+    pub fn load_binary(path: &Path) -> Result<ShaderProgram> {
+        if !program_binary_supported() {
+            return Err(ShaderError::Binary("program binaries are not supported".to_string()));
+        }
+        let contents = fs::read(path)?;
+        let header_len = PROGRAM_BINARY_MAGIC.len() + 12;
+        if contents.len() <= header_len || !contents.starts_with(PROGRAM_BINARY_MAGIC) {
+            return Err(ShaderError::Binary(format!("{} is not a program binary", path.display())));
+        }
+        let field = |at: usize, len: usize| &contents[PROGRAM_BINARY_MAGIC.len() + at..][..len];
+        let format = GLenum::from_le_bytes(field(0, 4).try_into().unwrap());
+        let driver = u64::from_le_bytes(field(4, 8).try_into().unwrap());
+        let binary = &contents[header_len..];
+
+        if !program_binary_formats().contains(&(format as GLint)) {
+            return Err(ShaderError::Binary(format!("the driver cannot load binary format {:#x}", format)));
+        }
+        if driver != driver_hash() {
+            return Err(ShaderError::Binary("the binary was made by a different driver".to_string()));
+        }
+
+        let start = Instant::now();
+        let id = unsafe {
+            let id = gl::CreateProgram();
+            gl::ProgramBinary(id, format, binary.as_ptr() as *const c_void, binary.len() as GLsizei);
+            let mut success = gl::FALSE as GLint;
+            gl::GetProgramiv(id, gl::LINK_STATUS, &mut success);
+            if success != gl::TRUE as GLint {
+                gl::DeleteProgram(id);
+                return Err(ShaderError::Binary("the driver rejected the binary".to_string()));
+            }
+            id
+        };
+
+        let program = ShaderProgram::unlinked(Vec::new(), Vec::new());
+        program.id.set(id);
+        program.last_compile_duration.set(start.elapsed());
+        let mut hasher = DefaultHasher::new();
+        binary.hash(&mut hasher);
+        program.content_hash.set(hasher.finish());
+        Ok(program)
+    }
+
+    fn from_stages(stages: Vec<(ShaderStage, ShaderSource)>, defines: Vec<(String, String)>)
+        -> Result<ShaderProgram> {
+        let program = ShaderProgram::unlinked(stages, defines);
+        program.try_link()?;
+        Ok(program)
+    }
+
+    /* Program 0, which GL treats as no program, until link succeeds */
+    fn unlinked(stages: Vec<(ShaderStage, ShaderSource)>, defines: Vec<(String, String)>)
+        -> ShaderProgram {
+        ShaderProgram {
+            id: Cell::new(0),
+            content_hash: Cell::new(0),
+            stages,
+            defines,
+            last_compile_duration: Cell::new(Duration::from_secs(0)),
+            includes: RefCell::new(Vec::new()),
+            compiled: RefCell::new(Vec::new()),
+            output_locations: RefCell::new(Vec::new()),
+            transform_feedback: RefCell::new(None),
+            outputs_pending: Cell::new(false),
+            last_error: RefCell::new(None),
+        }
+    }
+
+    /* What stopped the last build, if it failed. The program keeps whatever it had before then,
+     * which is nothing if it has never built. */
+    pub fn last_error(&self) -> Option<Ref<ShaderError>> {
+        Ref::filter_map(self.last_error.borrow(), Option::as_ref).ok()
+    }
+
+    /* last_error as text, e.g. the compiler log */
+    pub fn build_error(&self) -> Option<String> {
+        self.last_error().map(|e| e.to_string())
+    }
+
+    /* Whether the program has ever built, so that drawing with it does something */
+    pub fn is_linked(&self) -> bool {
+        self.id.get() != 0
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id.get()
+    }
+
+    /* Changes whenever a build finds different source, unlike equality, which goes by what the
+     * program was declared with */
+    pub fn content_hash(&self) -> u64 {
+        self.content_hash.get()
     }
 
     pub fn activate<F>(&self, f: F)
@@ -45,16 +495,388 @@ impl ShaderProgram {
             f(&activated_shader);
     }
 
-    pub fn reload(&self) {
-        println!("Reloading shader ({} + {})", self.vertex_filename, self.fragment_filename);
-        let vertex_src: &str = &read_from_file(&self.vertex_filename);
-        let fragment_src: &str = &read_from_file(&self.fragment_filename);
-        let id = unsafe { create_shader_program(vertex_src, fragment_src).expect("Could not create shader program") };
-        self.id.set(id);
+    pub fn dispatch(&self, x: u32, y: u32, z: u32) {
+        let _activated = ActivatedShader::new(self.id.get());
+        unsafe {
+            gl::DispatchCompute(x, y, z);
+        }
+    }
+
+    /* Writes the fragment output `name` to colour attachment `index` when drawing into a
+     * framebuffer with several, for outputs without a layout(location = N) qualifier. Programs
+     * are linked when they are created, so this takes effect from the next link, i.e. the next
+     * reload, and stays in effect for every one after it. */
+    pub fn bind_output_location(&self, name: &str, index: u32) {
+        let mut outputs = self.output_locations.borrow_mut();
+        outputs.retain(|&(ref output, _)| output != name);
+        outputs.push((name.to_string(), index));
+        self.outputs_pending.set(true);
+    }
+
+    /* Captures the vertex shader outputs `varyings` into a TransformFeedbackBuffer when drawing,
+     * e.g. to move particles on the GPU. GL can only be told which outputs to capture before
+     * linking, so this relinks the program straight away, reusing the compiled stages, and keeps
+     * the varyings for every reload after. Returns whether the relink succeeded; if not, the old
+     * program is kept and last_error says why, e.g. a varying the shader doesn't write. */
+    pub fn enable_transform_feedback(&self, varyings: &[&str], mode: TransformFeedbackMode) -> bool {
+        let varyings = varyings.iter().map(|varying| varying.to_string()).collect();
+        self.transform_feedback.replace(Some((varyings, mode)));
+        self.outputs_pending.set(true);
+        self.link().is_ok()
+    }
+
+    /* If the program doesn't build, the old one is kept, and last_error says why until a build
+     * succeeds. Embedded sources can't change while the program is running, so a program built
+     * only from them is left as it is, unless it has output locations waiting to be bound. */
+    pub fn reload(&self) -> Result<()> {
+        let embedded = self.stages.iter().all(|(_, source)| matches!(*source, ShaderSource::Embedded(_)));
+        if embedded && !self.outputs_pending.get() {
+            return Ok(());
+        }
+        let names: Vec<String> = self.stages.iter().map(|(_, source)| source.to_string()).collect();
+        println!("Reloading shader ({})", names.join(" + "));
+        self.link()
+    }
+
+    /* Builds the program from its sources, replacing the current one if that succeeds, and keeps
+     * a copy of any error for last_error. After a failed build, says how this one went compared
+     * to it. */
+    fn link(&self) -> Result<()> {
+        let result = self.try_link();
+        let before = self.build_error().map(|log| error_line_count(&log));
+        match (before, result.as_ref()) {
+            (Some(before), Ok(_)) => println!("Recovered: {} error line(s) before, none now", before),
+            (before, Err(e)) => {
+                let after = error_line_count(&e.to_string());
+                if let Some(before) = before.filter(|&before| before != after) {
+                    println!("Still failing: {} error line(s) before, {} now", before, after);
+                }
+            }
+            (None, Ok(_)) => {}
+        }
+        self.last_error.replace(result.as_ref().err().cloned());
+        result
+    }
+
+    fn try_link(&self) -> Result<()> {
+        let (sources, includes, maps) = read_sources(&self.stages, &self.defines)?;
+        let (id, duration, compiled) = unsafe {
+            create_shader_program(
+                &sources,
+                &self.compiled.borrow(),
+                &self.output_locations.borrow(),
+                self.transform_feedback.borrow().as_ref(),
+            )
+        }
+        .map_err(|e| annotate_compile_error(e, &sources, &maps))?;
+        self.outputs_pending.set(false);
+        self.last_compile_duration.set(duration);
+        self.content_hash.set(content_hash(&sources));
+        self.includes.replace(includes);
+        let old_id = self.id.replace(id);
+        let old_compiled = self.compiled.replace(compiled);
+        unsafe {
+            gl::DeleteProgram(old_id);
+            for stage in old_compiled {
+                if !self.compiled.borrow().contains(&stage) {
+                    gl::DeleteShader(stage.shader);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /* Asks the driver whether the program could run with the current GL state, e.g. that no two
+     * samplers of different types share a texture unit. Unlike linking, this depends on what is
+     * bound at the time, so call it just before drawing. */
+    pub fn validate(&self) -> ::std::result::Result<(), String> {
+        let id = self.id.get();
+        let mut success = gl::FALSE as GLint;
+        let mut length = 0;
+        unsafe {
+            gl::ValidateProgram(id);
+            gl::GetProgramiv(id, gl::VALIDATE_STATUS, &mut success);
+            if success == gl::TRUE as GLint {
+                return Ok(());
+            }
+            gl::GetProgramiv(id, gl::INFO_LOG_LENGTH, &mut length);
+            let mut info_log = vec![0u8; length.max(1) as usize];
+            gl::GetProgramInfoLog(
+                id,
+                info_log.len() as GLsizei,
+                ptr::null_mut(),
+                info_log.as_mut_ptr() as *mut GLchar,
+            );
+            Err(info_log_to_str(info_log))
+        }
+    }
+
+    /* The source of `stage` as it is on disk now, with every #include expanded in place, so it
+     * stands alone as a single file. Defines and the GLSL ES rewrite are left out, as they are not
+     * part of what was written. None if the program has no such stage. */
+    pub fn expanded_source(&self, stage: ShaderStage) -> Option<Result<String>> {
+        self.stages
+            .iter()
+            .find(|&&(s, _)| s == stage)
+            .map(|(_, source)| expand_includes(source).map(|(src, _, _)| src))
+    }
+
+    pub fn last_compile_duration(&self) -> Duration {
+        self.last_compile_duration.get()
+    }
+
+    /* Where `stage` is built from, if the program has one */
+    pub fn stage_source(&self, stage: ShaderStage) -> Option<&ShaderSource> {
+        self.stages.iter().find(|&&(s, _)| s == stage).map(|(_, source)| source)
+    }
+
+    pub fn filenames(&self) -> Vec<&Path> {
+        self.stages
+            .iter()
+            .filter_map(|(_, source)| match *source {
+                ShaderSource::File(ref path) => Some(path.as_path()),
+                ShaderSource::Inline(_) | ShaderSource::Embedded(_) => None,
+            })
+            .collect()
+    }
+
+    /* The stage files plus everything they include */
+    pub fn watched_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self.filenames().iter().map(|f| f.to_path_buf()).collect();
+        files.extend(self.includes.borrow().iter().cloned());
+        files
+    }
+
+    pub fn depends_on(&self, path: &Path) -> bool {
+        self.watched_files().iter().any(|f| fs::canonicalize(f).map(|f| f == path).unwrap_or(false))
+    }
+
+    /* Includes added by later reloads are picked up by depends_on, but only if they live in a
+     * directory that is already being watched */
+    pub fn watch(&self, tx: Sender<ReloadEvent>) -> ::std::result::Result<(), ::notify::Error> {
+        hotloader::watch_files(&self.watched_files(), tx)
+    }
+
+    pub fn set_uniform(&self, name: &str, value: &UniformValue) {
+        match *value {
+            UniformValue::Float(v) => self.set_uniform_f32(name, v),
+            UniformValue::Vec2(v) => self.set_uniform_vec2(name, v),
+            UniformValue::Vec3(v) => self.set_uniform_vec3(name, v),
+            UniformValue::Vec4(v) => self.set_uniform_vec4(name, v),
+            UniformValue::Bool(v) => self.set_uniform_bool(name, v),
+        }
+    }
+
+    pub fn set_uniform_f32(&self, name: &str, value: f32) {
+        self.with_uniform(name, |loc| unsafe { gl::Uniform1f(loc, value) });
+    }
+
+    pub fn set_uniform_bool(&self, name: &str, value: bool) {
+        self.with_uniform(name, |loc| unsafe { gl::Uniform1i(loc, value as GLint) });
+    }
+
+    pub fn set_uniform_vec2(&self, name: &str, value: [f32; 2]) {
+        self.with_uniform(name, |loc| unsafe { gl::Uniform2f(loc, value[0], value[1]) });
+    }
+
+    pub fn set_uniform_i32(&self, name: &str, value: i32) {
+        self.with_uniform(name, |loc| unsafe { gl::Uniform1i(loc, value) });
+    }
+
+    pub fn set_uniform_vec3(&self, name: &str, value: [f32; 3]) {
+        self.with_uniform(name, |loc| unsafe {
+            gl::Uniform3f(loc, value[0], value[1], value[2])
+        });
+    }
+
+    pub fn set_uniform_vec4(&self, name: &str, value: [f32; 4]) {
+        self.with_uniform(name, |loc| unsafe {
+            gl::Uniform4f(loc, value[0], value[1], value[2], value[3])
+        });
+    }
+
+    pub fn set_uniform_sampler2d(&self, name: &str, unit: u32) {
+        self.with_uniform(name, |loc| unsafe { gl::Uniform1i(loc, unit as GLint) });
+    }
+
+    pub fn set_uniform_mat4f(&self, name: &str, value: &Matrix4<f32>) {
+        self.with_uniform(name, |loc| unsafe {
+            gl::UniformMatrix4fv(loc, 1, gl::FALSE, value.as_ptr())
+        });
+    }
+
+    pub fn has_uniform(&self, name: &str) -> bool {
+        match CString::new(name) {
+            Ok(c_name) => unsafe { gl::GetUniformLocation(self.id.get(), c_name.as_ptr()) != -1 },
+            Err(_) => false,
+        }
+    }
+
+    pub fn active_uniforms(&self) -> Vec<UniformInfo> {
+        let id = self.id.get();
+        self.active_variables(gl::ACTIVE_UNIFORMS, gl::ACTIVE_UNIFORM_MAX_LENGTH, |i, len, buf| {
+            let (mut length, mut size, mut kind) = (0, 0, 0);
+            unsafe {
+                gl::GetActiveUniform(id, i, len, &mut length, &mut size, &mut kind, buf);
+                let location = gl::GetUniformLocation(id, buf);
+                (length, location, kind, size)
+            }
+        })
+        .into_iter()
+        .map(|(name, location, kind, size)| UniformInfo { name, location, kind, size })
+        .collect()
+    }
+
+    pub fn active_attributes(&self) -> Vec<AttributeInfo> {
+        let id = self.id.get();
+        self.active_variables(gl::ACTIVE_ATTRIBUTES, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH, |i, len, buf| {
+            let (mut length, mut size, mut kind) = (0, 0, 0);
+            unsafe {
+                gl::GetActiveAttrib(id, i, len, &mut length, &mut size, &mut kind, buf);
+                let location = gl::GetAttribLocation(id, buf);
+                (length, location, kind, size)
+            }
+        })
+        .into_iter()
+        .map(|(name, location, kind, size)| AttributeInfo { name, location, kind, size })
+        .collect()
+    }
+
+    /* Reads back the current value of a float, vector or bool uniform. Other types, and arrays,
+     * have no UniformValue to hold them. */
+    pub fn uniform_value(&self, info: &UniformInfo) -> Option<UniformValue> {
+        let components = match info.kind {
+            gl::FLOAT | gl::BOOL => 1,
+            gl::FLOAT_VEC2 => 2,
+            gl::FLOAT_VEC3 => 3,
+            gl::FLOAT_VEC4 => 4,
+            _ => return None,
+        };
+        if info.size != 1 || info.location == -1 {
+            return None;
+        }
+
+        let mut v = [0.0f32; 4];
+        unsafe {
+            gl::GetUniformfv(self.id.get(), info.location, v.as_mut_ptr());
+        }
+        Some(match (info.kind, components) {
+            (gl::BOOL, _) => UniformValue::Bool(v[0] != 0.0),
+            (_, 1) => UniformValue::Float(v[0]),
+            (_, 2) => UniformValue::Vec2([v[0], v[1]]),
+            (_, 3) => UniformValue::Vec3([v[0], v[1], v[2]]),
+            _ => UniformValue::Vec4(v),
+        })
+    }
+
+    /* Runs `query` for each of the `count` active variables with a name buffer of `max_length`
+     * bytes. `query` returns the name's length followed by its location, type and size. */
+    fn active_variables<F>(&self, count: GLenum, max_length: GLenum, query: F)
+        -> Vec<(String, GLint, GLenum, GLint)>
+        where F: Fn(GLuint, GLsizei, *mut GLchar) -> (GLsizei, GLint, GLenum, GLint) {
+        let (mut n, mut len) = (0, 0);
+        unsafe {
+            gl::GetProgramiv(self.id.get(), count, &mut n);
+            gl::GetProgramiv(self.id.get(), max_length, &mut len);
+        }
+
+        let mut variables = Vec::with_capacity(n as usize);
+        let mut buf = vec![0u8; len.max(1) as usize];
+        for i in 0..n as GLuint {
+            let (length, location, kind, size) =
+                query(i, buf.len() as GLsizei, buf.as_mut_ptr() as *mut GLchar);
+            let name = String::from_utf8_lossy(&buf[..length as usize]).into_owned();
+            variables.push((name, location, kind, size));
+        }
+        variables
+    }
+
+    /* Looks up the uniform and runs `f` with this program bound, restoring whichever program was
+     * bound beforehand. Missing uniforms only produce a warning, as the GLSL compiler is free to
+     * strip any that are unused. */
+    fn with_uniform<F>(&self, name: &str, f: F)
+        where F: FnOnce(GLint) {
+        /* Nothing to set, and no need to warn about it again */
+        if !self.is_linked() {
+            return;
+        }
+        let c_name = match CString::new(name) {
+            Ok(c_name) => c_name,
+            Err(_) => {
+                println!("Warning: invalid uniform name {:?}", name);
+                return;
+            }
+        };
+
+        unsafe {
+            let loc = gl::GetUniformLocation(self.id.get(), c_name.as_ptr());
+            if loc == -1 {
+                println!("Warning: cannot find uniform {} in current shader", name);
+                return;
+            }
+
+            let mut previous = 0;
+            gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut previous);
+            gl::UseProgram(self.id.get());
+            f(loc);
+            gl::UseProgram(previous as GLuint);
+        }
     }
 
 }
 
+impl ShaderProgram {
+    /* What PartialEq and Hash go by. A program from load_binary has no stages or defines, but is
+     * never rebuilt, so the hash of its binary stands in for them. */
+    fn identity(&self) -> (&[(ShaderStage, ShaderSource)], &[(String, String)], u64) {
+        let binary = if self.stages.is_empty() { self.content_hash.get() } else { 0 };
+        (&self.stages, &self.defines, binary)
+    }
+}
+
+impl PartialEq for ShaderProgram {
+    fn eq(&self, other: &ShaderProgram) -> bool {
+        self.identity() == other.identity()
+    }
+}
+
+impl Eq for ShaderProgram {}
+
+impl Hash for ShaderProgram {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.identity().hash(state);
+    }
+}
+
+/* GL programs can't be copied, so cloning builds a new one from the same sources, defines and
+ * output locations: a full compile and link of every stage, so not something to do per frame.
+ * Sources are read again, so a clone made after a file changed gets the new source even if this
+ * program hasn't been reloaded. A clone that fails to build is unlinked with the reason in
+ * last_error, as from allowing_errors, and a clone of a program from load_binary, which has no
+ * sources, is always unlinked. */
+impl Clone for ShaderProgram {
+    fn clone(&self) -> ShaderProgram {
+        let program = ShaderProgram::unlinked(self.stages.clone(), self.defines.clone());
+        program.output_locations.replace(self.output_locations.borrow().clone());
+        program.transform_feedback.replace(self.transform_feedback.borrow().clone());
+        if !program.stages.is_empty() {
+            program.link().ok();
+        }
+        program
+    }
+}
+
+impl Drop for ShaderProgram {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.id.get());
+            for stage in self.compiled.borrow().iter() {
+                gl::DeleteShader(stage.shader);
+            }
+        }
+    }
+}
+
 impl Drop for ActivatedShader {
     fn drop(&mut self) {
         self.deactivate();
@@ -67,7 +889,7 @@ impl ActivatedShader {
         unsafe {
             gl::UseProgram(id);
         }
-        ActivatedShader { id: id }
+        ActivatedShader { id }
     }
 
     pub fn deactivate(&self) {
@@ -84,6 +906,14 @@ impl ActivatedShader {
         Ok(())
     }
 
+    pub fn set_int(&self, name: &str, value: i32) -> Result<()> {
+        let loc = self.location(name)?;
+        unsafe {
+            gl::Uniform1i(loc, value);
+        }
+        Ok(())
+    }
+
     pub fn set_float4(&self, name: &str, v1: f32, v2: f32, v3: f32, v4: f32) -> Result<()> {
         let loc = self.location(name)?;
         unsafe {
@@ -93,34 +923,154 @@ impl ActivatedShader {
     }
 
     fn location(&self, name: &str) -> Result<GLint> {
-        let c_name = CString::new(name)?;
+        let c_name = CString::new(name)
+            .map_err(|_| ShaderError::Uniform(format!("Invalid uniform name {:?}", name)))?;
         let loc = unsafe {
             gl::GetUniformLocation(self.id, c_name.as_ptr())
         };
         if loc == -1 {
-            return Err(format!("Cannot find location {} in current shader", name).into());
+            return Err(ShaderError::Uniform(format!("Cannot find location {} in current shader", name)));
         }
 
         Ok(loc)
     }
 }
 
-fn read_from_file(filename: &str) -> String {
-    let mut file = File::open(filename).expect("Could not open file");
-    let mut s = String::new();
-    file.read_to_string(&mut s).expect("Could not read file");
-    s
+type StageSources = Vec<(ShaderStage, String)>;
+
+/* Reads and preprocesses each stage, returning the sources along with the files they included
+ * and a source map for each stage */
+fn read_sources(stages: &[(ShaderStage, ShaderSource)], defines: &[(String, String)])
+    -> Result<(StageSources, Vec<PathBuf>, Vec<SourceMap>)> {
+    let mut sources = Vec::with_capacity(stages.len());
+    let mut includes = Vec::new();
+    let mut maps = Vec::with_capacity(stages.len());
+    for &(stage, ref source) in stages {
+        let (src, stage_includes, map) = expand_includes(source)?;
+        let (src, map) = if GLSL_ES.load(Ordering::SeqCst) {
+            preprocess::to_glsl_es(&src, &map)
+        } else {
+            (src, map)
+        };
+        let (src, map) = preprocess::inject_defines(&src, &map, defines);
+        for include in stage_includes {
+            if !includes.contains(&include) {
+                includes.push(include);
+            }
+        }
+        sources.push((stage, src));
+        maps.push(map);
+    }
+    Ok((sources, includes, maps))
+}
+
+/* Reads `source` and expands its #includes, looking next to the file first */
+fn expand_includes(source: &ShaderSource) -> Result<(String, Vec<PathBuf>, SourceMap)> {
+    let src = resolve_source(source)?;
+    let expanded = match *source {
+        ShaderSource::File(ref path) => {
+            let dir = path.parent().unwrap_or_else(|| Path::new("."));
+            preprocess::preprocess_with_source_map(&src, Some(path), &[dir])?
+        }
+        ShaderSource::Inline(_) | ShaderSource::Embedded(_) => {
+            preprocess::preprocess_with_source_map(&src, None, &[Path::new(".")])?
+        }
+    };
+    Ok(expanded)
 }
 
-unsafe fn create_shader(src: &str, shader_type: GLuint) -> Result<GLuint> {
-    let vertex_shader = gl::CreateShader(shader_type);
-    let c_str_vert = CString::new(src.as_bytes()).expect("Could not create vertex shader c string");
+/* Points the line numbers of a compile error at the files the failing stage was expanded from */
+fn annotate_compile_error(error: ShaderError, sources: &StageSources, maps: &[SourceMap]) -> ShaderError {
+    match error {
+        ShaderError::Compile { stage, log } => {
+            let log = match sources.iter().position(|&(s, _)| s == stage) {
+                Some(i) => preprocess::annotate_error_log(&log, &maps[i]),
+                None => log,
+            };
+            ShaderError::Compile { stage, log }
+        }
+        error => error,
+    }
+}
+
+fn resolve_source(src: &ShaderSource) -> io::Result<String> {
+    match *src {
+        ShaderSource::File(ref path) => {
+            let mut file = File::open(path)?;
+            let mut s = String::new();
+            file.read_to_string(&mut s)?;
+            Ok(s)
+        }
+        ShaderSource::Inline(ref s) => Ok(s.clone()),
+        ShaderSource::Embedded(bytes) => str::from_utf8(bytes)
+            .map(str::to_string)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+    }
+}
+
+unsafe fn create_shader(src: &str, stage: ShaderStage) -> Result<GLuint> {
+    let src = with_version_directive(src);
+    let c_str_vert = CString::new(src.as_bytes()).map_err(|_| ShaderError::Compile {
+        stage,
+        log: "Shader source contains a nul byte".to_string(),
+    })?;
+    let vertex_shader = gl::CreateShader(stage.gl_type());
     gl::ShaderSource(vertex_shader, 1, &c_str_vert.as_ptr(), ptr::null());
     gl::CompileShader(vertex_shader);
+    check_compile_status(vertex_shader, stage)
+}
+
+/* Prepends a #version matching the context if the source does not declare one, followed by a
+ * #line so compiler messages keep the original line numbers */
+fn with_version_directive(src: &str) -> String {
+    if src.lines().any(|line| line.trim_start().starts_with("#version")) {
+        return src.to_string();
+    }
+    match context_version_directive() {
+        Some(directive) => format!("{}\n#line 1\n{}", directive, src),
+        None => src.to_string(),
+    }
+}
+
+/* Turns e.g. "4.60 NVIDIA" into "#version 460 core" and "OpenGL ES GLSL ES 3.00" into
+ * "#version 300 es" */
+fn context_version_directive() -> Option<String> {
+    let version = unsafe {
+        let version = gl::GetString(gl::SHADING_LANGUAGE_VERSION);
+        if version.is_null() {
+            return None;
+        }
+        CStr::from_ptr(version as *const _).to_string_lossy().into_owned()
+    };
 
+    let es = version.contains(" ES ");
+    let number = version.split_whitespace().find(|word| word.contains('.'))?;
+    let mut parts = number.split('.');
+    let major: u32 = parts.next()?.parse().ok()?;
+    /* Minor versions are two digits, but some drivers drop the trailing zero */
+    let minor: String = parts.next()?.chars().take(2).collect();
+    let minor: u32 = format!("{:0<2}", minor).parse().ok()?;
+    let number = major * 100 + minor;
+
+    Some(if es {
+        format!("#version {} es", number)
+    } else if number >= 150 {
+        format!("#version {} core", number)
+    } else {
+        format!("#version {}", number)
+    })
+}
+
+unsafe fn create_spirv_shader(binary: &[u8], stage: ShaderStage) -> Result<GLuint> {
+    let shader = gl::CreateShader(stage.gl_type());
+    spirv::specialize(shader, binary);
+    check_compile_status(shader, stage)
+}
+
+/* Deletes the shader if it failed to compile */
+unsafe fn check_compile_status(vertex_shader: GLuint, stage: ShaderStage) -> Result<GLuint> {
     let mut success = gl::FALSE as GLint;
-    let mut info_log = Vec::with_capacity(512);
-    info_log.set_len(512 - 1);
+    let mut info_log = vec![0u8; 512];
     gl::GetShaderiv(vertex_shader, gl::COMPILE_STATUS, &mut success);
     if success != gl::TRUE as GLint {
         gl::GetShaderInfoLog(
@@ -130,28 +1080,191 @@ unsafe fn create_shader(src: &str, shader_type: GLuint) -> Result<GLuint> {
             info_log.as_mut_ptr() as *mut GLchar,
         );
 
-        let s = info_log_to_str(info_log);
-        return Err(format!("ERROR::SHADER::VERTEX::COMPILATION_FAILED\n{}", s).into());
+        gl::DeleteShader(vertex_shader);
+        return Err(ShaderError::Compile { stage, log: info_log_to_str(info_log) });
     }
     Ok(vertex_shader)
 }
 
+/* Compiles and links `sources`, reusing the shader from `previous` for any stage whose source
+ * hashes the same, so an edit to one stage only recompiles that stage. Each of `outputs` is bound
+ * to its colour attachment before linking. */
 unsafe fn create_shader_program(
-    vertex_src: &str,
-    fragment_src: &str,
-) -> Result<GLuint> {
-    let vertex_shader = create_shader(vertex_src, gl::VERTEX_SHADER)?;
-    let fragment_shader = create_shader(fragment_src, gl::FRAGMENT_SHADER)?;
+    sources: &[(ShaderStage, String)],
+    previous: &[CompiledStage],
+    outputs: &[(String, u32)],
+    transform_feedback: Option<&(Vec<String>, TransformFeedbackMode)>,
+) -> Result<(GLuint, Duration, Vec<CompiledStage>)> {
+    let start = Instant::now();
+    let mut compiled = Vec::with_capacity(sources.len());
+    /* Only the shaders made by this call may be deleted if it fails */
+    let mut created = Vec::new();
+    for (i, &(stage, ref src)) in sources.iter().enumerate() {
+        let hash = stage_hash(stage, src);
+        match previous.get(i) {
+            Some(&old) if old.hash == hash => compiled.push(old),
+            _ => match create_shader(src, stage) {
+                Ok(shader) => {
+                    created.push(shader);
+                    compiled.push(CompiledStage { hash, shader });
+                }
+                Err(e) => {
+                    for shader in created {
+                        gl::DeleteShader(shader);
+                    }
+                    return Err(e);
+                }
+            },
+        }
+    }
+    if !previous.is_empty() {
+        debug!("Recompiled {} of {} shader stages", created.len(), sources.len());
+    }
+
+    let shaders: Vec<GLuint> = compiled.iter().map(|stage| stage.shader).collect();
+    match link_program(&shaders, outputs, transform_feedback, start) {
+        Ok((id, duration)) => Ok((id, duration, compiled)),
+        Err(e) => {
+            for shader in created {
+                gl::DeleteShader(shader);
+            }
+            Err(e)
+        }
+    }
+}
+
+/* FNV-1a. Unlike DefaultHasher its output is fixed, so hashes written to disk, such as SPIR-V
+ * cache names and the driver hash in saved binaries, still match in another build. Callers feed
+ * it bytes through `write` rather than Hash impls, whose output is not fixed either. */
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> StableHasher {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+
+    /* `bytes` followed by a separator, so that consecutive fields can't run together */
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write(bytes);
+        self.write(&[0xff]);
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+pub fn stage_hash(stage: ShaderStage, src: &str) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.write_field(stage.to_string().as_bytes());
+    hasher.write_field(src.as_bytes());
+    hasher.finish()
+}
+
+/* Lines in an error's text that look like compiler messages, for comparing one failed build with
+ * the next */
+fn error_line_count(log: &str) -> usize {
+    log.lines().filter(|line| line.contains("ERROR") || line.contains("error")).count()
+}
+
+fn content_hash(sources: &[(ShaderStage, String)]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for &(stage, ref src) in sources {
+        stage_hash(stage, src).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/* Returns Ok(None) if any stage is missing from the cache */
+unsafe fn load_spirv_program(
+    sources: &[(ShaderStage, String)],
+    cache_dir: &Path,
+) -> Result<Option<(GLuint, Duration)>> {
+    let start = Instant::now();
+    let mut binaries = Vec::with_capacity(sources.len());
+    for &(stage, ref src) in sources {
+        let path = spirv::cache_path(cache_dir, stage, src);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        binaries.push((stage, fs::read(path)?));
+    }
 
+    let mut shaders = Vec::with_capacity(binaries.len());
+    for (stage, binary) in binaries {
+        match create_spirv_shader(&binary, stage) {
+            Ok(shader) => shaders.push(shader),
+            Err(e) => {
+                for shader in shaders {
+                    gl::DeleteShader(shader);
+                }
+                return Err(e);
+            }
+        }
+    }
+    let result = link_program(&shaders, &[], None, start);
+    for shader in shaders {
+        gl::DeleteShader(shader);
+    }
+    result.map(Some)
+}
+
+/* Links `shaders`, timing from `start`. The shaders stay alive, so the caller must delete
+ * them once they are no longer needed. */
+unsafe fn link_program(
+    shaders: &[GLuint],
+    outputs: &[(String, u32)],
+    transform_feedback: Option<&(Vec<String>, TransformFeedbackMode)>,
+    start: Instant,
+) -> Result<(GLuint, Duration)> {
     let shader_program = gl::CreateProgram();
-    gl::AttachShader(shader_program, vertex_shader);
-    gl::AttachShader(shader_program, fragment_shader);
+    for &shader in shaders {
+        gl::AttachShader(shader_program, shader);
+    }
+    for &(ref name, index) in outputs {
+        match CString::new(name.as_str()) {
+            Ok(c_name) => gl::BindFragDataLocation(shader_program, index, c_name.as_ptr()),
+            Err(_) => println!("Warning: invalid fragment output name {:?}", name),
+        }
+    }
+    if let Some(&(ref varyings, mode)) = transform_feedback {
+        let names: Vec<CString> = varyings
+            .iter()
+            .filter_map(|varying| match CString::new(varying.as_str()) {
+                Ok(name) => Some(name),
+                Err(_) => {
+                    println!("Warning: invalid transform feedback varying {:?}", varying);
+                    None
+                }
+            })
+            .collect();
+        let pointers: Vec<*const GLchar> = names.iter().map(|name| name.as_ptr()).collect();
+        gl::TransformFeedbackVaryings(
+            shader_program,
+            pointers.len() as GLsizei,
+            pointers.as_ptr(),
+            mode.gl_mode(),
+        );
+    }
+    /* Some drivers only keep a binary for save_binary if asked before linking */
+    if program_binary_supported() {
+        gl::ProgramParameteri(shader_program, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as GLint);
+    }
     gl::LinkProgram(shader_program);
 
     let mut success = gl::FALSE as GLint;
-    let mut info_log = Vec::with_capacity(512);
-    info_log.set_len(512 - 1);
+    let mut info_log = vec![0u8; 512];
     gl::GetProgramiv(shader_program, gl::LINK_STATUS, &mut success);
+    let duration = start.elapsed();
     if success != gl::TRUE as GLint {
         gl::GetProgramInfoLog(
             shader_program,
@@ -159,18 +1272,11 @@ unsafe fn create_shader_program(
             ptr::null_mut(),
             info_log.as_mut_ptr() as *mut GLchar,
         );
-        return Err(
-            format!(
-                "ERROR::SHADER::PROGRAM::COMPILATION_FAILED\n{}",
-                str::from_utf8(&info_log).unwrap()
-            ).into(),
-        );
+        gl::DeleteProgram(shader_program);
+        return Err(ShaderError::Link(info_log_to_str(info_log)));
     }
 
-    gl::DeleteShader(vertex_shader);
-    gl::DeleteShader(fragment_shader);
-
-    Ok(shader_program)
+    Ok((shader_program, duration))
 }
 
 unsafe fn update_shader_program(
@@ -178,16 +1284,15 @@ unsafe fn update_shader_program(
     vertex_src: &str,
     fragment_src: &str,
 ) -> Result<()> {
-    let vertex_shader = create_shader(vertex_src, gl::VERTEX_SHADER)?;
-    let fragment_shader = create_shader(fragment_src, gl::FRAGMENT_SHADER)?;
+    let vertex_shader = create_shader(vertex_src, ShaderStage::Vertex)?;
+    let fragment_shader = create_shader(fragment_src, ShaderStage::Fragment)?;
 
     gl::AttachShader(shader_program, vertex_shader);
     gl::AttachShader(shader_program, fragment_shader);
     gl::LinkProgram(shader_program);
 
     let mut success = gl::FALSE as GLint;
-    let mut info_log = Vec::with_capacity(512);
-    info_log.set_len(512 - 1);
+    let mut info_log = vec![0u8; 512];
     gl::GetProgramiv(shader_program, gl::LINK_STATUS, &mut success);
     if success != gl::TRUE as GLint {
         gl::GetProgramInfoLog(
@@ -196,12 +1301,7 @@ unsafe fn update_shader_program(
             ptr::null_mut(),
             info_log.as_mut_ptr() as *mut GLchar,
         );
-        return Err(
-            format!(
-                "ERROR::SHADER::PROGRAM::COMPILATION_FAILED\n{}",
-                info_log_to_str(info_log)
-            ).into(),
-        );
+        return Err(ShaderError::Link(info_log_to_str(info_log)));
     }
 
     gl::DeleteShader(vertex_shader);
@@ -209,9 +1309,45 @@ unsafe fn update_shader_program(
     Ok(())
 }
 
+/* Core since GL 4.1, and ARB_get_program_binary before that. A driver may support it and still
+ * offer no formats, in which case there is nothing it could save or load. */
+/* Not used with ES, where the desktop GL_ARB_get_program_binary entry points may be missing or
+ * report formats the driver then rejects */
+fn program_binary_supported() -> bool {
+    !GLSL_ES.load(Ordering::SeqCst)
+        && gl::GetProgramBinary::is_loaded()
+        && !program_binary_formats().is_empty()
+}
+
+fn program_binary_formats() -> Vec<GLint> {
+    unsafe {
+        let mut count = 0;
+        gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut count);
+        let mut formats = vec![0; count.max(0) as usize];
+        if count > 0 {
+            gl::GetIntegerv(gl::PROGRAM_BINARY_FORMATS, formats.as_mut_ptr());
+        }
+        formats
+    }
+}
+
+/* Identifies the driver by its vendor, renderer and version strings */
+fn driver_hash() -> u64 {
+    let mut hasher = StableHasher::new();
+    for &name in &[gl::VENDOR, gl::RENDERER, gl::VERSION] {
+        unsafe {
+            let value = gl::GetString(name);
+            if !value.is_null() {
+                hasher.write_field(CStr::from_ptr(value as *const _).to_bytes());
+            }
+        }
+    }
+    hasher.finish()
+}
+
 fn info_log_to_str(info_log: Vec<u8>) -> String {
     let s = str::from_utf8(&info_log).unwrap();
     let s: String = s.chars().filter(|c| *c != '\0').collect();
-    let s = s.trim_right();
+    let s = s.trim_end();
     s.to_string()
 }
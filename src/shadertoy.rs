@@ -0,0 +1,110 @@
+use glfw::{self, Action, MouseButton, WindowEvent};
+
+use shader::ShaderProgram;
+
+/* Mouse input in ShaderToy's iMouse encoding, all positions in pixels from the bottom left:
+ * xy is the cursor position as of the last time the left button was held down over it, z the x
+ * of the last click, positive while the button is held and negative after, and w the y of the
+ * last click, positive only for the frame the click happened in. */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MouseState {
+    pub cursor: [f32; 2],
+    pub drag: [f32; 2],
+    pub click: [f32; 2],
+    pub down: bool,
+    /* Set by a press and cleared by end_frame */
+    pub clicked: bool,
+}
+
+impl MouseState {
+    pub fn on_move(&mut self, position: [f32; 2]) {
+        self.cursor = position;
+        if self.down {
+            self.drag = position;
+        }
+    }
+
+    pub fn on_press(&mut self) {
+        self.down = true;
+        self.clicked = true;
+        self.click = self.cursor;
+        self.drag = self.cursor;
+    }
+
+    pub fn on_release(&mut self) {
+        self.down = false;
+    }
+
+    /* Call once a frame has been drawn with the uniform, so each click only shows in w once */
+    pub fn end_frame(&mut self) {
+        self.clicked = false;
+    }
+
+    pub fn to_uniform(&self) -> [f32; 4] {
+        let sign = |positive: bool, value: f32| if positive { value.abs() } else { -value.abs() };
+        [
+            self.drag[0],
+            self.drag[1],
+            sign(self.down, self.click[0]),
+            sign(self.clicked, self.click[1]),
+        ]
+    }
+}
+
+/* The inputs ShaderToy provides to every shader. Uploading them under the same names lets shaders
+ * copied from ShaderToy run unmodified. */
+#[derive(Debug, Clone)]
+pub struct ShaderToyUniforms {
+    pub time: f32,
+    pub resolution: [f32; 2],
+    pub mouse: MouseState,
+    pub frame: i32,
+}
+
+impl ShaderToyUniforms {
+    pub fn new(width: u32, height: u32) -> ShaderToyUniforms {
+        ShaderToyUniforms {
+            time: 0.0,
+            resolution: [width as f32, height as f32],
+            mouse: MouseState::default(),
+            frame: 0,
+        }
+    }
+
+    /* Called once per rendered frame */
+    pub fn advance(&mut self, time: f32) {
+        self.time = time;
+        self.frame += 1;
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::CursorPos(x, y) => {
+                self.mouse.on_move([x as f32, self.resolution[1] - y as f32]);
+            }
+            WindowEvent::MouseButton(MouseButton::Button1, Action::Press, _) => self.mouse.on_press(),
+            WindowEvent::MouseButton(MouseButton::Button1, Action::Release, _) => {
+                self.mouse.on_release()
+            }
+            _ => {}
+        }
+    }
+
+    /* Uniforms the shader does not declare are skipped silently, as most slides only use some of
+     * them */
+    pub fn upload_to(&self, program: &ShaderProgram) {
+        if program.has_uniform("iTime") {
+            program.set_uniform_f32("iTime", self.time);
+        }
+        if program.has_uniform("iResolution") {
+            /* ShaderToy declares this as a vec3, the z component being the pixel aspect ratio */
+            program.set_uniform_vec3("iResolution", [self.resolution[0], self.resolution[1], 1.0]);
+        }
+        if program.has_uniform("iMouse") {
+            program.set_uniform_vec4("iMouse", self.mouse.to_uniform());
+        }
+        if program.has_uniform("iFrame") {
+            program.set_uniform_i32("iFrame", self.frame);
+        }
+    }
+}
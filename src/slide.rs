@@ -0,0 +1,586 @@
+extern crate gl;
+extern crate rand;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use animation::AnimationPlayer;
+use aspect::{Aspect, AspectMode};
+use backend::{GlBackend, RenderBackend};
+use channels::ChannelBinding;
+use code_slide::CodeSlide;
+use errors::{BackendError, GlError, NavigationError, ShaderError};
+use framebuffer::Framebuffer;
+use history::NavigationHistory;
+use image_slide::ImageSlide;
+use preload::Preloader;
+use quad::{PASSTHROUGH_VERTEX_SHADER, SOLID_COLOR_FRAGMENT_SHADER};
+use shader::{ShaderProgram, ShaderSource, ActivatedShader, UniformValue};
+use shadertoy::ShaderToyUniforms;
+use texture::Texture2D;
+use transition::Transition;
+#[cfg(feature = "video")]
+use video::VideoSlide;
+
+pub const THUMBNAIL_WIDTH: u32 = 320;
+pub const THUMBNAIL_HEIGHT: u32 = 180;
+/* Used by fill slides whose manifest entry doesn't set a background */
+pub const DEFAULT_BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+/* Behind the build error of a slide whose program has never built, unless
+ * set_error_slide_program gives it something else */
+pub const ERROR_BACKGROUND: [f32; 4] = [0.08, 0.06, 0.07, 1.0];
+pub const ERROR_TEXT_COLOR: [f32; 4] = [1.0, 0.3, 0.3, 1.0];
+
+pub enum SlideKind {
+    /* Everything is drawn by the slide's own program */
+    Shader,
+    /* The program is the built-in image program */
+    Image(ImageSlide),
+    /* The program is the built-in code background program, with the code drawn over it */
+    Code(Box<CodeSlide>),
+    /* The program is the built-in fill program, drawing background_color over everything */
+    Fill,
+    /* Either the built-in image program or one that samples iChannel0 */
+    #[cfg(feature = "video")]
+    Video(VideoSlide),
+}
+
+/* A slide drawn through a RenderBackend, OpenGL unless said otherwise. The slide kinds, channels
+ * and animations are only bound by GlBackend so far. */
+pub struct Slide<B: RenderBackend = GlBackend> {
+    pub title: String,
+    /* From the deck's `name`, for goto_named */
+    pub name: Option<String>,
+    pub auto_advance_after: Option<Duration>,
+    pub program: B::Shader,
+    pub uniforms: Vec<(String, UniformValue)>,
+    pub notes: String,
+    pub kind: SlideKind,
+    pub channels: ChannelBinding,
+    pub background_color: [f32; 4],
+    /* Where the slide is in the deck manifest, which differs from its index in SlideManager
+     * when slides are skipped */
+    pub manifest_index: usize,
+    pub animations: AnimationPlayer,
+    /* The size the slide was designed for, e.g. 1024x768 for a 4:3 slide. When set the slide is
+     * letterboxed into whatever it is drawn in; iResolution is the size of the letterboxed area
+     * and iViewport where it is, see with_slide_viewport. */
+    pub resolution: Option<(u32, u32)>,
+}
+
+impl<B: RenderBackend> Slide<B> {
+    /* Slide::new for a backend other than OpenGL */
+    pub fn from_program(title: &str, program: B::Shader) -> Slide<B> {
+        Slide {
+            title: title.to_string(),
+            name: None,
+            auto_advance_after: None,
+            program,
+            uniforms: Vec::new(),
+            notes: String::new(),
+            kind: SlideKind::Shader,
+            channels: ChannelBinding::new(),
+            background_color: DEFAULT_BACKGROUND,
+            manifest_index: 0,
+            animations: AnimationPlayer::default(),
+            resolution: None,
+        }
+    }
+
+    pub fn with_auto_advance_after(mut self, duration: Duration) -> Slide<B> {
+        self.auto_advance_after = Some(duration);
+        self
+    }
+
+    pub fn with_manifest_index(mut self, index: usize) -> Slide<B> {
+        self.manifest_index = index;
+        self
+    }
+
+    pub fn with_uniforms(mut self, uniforms: Vec<(String, UniformValue)>) -> Slide<B> {
+        self.uniforms = uniforms;
+        self
+    }
+
+    pub fn with_channels(mut self, channels: ChannelBinding) -> Slide<B> {
+        self.channels = channels;
+        self
+    }
+
+    pub fn with_animations(mut self, animations: AnimationPlayer) -> Slide<B> {
+        self.animations = animations;
+        self
+    }
+
+    pub fn with_name(mut self, name: &str) -> Slide<B> {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn with_notes(mut self, notes: &str) -> Slide<B> {
+        self.notes = notes.to_string();
+        self
+    }
+
+    pub fn with_resolution(mut self, width: u32, height: u32) -> Slide<B> {
+        self.resolution = Some((width, height));
+        self
+    }
+
+    /* Moves any time-based content on by `dt` of presentation time */
+    pub fn advance(&mut self, dt: Duration) {
+        self.channels.advance(dt);
+        self.animations.advance(dt);
+        if let SlideKind::Code(ref code) = self.kind {
+            code.advance(dt);
+        }
+        #[cfg(feature = "video")]
+        {
+            if let SlideKind::Video(ref mut video) = self.kind {
+                if let Err(e) = video.advance(dt) {
+                    println!("Warning: {}", e);
+                }
+            }
+        }
+    }
+
+    /* Sets a uniform now and remembers it, so it survives the slide being reactivated or its
+     * program being reloaded */
+    pub fn set_uniform_value(&mut self, name: &str, value: UniformValue) {
+        B::set_uniform(&self.program, name, &value);
+        match self.uniforms.iter_mut().find(|u| u.0 == name) {
+            Some(uniform) => uniform.1 = value,
+            None => self.uniforms.push((name.to_string(), value)),
+        }
+    }
+
+    /* Uniform values live in the program object, so these only need setting when the slide is
+     * activated or its program has been rebuilt */
+    pub fn apply_uniforms(&self) {
+        for (name, value) in &self.uniforms {
+            B::set_uniform(&self.program, name, value);
+        }
+    }
+}
+
+impl Slide {
+    pub fn new(title: &str, program: ShaderProgram) -> Slide {
+        Slide::from_program(title, program)
+    }
+
+    pub fn new_image(title: &str, image: ImageSlide) -> Result<Slide, ShaderError> {
+        let mut slide = Slide::new(title, ImageSlide::program()?);
+        slide.kind = SlideKind::Image(image);
+        Ok(slide)
+    }
+
+    pub fn new_code(title: &str, code: CodeSlide) -> Result<Slide, ShaderError> {
+        let mut slide = Slide::new(title, CodeSlide::program()?);
+        slide.kind = SlideKind::Code(Box::new(code));
+        Ok(slide)
+    }
+
+    /* For slides that declare no shader: a solid colour */
+    pub fn new_fill(title: &str, color: [f32; 4]) -> Result<Slide, ShaderError> {
+        let program = ShaderProgram::new(
+            ShaderSource::Inline(PASSTHROUGH_VERTEX_SHADER.to_string()),
+            ShaderSource::Inline(SOLID_COLOR_FRAGMENT_SHADER.to_string()),
+            None,
+            None,
+            None,
+        )?;
+        let mut slide = Slide::new(title, program);
+        slide.kind = SlideKind::Fill;
+        slide.background_color = color;
+        Ok(slide)
+    }
+
+    #[cfg(feature = "video")]
+    pub fn new_video(title: &str, program: ShaderProgram, video: VideoSlide) -> Slide {
+        let mut slide = Slide::new(title, program);
+        slide.kind = SlideKind::Video(video);
+        slide
+    }
+
+    pub fn activate<F>(&self, f: F)
+        where F: Fn(&ActivatedShader) {
+        self.channels.bind_all(&self.program);
+        self.animations.upload_to(&self.program);
+        match self.kind {
+            SlideKind::Shader => {}
+            SlideKind::Image(ref image) => image.bind(&self.program),
+            SlideKind::Code(ref code) => code.bind(&self.program),
+            SlideKind::Fill => self.program.set_uniform_vec4("u_color", self.background_color),
+            #[cfg(feature = "video")]
+            SlideKind::Video(ref video) => video.bind(&self.program),
+        }
+        self.program.activate(f);
+        if let SlideKind::Code(ref code) = self.kind {
+            code.render();
+        }
+    }
+}
+
+/* Navigation and timing work the same on every backend; activating and thumbnailing slides are
+ * OpenGL only, and other backends draw a slide with draw() */
+pub struct SlideManager<B: RenderBackend = GlBackend> {
+    slides: Vec<Slide<B>>,
+    current: usize,
+    previous: Option<usize>,
+    transition: Option<Transition>,
+    /* Time spent on the current slide, reset whenever the current slide changes */
+    elapsed: Duration,
+    /* Replaces every slide's own auto advance duration when set */
+    auto_advance: Option<Duration>,
+    /* First and last slide, inclusive, that next() cycles through */
+    loop_range: Option<(usize, usize)>,
+    history: NavigationHistory,
+    preloader: Option<Preloader>,
+    /* Drawn in place of slides whose program has never built */
+    error_program: Option<B::Shader>,
+}
+
+impl<B: RenderBackend> SlideManager<B> {
+    pub fn new(slides: Vec<Slide<B>>) -> SlideManager<B> {
+        assert!(!slides.is_empty(), "SlideManager requires at least one slide");
+        slides[0].apply_uniforms();
+        SlideManager {
+            slides,
+            current: 0,
+            previous: None,
+            transition: None,
+            elapsed: Duration::from_secs(0),
+            auto_advance: None,
+            loop_range: None,
+            history: NavigationHistory::default(),
+            preloader: None,
+            error_program: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slides.len()
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn current(&self) -> &Slide<B> {
+        &self.slides[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut Slide<B> {
+        &mut self.slides[self.current]
+    }
+
+    pub fn slides(&self) -> &[Slide<B>] {
+        &self.slides
+    }
+
+    /* Puts the slides in a random order for practising, and starts again from the first. Any loop
+     * range is dropped, as it no longer covers the same slides. */
+    pub fn shuffle<R: Rng>(&mut self, rng: &mut R) {
+        self.slides.shuffle(rng);
+        self.loop_range = None;
+        self.start_at(0);
+    }
+
+    /* Begins the presentation on `index`, as if the deck started there: no transition plays and
+     * there is no history to go back through. Returns false if there is no such slide. */
+    pub fn start_at(&mut self, index: usize) -> bool {
+        if index >= self.slides.len() {
+            return false;
+        }
+        self.history.clear();
+        self.current = index;
+        self.previous = None;
+        self.elapsed = Duration::from_secs(0);
+        self.slides[index].animations.restart();
+        self.slides[index].apply_uniforms();
+        true
+    }
+
+    /* e.g. "3/10", with the slide's number in the deck as well when that differs because slides
+     * are shuffled or skipped: "3/10 (deck 7)" */
+    pub fn position_label(&self) -> String {
+        let label = format!("{}/{}", self.current + 1, self.slides.len());
+        match self.current().manifest_index {
+            index if index == self.current => label,
+            index => format!("{} (deck {})", label, index + 1),
+        }
+    }
+
+    /* Navigation methods return whether the current slide changed */
+    pub fn next(&mut self) -> bool {
+        let index = self.following(self.current);
+        self.jump_to(index)
+    }
+
+    /* The slide next() goes to from `index`, which is past the end after the last slide */
+    fn following(&self, index: usize) -> usize {
+        match self.loop_range {
+            Some((start, end)) if index == end => start,
+            _ => index + 1,
+        }
+    }
+
+    pub fn prev(&mut self) -> bool {
+        if self.current == 0 {
+            return false;
+        }
+        let index = self.current - 1;
+        self.jump_to(index)
+    }
+
+    pub fn jump_to(&mut self, index: usize) -> bool {
+        let from = self.current;
+        let changed = self.show(index);
+        if changed {
+            self.history.visit(from);
+        }
+        changed
+    }
+
+    pub fn index_named(&self, name: &str) -> Option<usize> {
+        self.slides.iter().position(|slide| slide.name.as_ref().map_or(false, |n| n == name))
+    }
+
+    /* Like jump_to, for a slide given a `name` in the deck. It is not an error to be on it
+     * already. */
+    pub fn goto_named(&mut self, name: &str) -> Result<(), NavigationError> {
+        let index = self
+            .index_named(name)
+            .ok_or_else(|| NavigationError::UnknownName(name.to_string()))?;
+        self.jump_to(index);
+        Ok(())
+    }
+
+    /* Returns to the slide shown before this one, however it was reached */
+    pub fn back(&mut self) -> bool {
+        match self.history.back(self.current) {
+            Some(index) => self.show(index),
+            None => false,
+        }
+    }
+
+    /* Undoes a back() */
+    pub fn forward(&mut self) -> bool {
+        match self.history.forward(self.current) {
+            Some(index) => self.show(index),
+            None => false,
+        }
+    }
+
+    /* Replaces the history back() and forward() go through, e.g. to change its capacity */
+    pub fn set_history(&mut self, history: NavigationHistory) {
+        self.history = history;
+    }
+
+    /* Makes `index` the current slide without recording it in the history */
+    fn show(&mut self, index: usize) -> bool {
+        if index >= self.slides.len() || index == self.current {
+            return false;
+        }
+        if let Some(ref mut preloader) = self.preloader {
+            preloader.wait_for(index);
+        }
+        if let Some(ref transition) = self.transition {
+            transition.start();
+            self.previous = Some(self.current);
+        }
+        self.current = index;
+        self.elapsed = Duration::from_secs(0);
+        self.slides[index].animations.restart();
+        self.slides[index].apply_uniforms();
+        true
+    }
+
+    pub fn set_transition(&mut self, transition: Option<Transition>) {
+        self.transition = transition;
+        self.previous = None;
+    }
+
+    pub fn transition(&self) -> Option<&Transition> {
+        self.transition.as_ref()
+    }
+
+    /* The slide being transitioned away from, if a transition is in progress */
+    pub fn transitioning_from(&self) -> Option<usize> {
+        self.previous
+    }
+
+    /* Makes next() on slide `end` go back to slide `start`, e.g. for a kiosk. Slides before
+     * `start` lead into the loop as usual. */
+    pub fn loop_range(&mut self, start: usize, end: usize) {
+        assert!(start <= end && end < self.slides.len(), "loop range is outside the slides");
+        self.loop_range = Some((start, end));
+    }
+
+    /* Replaces the plain ERROR_BACKGROUND drawn for a broken slide. The program is drawn like a
+     * slide's, so it gets the same quad and uniforms. */
+    pub fn set_error_slide_program(&mut self, program: B::Shader) {
+        self.error_program = Some(program);
+    }
+
+    pub fn set_preloader(&mut self, preloader: Option<Preloader>) {
+        self.preloader = preloader;
+    }
+
+    pub fn set_auto_advance(&mut self, duration: Option<Duration>) {
+        self.auto_advance = duration;
+    }
+
+    /* Gives each slide with an entry in `timings`, keyed by manifest index, that auto advance
+     * duration in place of its own */
+    pub fn set_timings(&mut self, timings: &BTreeMap<usize, Duration>) {
+        for slide in &mut self.slides {
+            if let Some(&duration) = timings.get(&slide.manifest_index) {
+                slide.auto_advance_after = Some(duration);
+            }
+        }
+    }
+
+    pub fn advance(&mut self, dt: Duration) {
+        if let Some(ref transition) = self.transition {
+            transition.advance(dt);
+            if transition.is_complete() {
+                self.previous = None;
+            }
+        }
+
+        self.slides[self.current].advance(dt);
+        self.elapsed += dt;
+        if let Some(after) = self.auto_advance.or(self.current().auto_advance_after) {
+            if self.elapsed >= after {
+                self.next();
+            }
+        }
+    }
+
+    /* Draws slide `index` through `backend`, between its begin_frame and end_frame */
+    pub fn draw(&self, index: usize, backend: &mut B) -> Result<(), BackendError> {
+        backend.draw_slide(self, index)
+    }
+}
+
+impl SlideManager {
+    /* Has the preloader draw the programs of the `n` slides after the current one in the
+     * background, so they are ready by the time next() reaches them. Slides already drawn are
+     * skipped, so this is cheap enough to call every frame. */
+    pub fn preload_ahead(&mut self, n: usize) {
+        let mut index = self.current;
+        for _ in 0..n {
+            index = self.following(index);
+            if index >= self.slides.len() || index == self.current {
+                break;
+            }
+            let program = self.slides[index].program.id();
+            if let Some(ref mut preloader) = self.preloader {
+                preloader.request(index, program);
+            }
+        }
+    }
+
+    /* Only the current slide's program is ever bound: the ActivatedShader guard unbinds it as
+     * soon as `f` returns, so a navigation between frames never leaves the old program active. */
+    pub fn activate<F>(&self, f: F)
+        where F: Fn(&ActivatedShader) {
+            self.activate_slide(self.current, f);
+    }
+
+    /* A slide whose program has never built draws the error slide instead. Its build error is
+     * left for the caller to draw over it, as only the caller has a font. */
+    pub fn activate_slide<F>(&self, index: usize, f: F)
+        where F: Fn(&ActivatedShader) {
+            let slide = &self.slides[index];
+            match slide.resolution {
+                Some((width, height)) => {
+                    with_slide_viewport(&slide.program, width, height, || self.draw_slide(slide, &f))
+                }
+                None => self.draw_slide(slide, &f),
+            }
+    }
+
+    fn draw_slide<F>(&self, slide: &Slide, f: &F)
+        where F: Fn(&ActivatedShader) {
+            if slide.program.is_linked() {
+                slide.activate(f);
+                return;
+            }
+            match self.error_program {
+                Some(ref program) => program.activate(f),
+                None => unsafe {
+                    let [r, g, b, a] = ERROR_BACKGROUND;
+                    gl::ClearColor(r, g, b, a);
+                    gl::Clear(gl::COLOR_BUFFER_BIT);
+                },
+            }
+    }
+
+    /* Renders the slide at its start into a small texture. Textures are shared between
+     * contexts, unlike the framebuffer it is drawn in, so the result can be shown in any window
+     * of the share group. */
+    pub fn render_thumbnail<F>(&self, index: usize, f: F) -> Result<Texture2D, GlError>
+        where F: Fn(&ActivatedShader) {
+            let target = Framebuffer::new(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT)?;
+            ShaderToyUniforms::new(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT)
+                .upload_to(&self.slides[index].program);
+
+            target.bind();
+            unsafe {
+                gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+            }
+            self.activate_slide(index, f);
+            target.unbind();
+
+            let pixels = target.read_pixels();
+            Ok(Texture2D::from_rgba(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT, Some(&pixels)))
+    }
+}
+
+/* Runs `draw` in the largest width x height area that fits in the current viewport, with black
+ * bars around it. iResolution is set to the area's size in pixels and iViewport to the area
+ * itself. gl_FragCoord still counts from the corner of the viewport, not the area, so a shader
+ * finds its place in the slide with (gl_FragCoord.xy - iViewport.xy) / iResolution.xy. Clearing is
+ * confined to the area too. */
+fn with_slide_viewport<F>(program: &ShaderProgram, width: u32, height: u32, draw: F)
+    where F: FnOnce() {
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        let [x, y, viewport_width, viewport_height] = viewport;
+        if viewport_width <= 0 || viewport_height <= 0 {
+            return;
+        }
+        let aspect = Aspect::new(AspectMode::Fit, width as f32 / height as f32);
+        let rect = aspect.rect(viewport_width, viewport_height);
+        let rect = [x + rect[0], y + rect[1], rect[2], rect[3]];
+
+        if program.has_uniform("iResolution") {
+            program.set_uniform_vec3("iResolution", [rect[2] as f32, rect[3] as f32, 1.0]);
+        }
+        if program.has_uniform("iViewport") {
+            program.set_uniform_vec4(
+                "iViewport",
+                [rect[0] as f32, rect[1] as f32, rect[2] as f32, rect[3] as f32],
+            );
+        }
+
+        unsafe {
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::Scissor(x, y, viewport_width, viewport_height);
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::Scissor(rect[0], rect[1], rect[2], rect[3]);
+            gl::Viewport(rect[0], rect[1], rect[2], rect[3]);
+        }
+        draw();
+        unsafe {
+            gl::Viewport(x, y, viewport_width, viewport_height);
+            gl::Disable(gl::SCISSOR_TEST);
+        }
+}
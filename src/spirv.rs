@@ -0,0 +1,94 @@
+extern crate gl;
+#[cfg(feature = "spirv")]
+extern crate shaderc;
+use gl::types::*;
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use shader::{self, ShaderStage};
+
+/* Not exported by the gl crate, whose bindings stop at GL 4.5 */
+pub const SHADER_BINARY_FORMAT_SPIR_V: GLenum = 0x9551;
+
+type SpecializeShaderFn = extern "system" fn(GLuint, *const GLchar, GLuint, *const GLuint, *const GLuint);
+
+static SPECIALIZE_SHADER: AtomicUsize = AtomicUsize::new(0);
+
+/* Looks up glSpecializeShader, falling back to the ARB_gl_spirv name. Call this next to
+ * gl::load_with once the context is current. */
+pub fn load_with<F>(mut loader: F)
+    where F: FnMut(&str) -> *const c_void {
+    let mut f = loader("glSpecializeShader");
+    if f.is_null() {
+        f = loader("glSpecializeShaderARB");
+    }
+    SPECIALIZE_SHADER.store(f as usize, Ordering::SeqCst);
+}
+
+pub fn is_supported() -> bool {
+    if SPECIALIZE_SHADER.load(Ordering::SeqCst) == 0 {
+        return false;
+    }
+    unsafe {
+        let mut count = 0;
+        gl::GetIntegerv(gl::NUM_SHADER_BINARY_FORMATS, &mut count);
+        let mut formats = vec![0; count as usize];
+        if count > 0 {
+            gl::GetIntegerv(gl::SHADER_BINARY_FORMATS, formats.as_mut_ptr());
+        }
+        formats.contains(&(SHADER_BINARY_FORMAT_SPIR_V as GLint))
+    }
+}
+
+/* Loads a SPIR-V module into `shader` and specializes its `main` entry point. The caller checks
+ * the compile status. */
+pub unsafe fn specialize(shader: GLuint, binary: &[u8]) {
+    gl::ShaderBinary(
+        1,
+        &shader,
+        SHADER_BINARY_FORMAT_SPIR_V,
+        binary.as_ptr() as *const c_void,
+        binary.len() as GLsizei,
+    );
+    let f = SPECIALIZE_SHADER.load(Ordering::SeqCst);
+    assert!(f != 0, "glSpecializeShader has not been loaded");
+    let specialize_shader: SpecializeShaderFn = mem::transmute(f);
+    let entry_point = CString::new("main").unwrap();
+    specialize_shader(shader, entry_point.as_ptr(), 0, ptr::null(), ptr::null());
+}
+
+/* Cached binaries are named after a hash of the stage and its GLSL source, so editing a shader
+ * invalidates its entry */
+pub fn cache_path(cache_dir: &Path, stage: ShaderStage, source: &str) -> PathBuf {
+    cache_dir.join(format!("{:016x}.spv", shader::stage_hash(stage, source)))
+}
+
+/* Compiles GLSL to a SPIR-V module for OpenGL, for specialize() to load later. Uniforms outside
+ * blocks are given locations and bindings automatically, as GL_ARB_gl_spirv needs them explicit.
+ * Errors are shaderc's messages. */
+#[cfg(feature = "spirv")]
+pub fn compile(src: &str, stage: ShaderStage) -> Result<Vec<u32>, String> {
+    let kind = match stage {
+        ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
+        ShaderStage::Fragment => shaderc::ShaderKind::Fragment,
+        ShaderStage::Geometry => shaderc::ShaderKind::Geometry,
+        ShaderStage::TessControl => shaderc::ShaderKind::TessControl,
+        ShaderStage::TessEvaluation => shaderc::ShaderKind::TessEvaluation,
+        ShaderStage::Compute => shaderc::ShaderKind::Compute,
+    };
+    let compiler = shaderc::Compiler::new().ok_or("cannot start the shaderc compiler")?;
+    let mut options = shaderc::CompileOptions::new().ok_or("cannot create shaderc options")?;
+    options.set_target_env(shaderc::TargetEnv::OpenGL, shaderc::EnvVersion::OpenGL4_5 as u32);
+    options.set_auto_map_locations(true);
+    options.set_auto_bind_uniforms(true);
+
+    let name = format!("{}.glsl", stage);
+    compiler
+        .compile_into_spirv(src, kind, &name, "main", Some(&options))
+        .map(|artifact| artifact.as_binary().to_vec())
+        .map_err(|e| e.to_string())
+}
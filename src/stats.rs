@@ -0,0 +1,148 @@
+extern crate gl;
+use gl::types::*;
+use std::collections::VecDeque;
+use std::ffi::CStr;
+use std::time::Duration;
+
+use text::TextRenderer;
+
+/* Frames the FPS figures are taken over */
+const WINDOW: usize = 60;
+const FONT_SIZE: f32 = 16.0;
+const MARGIN: f32 = 10.0;
+const COLOR: [f32; 4] = [1.0, 1.0, 0.4, 1.0];
+
+/* From GL_NVX_gpu_memory_info and GL_ATI_meminfo, which the gl crate doesn't export */
+const GPU_MEMORY_INFO_DEDICATED_VIDMEM_NVX: GLenum = 0x9047;
+const GPU_MEMORY_INFO_CURRENT_AVAILABLE_VIDMEM_NVX: GLenum = 0x9049;
+const TEXTURE_FREE_MEMORY_ATI: GLenum = 0x87FC;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MemoryInfo {
+    /* Total and free dedicated memory, so usage can be shown */
+    Nvidia,
+    /* Only free texture memory is reported */
+    Ati,
+    Unavailable,
+}
+
+/* Frame rate over the last WINDOW frames, the GL renderer and, where the driver reports it, video
+ * memory, drawn in the top right corner when visible */
+pub struct StatsOverlay {
+    frame_times: VecDeque<Duration>,
+    renderer: String,
+    memory: MemoryInfo,
+    pub visible: bool,
+}
+
+impl StatsOverlay {
+    /* Needs a current context to ask for the renderer and extensions */
+    pub fn new() -> StatsOverlay {
+        let memory = if has_extension("GL_NVX_gpu_memory_info") {
+            MemoryInfo::Nvidia
+        } else if has_extension("GL_ATI_meminfo") {
+            MemoryInfo::Ati
+        } else {
+            MemoryInfo::Unavailable
+        };
+        StatsOverlay {
+            frame_times: VecDeque::with_capacity(WINDOW),
+            renderer: gl_string(gl::RENDERER).unwrap_or_else(|| "unknown renderer".to_string()),
+            memory,
+            visible: false,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /* Call once per frame, whether or not the overlay is visible, so it has a full window of
+     * frames as soon as it is shown */
+    pub fn record_frame(&mut self, frame_time: Duration) {
+        if self.frame_times.len() == WINDOW {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+
+    /* Mean, minimum and maximum frames per second, from the slowest and fastest frames */
+    pub fn fps(&self) -> Option<(f32, f32, f32)> {
+        let secs = |d: &Duration| d.as_secs() as f32 + d.subsec_nanos() as f32 * 1e-9;
+        let total: f32 = self.frame_times.iter().map(secs).sum();
+        let slowest = self.frame_times.iter().max().map(secs)?;
+        let fastest = self.frame_times.iter().min().map(secs)?;
+        if total <= 0.0 || fastest <= 0.0 {
+            return None;
+        }
+        Some((self.frame_times.len() as f32 / total, 1.0 / slowest, 1.0 / fastest))
+    }
+
+    pub fn render(&self, text: &TextRenderer) {
+        if !self.visible {
+            return;
+        }
+
+        let mut lines = Vec::with_capacity(3);
+        lines.push(match self.fps() {
+            Some((mean, min, max)) => {
+                format!("{:.1} fps (min {:.1}, max {:.1})", mean, min, max)
+            }
+            None => "-- fps".to_string(),
+        });
+        lines.push(self.renderer.clone());
+        if let Some(memory) = self.memory_line() {
+            lines.push(memory);
+        }
+        let lines = lines.join("\n");
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        let x = viewport[2] as f32 - MARGIN - text.measure(&lines, FONT_SIZE);
+        text.draw_string(&lines, x, MARGIN, FONT_SIZE, COLOR);
+    }
+
+    fn memory_line(&self) -> Option<String> {
+        match self.memory {
+            MemoryInfo::Nvidia => {
+                let (mut total, mut available) = (0, 0);
+                unsafe {
+                    gl::GetIntegerv(GPU_MEMORY_INFO_DEDICATED_VIDMEM_NVX, &mut total);
+                    gl::GetIntegerv(GPU_MEMORY_INFO_CURRENT_AVAILABLE_VIDMEM_NVX, &mut available);
+                }
+                Some(format!("VRAM {} / {} MB", (total - available) / 1024, total / 1024))
+            }
+            MemoryInfo::Ati => {
+                /* Free kilobytes, largest free block, and the same for auxiliary memory */
+                let mut free = [0; 4];
+                unsafe {
+                    gl::GetIntegerv(TEXTURE_FREE_MEMORY_ATI, free.as_mut_ptr());
+                }
+                Some(format!("VRAM {} MB free", free[0] / 1024))
+            }
+            MemoryInfo::Unavailable => None,
+        }
+    }
+}
+
+fn gl_string(name: GLenum) -> Option<String> {
+    unsafe {
+        let value = gl::GetString(name);
+        if value.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(value as *const _).to_string_lossy().into_owned())
+    }
+}
+
+fn has_extension(name: &str) -> bool {
+    unsafe {
+        let mut count = 0;
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+        (0..count.max(0) as GLuint).any(|i| {
+            let extension = gl::GetStringi(gl::EXTENSIONS, i);
+            !extension.is_null() && CStr::from_ptr(extension as *const _).to_bytes() == name.as_bytes()
+        })
+    }
+}
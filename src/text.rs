@@ -0,0 +1,295 @@
+extern crate fontdue;
+extern crate gl;
+
+use gl::types::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::mem;
+use std::os::raw::c_void;
+use std::path::Path;
+use std::ptr;
+
+use errors::TextError;
+use shader::{ShaderProgram, ShaderSource};
+use texture::Texture2D;
+
+const TEXT_VERTEX_SHADER: &str = "#version 330 core
+layout (location = 0) in vec4 aVertex;
+out vec2 uv;
+
+void main() {
+    uv = aVertex.zw;
+    gl_Position = vec4(aVertex.xy, 0.0, 1.0);
+}
+";
+
+const TEXT_FRAGMENT_SHADER: &str = "#version 330 core
+in vec2 uv;
+out vec4 FragColor;
+uniform sampler2D u_atlas;
+uniform vec4 u_color;
+
+void main() {
+    FragColor = vec4(u_color.rgb, u_color.a * texture(u_atlas, uv).a);
+}
+";
+
+const INITIAL_ATLAS_SIZE: u32 = 512;
+/* Empty pixels around each glyph so linear filtering doesn't bleed in its neighbours */
+const PADDING: u32 = 1;
+
+/* Where a rasterised glyph lives in the atlas, in atlas pixels with row 0 at the top */
+#[derive(Clone, Copy)]
+struct Glyph {
+    metrics: fontdue::Metrics,
+    x: u32,
+    y: u32,
+}
+
+/* Glyphs are packed left to right in rows, and the atlas doubles in height when it runs out of
+ * room. The CPU copy is kept so growing only needs a single upload. */
+struct Atlas {
+    texture: Texture2D,
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    cursor: (u32, u32),
+    row_height: u32,
+    /* Keyed by character and pixel size, as the bits of the f32 */
+    glyphs: HashMap<(char, u32), Glyph>,
+    dirty: bool,
+}
+
+impl Atlas {
+    fn new() -> Atlas {
+        let (width, height) = (INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE);
+        let pixels = vec![0; (width * height * 4) as usize];
+        Atlas {
+            texture: Texture2D::from_rgba(width, height, Some(&pixels)),
+            pixels,
+            width,
+            height,
+            cursor: (0, 0),
+            row_height: 0,
+            glyphs: HashMap::new(),
+            dirty: false,
+        }
+    }
+
+    fn glyph(&mut self, font: &fontdue::Font, c: char, size: f32) -> Glyph {
+        if let Some(glyph) = self.glyphs.get(&(c, size.to_bits())) {
+            return *glyph;
+        }
+
+        let (metrics, coverage) = font.rasterize(c, size);
+        let (w, h) = (metrics.width as u32 + PADDING, metrics.height as u32 + PADDING);
+        if self.cursor.0 + w > self.width {
+            self.cursor = (0, self.cursor.1 + self.row_height);
+            self.row_height = 0;
+        }
+        while self.cursor.1 + h > self.height {
+            self.grow();
+        }
+
+        let (x, y) = self.cursor;
+        for row in 0..metrics.height {
+            for column in 0..metrics.width {
+                let i = (((y as usize + row) * self.width as usize) + x as usize + column) * 4;
+                self.pixels[i..i + 4].copy_from_slice(&[255, 255, 255, coverage[row * metrics.width + column]]);
+            }
+        }
+        self.cursor.0 += w;
+        self.row_height = self.row_height.max(h);
+        self.dirty = true;
+
+        let glyph = Glyph { metrics, x, y };
+        self.glyphs.insert((c, size.to_bits()), glyph);
+        glyph
+    }
+
+    /* Rows are stored top first, so doubling the height just appends empty rows */
+    fn grow(&mut self) {
+        self.height *= 2;
+        self.pixels.resize((self.width * self.height * 4) as usize, 0);
+        self.dirty = true;
+    }
+
+    fn upload(&mut self) {
+        if self.dirty {
+            self.texture.upload(self.width, self.height, Some(&self.pixels));
+            self.dirty = false;
+        }
+    }
+}
+
+/* Draws text with a TrueType or OpenType font, rasterised on first use into a shared atlas */
+pub struct TextRenderer {
+    font: fontdue::Font,
+    program: ShaderProgram,
+    atlas: RefCell<Atlas>,
+    vao: GLuint,
+    vbo: GLuint,
+}
+
+impl TextRenderer {
+    pub fn from_file(path: &Path) -> Result<TextRenderer, TextError> {
+        TextRenderer::new(fs::read(path)?)
+    }
+
+    pub fn new(font_data: Vec<u8>) -> Result<TextRenderer, TextError> {
+        let font = fontdue::Font::from_bytes(font_data, fontdue::FontSettings::default())
+            .map_err(TextError::Font)?;
+        let program = ShaderProgram::new(
+            ShaderSource::Inline(TEXT_VERTEX_SHADER.to_string()),
+            ShaderSource::Inline(TEXT_FRAGMENT_SHADER.to_string()),
+            None,
+            None,
+            None,
+        )?;
+        program.set_uniform_sampler2d("u_atlas", 0);
+
+        let (mut vao, mut vbo) = (0, 0);
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::VertexAttribPointer(
+                0,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                4 * mem::size_of::<GLfloat>() as GLsizei,
+                ptr::null(),
+            );
+            gl::EnableVertexAttribArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+
+        Ok(TextRenderer {
+            font,
+            program,
+            atlas: RefCell::new(Atlas::new()),
+            vao,
+            vbo,
+        })
+    }
+
+    /* The height of one line of text at `size` pixels */
+    pub fn line_height(&self, size: f32) -> f32 {
+        self.font
+            .horizontal_line_metrics(size)
+            .map(|m| m.new_line_size)
+            .unwrap_or(size)
+    }
+
+    /* The width of the longest line of `text` at `size` pixels */
+    pub fn measure(&self, text: &str, size: f32) -> f32 {
+        text.lines()
+            .map(|line| line.chars().map(|c| self.font.metrics(c, size).advance_width).sum())
+            .fold(0.0, f32::max)
+    }
+
+    /* Draws `text` with its top left corner at (x, y), in pixels from the top left of the
+     * viewport. `size` is the font size in pixels and newlines start a new line. */
+    pub fn draw_string(&self, text: &str, x: f32, y: f32, size: f32, color: [f32; 4]) {
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        let (view_w, view_h) = (viewport[2] as f32, viewport[3] as f32);
+        if view_w <= 0.0 || view_h <= 0.0 {
+            return;
+        }
+
+        let ascent = self.font.horizontal_line_metrics(size).map(|m| m.ascent).unwrap_or(size);
+        let line_height = self.line_height(size);
+
+        let mut atlas = self.atlas.borrow_mut();
+        let mut vertices: Vec<f32> = Vec::with_capacity(text.len() * 24);
+        let mut glyphs = Vec::with_capacity(text.len());
+        let mut pen = (x, y + ascent);
+        let mut previous = None;
+        for c in text.chars() {
+            if c == '\n' {
+                pen = (x, pen.1 + line_height);
+                previous = None;
+                continue;
+            }
+            if let Some(previous) = previous {
+                pen.0 += self.font.horizontal_kern(previous, c, size).unwrap_or(0.0);
+            }
+            let glyph = atlas.glyph(&self.font, c, size);
+            glyphs.push((pen, glyph));
+            pen.0 += glyph.metrics.advance_width;
+            previous = Some(c);
+        }
+        /* Texture coordinates can only be worked out once every glyph is in, as the atlas may
+         * have grown along the way */
+        let (atlas_w, atlas_h) = (atlas.width as f32, atlas.height as f32);
+        for ((pen_x, baseline), glyph) in glyphs {
+            let m = glyph.metrics;
+            if m.width == 0 || m.height == 0 {
+                continue;
+            }
+            let left = pen_x + m.xmin as f32;
+            let top = baseline - (m.ymin + m.height as i32) as f32;
+            let (right, bottom) = (left + m.width as f32, top + m.height as f32);
+
+            let to_ndc = |px: f32, py: f32| (px / view_w * 2.0 - 1.0, 1.0 - py / view_h * 2.0);
+            let (x0, y0) = to_ndc(left, top);
+            let (x1, y1) = to_ndc(right, bottom);
+            let (u0, v0) = (glyph.x as f32 / atlas_w, glyph.y as f32 / atlas_h);
+            let (u1, v1) = (
+                (glyph.x + m.width as u32) as f32 / atlas_w,
+                (glyph.y + m.height as u32) as f32 / atlas_h,
+            );
+            vertices.extend_from_slice(&[
+                x0, y0, u0, v0, x1, y0, u1, v0, x1, y1, u1, v1,
+                x0, y0, u0, v0, x1, y1, u1, v1, x0, y1, u0, v1,
+            ]);
+        }
+        atlas.upload();
+        if vertices.is_empty() {
+            return;
+        }
+
+        self.program.set_uniform_vec4("u_color", color);
+        unsafe {
+            let blend_enabled = gl::IsEnabled(gl::BLEND) == gl::TRUE;
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(&vertices[..]) as GLsizeiptr,
+                vertices.as_ptr() as *const c_void,
+                gl::STREAM_DRAW,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+
+            atlas.texture.bind_to_unit(0);
+            self.program.activate(|_| {
+                gl::BindVertexArray(self.vao);
+                gl::DrawArrays(gl::TRIANGLES, 0, (vertices.len() / 4) as GLsizei);
+                gl::BindVertexArray(0);
+            });
+
+            if !blend_enabled {
+                gl::Disable(gl::BLEND);
+            }
+        }
+    }
+}
+
+impl Drop for TextRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
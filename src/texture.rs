@@ -0,0 +1,509 @@
+extern crate gl;
+use gl::types::*;
+use std::cell::Cell;
+use std::os::raw::c_void;
+use std::fs;
+use std::path::Path;
+use std::ptr;
+
+use image::{self, GenericImage};
+
+use errors::TextureError;
+use noise::{self, NoiseKind};
+
+#[derive(Debug)]
+pub struct Texture2D {
+    id: GLuint,
+    width: u32,
+    height: u32,
+    /* The pixel buffer upload_via_pbo streams through, made on first use */
+    pbo: GLuint,
+    /* Signalled once the GPU has finished the last upload_via_pbo */
+    upload_fence: Cell<Option<UploadFence>>,
+}
+
+/* A GL sync object, which belongs to the share group rather than a thread, so a texture uploaded
+ * on the BackgroundUploader's worker can still be polled on the main thread */
+#[derive(Debug, Clone, Copy)]
+struct UploadFence(GLsync);
+
+unsafe impl Send for UploadFence {}
+
+impl Texture2D {
+    pub fn from_file(path: &Path) -> Result<Texture2D, TextureError> {
+        /* Images are stored top row first, whereas GL expects the bottom row first */
+        let img = image::open(path)?.flipv();
+        let (width, height) = img.dimensions();
+        let data = img.to_rgba().into_raw();
+        Ok(Texture2D::from_rgba(width, height, Some(&data)))
+    }
+
+    pub fn from_rgba(width: u32, height: u32, data: Option<&[u8]>) -> Texture2D {
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+        }
+
+        let mut texture = Texture2D {
+            id,
+            width,
+            height,
+            pbo: 0,
+            upload_fence: Cell::new(None),
+        };
+        texture.upload(width, height, data);
+        texture
+    }
+
+    /* A single mid grey texel, to sample while the real texture is still loading */
+    pub fn placeholder() -> Texture2D {
+        Texture2D::from_rgba(1, 1, Some(&[128, 128, 128, 255]))
+    }
+
+    /* Tileable white noise; see generate_noise_of for the other kinds */
+    pub fn generate_noise(width: u32, height: u32, seed: u64) -> Texture2D {
+        Texture2D::generate_noise_of(NoiseKind::White, width, height, seed)
+    }
+
+    pub fn generate_noise_of(kind: NoiseKind, width: u32, height: u32, seed: u64) -> Texture2D {
+        let pixels = noise::generate(kind, width, height, seed);
+        let texture = Texture2D::from_rgba(width, height, Some(&pixels));
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, texture.id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as GLint);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        texture
+    }
+
+    pub fn upload(&mut self, width: u32, height: u32, data: Option<&[u8]>) {
+        self.width = width;
+        self.height = height;
+        let pixels = match data {
+            Some(data) => data.as_ptr() as *const c_void,
+            None => ptr::null(),
+        };
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels,
+                );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    /* As `upload`, but the pixels are copied into a pixel buffer object and the texture is filled
+     * from that by the GPU in its own time, so a large image doesn't stall the frame. Sampling the
+     * texture before poll_upload returns true waits for it. Without PBOs and fence syncs, i.e.
+     * before GL 3.2, this is a plain upload. */
+    pub fn upload_via_pbo(&mut self, data: &[u8], width: u32, height: u32) {
+        if !pbo_supported() {
+            self.upload(width, height, Some(data));
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+        unsafe {
+            if self.pbo == 0 {
+                gl::GenBuffers(1, &mut self.pbo);
+            }
+            gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, self.pbo);
+            /* A new store each time, so this never waits for the GPU to finish with the last one */
+            gl::BufferData(
+                gl::PIXEL_UNPACK_BUFFER,
+                data.len() as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+                gl::STREAM_DRAW,
+                );
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            /* With a buffer bound, the pointer is an offset into it */
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                ptr::null(),
+                );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+
+            self.delete_upload_fence();
+            let fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            self.upload_fence.set(Some(UploadFence(fence)));
+            /* Otherwise the fence may never reach the GPU for poll_upload to see it signalled */
+            gl::Flush();
+        }
+    }
+
+    /* Whether the last upload_via_pbo has finished, without waiting for it. True if there is none
+     * in flight. */
+    pub fn poll_upload(&self) -> bool {
+        let fence = match self.upload_fence.get() {
+            Some(fence) => fence,
+            None => return true,
+        };
+        let status = unsafe { gl::ClientWaitSync(fence.0, 0, 0) };
+        match status {
+            gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => {
+                self.delete_upload_fence();
+                true
+            }
+            gl::TIMEOUT_EXPIRED => false,
+            _ => {
+                /* Only if the fence is invalid, in which case there is nothing left to wait for */
+                println!("Warning: cannot check texture upload {}", self.id);
+                self.delete_upload_fence();
+                true
+            }
+        }
+    }
+
+    fn delete_upload_fence(&self) {
+        if let Some(fence) = self.upload_fence.take() {
+            unsafe {
+                gl::DeleteSync(fence.0);
+            }
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /* Sets both the minification and magnification filter, e.g. gl::NEAREST for pixel art */
+    pub fn set_filter(&self, filter: GLenum) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as GLint);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    pub fn bind_to_unit(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+        }
+    }
+}
+
+/* Six square faces of the same size, sampled by direction through a samplerCube, e.g. for an
+ * environment to reflect */
+#[derive(Debug)]
+pub struct TextureCubemap {
+    id: GLuint,
+    size: u32,
+}
+
+impl TextureCubemap {
+    /* The faces are in GL's order: +X, -X, +Y, -Y, +Z, -Z. Unlike 2D textures the images are not
+     * flipped, as cubemaps follow the RenderMan convention of the first row being the top. */
+    pub fn from_faces(paths: [&Path; 6]) -> Result<TextureCubemap, TextureError> {
+        let mut faces = Vec::with_capacity(6);
+        for path in paths.iter() {
+            let img = image::open(path)?;
+            let (width, height) = img.dimensions();
+            if width != height {
+                return Err(TextureError::Cubemap(format!(
+                    "{} is {}x{}, but faces must be square",
+                    path.display(),
+                    width,
+                    height
+                )));
+            }
+            faces.push((path, width, img.to_rgba().into_raw()));
+        }
+        let size = faces[0].1;
+        if let Some(&(path, face_size, _)) = faces.iter().find(|&&(_, face_size, _)| face_size != size) {
+            return Err(TextureError::Cubemap(format!(
+                "{} is {} pixels across, but {} is {}",
+                path.display(),
+                face_size,
+                paths[0].display(),
+                size
+            )));
+        }
+
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, id);
+            for (i, &(_, _, ref pixels)) in faces.iter().enumerate() {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as GLenum,
+                    0,
+                    gl::RGBA8 as GLint,
+                    size as GLsizei,
+                    size as GLsizei,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    pixels.as_ptr() as *const c_void,
+                    );
+            }
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+        Ok(TextureCubemap { id, size })
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    /* The width and height of each face */
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn bind_to_unit(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+        }
+    }
+}
+
+impl Drop for TextureCubemap {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+/* A colour lookup table, indexed by red, green and blue in [0, 1]. Sample it through a
+ * sampler3D with lut::APPLY_LUT, which lines the colours up with the texel centres. */
+#[derive(Debug)]
+pub struct Texture3D {
+    id: GLuint,
+    size: u32,
+}
+
+impl Texture3D {
+    /* Reads a 3D LUT in the Adobe/Resolve .cube format, as exported by most grading tools. Only
+     * the default domain of 0 to 1 is supported. */
+    pub fn from_cube_lut(path: &Path) -> Result<Texture3D, TextureError> {
+        let text = fs::read_to_string(path)?;
+        let (size, table) = parse_cube_lut(&text).map_err(|(line, reason)| TextureError::Lut {
+            path: path.to_path_buf(),
+            line,
+            reason,
+        })?;
+
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_3D, id);
+            /* .cube tables list red fastest, then green, then blue, the same as GL's x, y, z */
+            gl::TexImage3D(
+                gl::TEXTURE_3D,
+                0,
+                gl::RGB32F as GLint,
+                size as GLsizei,
+                size as GLsizei,
+                size as GLsizei,
+                0,
+                gl::RGB,
+                gl::FLOAT,
+                table.as_ptr() as *const c_void,
+                );
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::BindTexture(gl::TEXTURE_3D, 0);
+        }
+        Ok(Texture3D { id, size })
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    /* Entries along each axis */
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn bind_to_unit(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_3D, self.id);
+        }
+    }
+}
+
+impl Drop for Texture3D {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+/* The size of the table and its RGB entries, or the line, counting from 1, and what is wrong
+ * with it */
+fn parse_cube_lut(text: &str) -> Result<(u32, Vec<f32>), (usize, String)> {
+    let mut size = None;
+    let mut table = Vec::new();
+    let mut last_line = 0;
+    for (i, line) in text.lines().enumerate() {
+        last_line = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap();
+        let numbers = |words: ::std::str::SplitWhitespace| {
+            words.map(str::parse::<f32>).collect::<Result<Vec<f32>, _>>()
+                .map_err(|e| (i + 1, e.to_string()))
+        };
+        match keyword {
+            "TITLE" => {}
+            "LUT_1D_SIZE" => return Err((i + 1, "1D LUTs are not supported".to_string())),
+            "LUT_3D_SIZE" => {
+                let n = words.next().and_then(|n| n.parse::<u32>().ok()).filter(|&n| n >= 2);
+                match n {
+                    Some(n) => size = Some(n),
+                    None => return Err((i + 1, "LUT_3D_SIZE must be at least 2".to_string())),
+                }
+            }
+            "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                if numbers(words)? != [expected; 3] {
+                    return Err((i + 1, "only a domain of 0 to 1 is supported".to_string()));
+                }
+            }
+            _ if keyword.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') => {
+                if size.is_none() {
+                    return Err((i + 1, "entries must come after LUT_3D_SIZE".to_string()));
+                }
+                let entry = numbers(line.split_whitespace())?;
+                if entry.len() != 3 {
+                    return Err((i + 1, "entries must have 3 values".to_string()));
+                }
+                table.extend(entry);
+            }
+            _ => return Err((i + 1, format!("unknown keyword {}", keyword))),
+        }
+    }
+
+    let size = size.ok_or_else(|| (last_line, "no LUT_3D_SIZE".to_string()))?;
+    let expected = (size * size * size) as usize;
+    if table.len() != expected * 3 {
+        return Err((last_line, format!("{} entries, but a size of {} needs {}", table.len() / 3, size, expected)));
+    }
+    Ok((size, table))
+}
+
+impl Drop for Texture2D {
+    fn drop(&mut self) {
+        self.delete_upload_fence();
+        unsafe {
+            if self.pbo != 0 {
+                gl::DeleteBuffers(1, &self.pbo);
+            }
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+fn pbo_supported() -> bool {
+    gl::FenceSync::is_loaded() && gl::ClientWaitSync::is_loaded() && gl::DeleteSync::is_loaded()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_cube_lut;
+
+    const IDENTITY_2: &str = "TITLE \"identity\"
+# red changes fastest
+LUT_3D_SIZE 2
+DOMAIN_MIN 0 0 0
+DOMAIN_MAX 1 1 1
+0 0 0
+1 0 0
+0 1 0
+1 1 0
+0 0 1
+1 0 1
+0 1 1
+1 1 1
+";
+
+    #[test]
+    fn parses_size_and_entries() {
+        let (size, table) = parse_cube_lut(IDENTITY_2).unwrap();
+        assert_eq!(size, 2);
+        assert_eq!(table.len(), 8 * 3);
+        assert_eq!(&table[3..6], &[1.0, 0.0, 0.0]);
+        assert_eq!(&table[21..24], &[1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn rejects_entries_before_size() {
+        assert_eq!(parse_cube_lut("0 0 0\nLUT_3D_SIZE 2\n").unwrap_err().0, 1);
+    }
+
+    #[test]
+    fn rejects_missing_entries() {
+        let text = "LUT_3D_SIZE 2\n0 0 0\n1 0 0\n";
+        let (line, reason) = parse_cube_lut(text).unwrap_err();
+        assert_eq!(line, 3);
+        assert!(reason.contains("needs 8"), "{}", reason);
+    }
+
+    #[test]
+    fn rejects_entries_without_three_values() {
+        assert_eq!(parse_cube_lut("LUT_3D_SIZE 2\n0 0\n").unwrap_err().0, 2);
+    }
+
+    #[test]
+    fn rejects_1d_luts_and_other_domains() {
+        assert!(parse_cube_lut("LUT_1D_SIZE 4\n").is_err());
+        assert!(parse_cube_lut("LUT_3D_SIZE 2\nDOMAIN_MAX 2 2 2\n").is_err());
+        assert!(parse_cube_lut("LUT_3D_SIZE 1\n").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_keywords() {
+        let (line, reason) = parse_cube_lut("LUT_3D_SIZE 2\nLUT_3D_INPUT_RANGE 0 1\n").unwrap_err();
+        assert_eq!(line, 2);
+        assert!(reason.contains("LUT_3D_INPUT_RANGE"));
+    }
+}
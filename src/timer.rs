@@ -0,0 +1,154 @@
+extern crate gl;
+use gl::types::*;
+use std::time::{Duration, Instant};
+
+use errors::ShaderError;
+use font;
+use quad::FullscreenQuad;
+use shader::{ShaderProgram, ShaderSource};
+use texture::Texture2D;
+
+const OVERLAY_VERTEX_SHADER: &str = "#version 330 core
+layout (location = 0) in vec3 aPos;
+out vec2 uv;
+
+void main() {
+    uv = aPos.xy * 0.5 + 0.5;
+    gl_Position = vec4(aPos, 1.0);
+}
+";
+
+const OVERLAY_FRAGMENT_SHADER: &str = "#version 330 core
+in vec2 uv;
+out vec4 FragColor;
+uniform sampler2D u_text;
+
+void main() {
+    FragColor = texture(u_text, uv);
+}
+";
+
+/* Size of each font pixel on screen, and the gap to the corner of the window */
+const SCALE: GLint = 3;
+const MARGIN: GLint = 10;
+
+/* Tracks time since the presentation started and, optionally, on the current slide. The overlay
+ * is drawn with its own program into a corner of the viewport, so it leaves the slide programs
+ * and their uniforms alone. */
+pub struct PresentationTimer {
+    start: Instant,
+    slide_start: Instant,
+    slide: Option<usize>,
+    show_slide_time: bool,
+    pub visible: bool,
+    text: String,
+    texture: Texture2D,
+    quad: FullscreenQuad,
+}
+
+impl PresentationTimer {
+    pub fn new(show_slide_time: bool) -> PresentationTimer {
+        let now = Instant::now();
+        let texture = Texture2D::from_rgba(1, 1, None);
+        texture.set_filter(gl::NEAREST);
+        PresentationTimer {
+            start: now,
+            slide_start: now,
+            slide: None,
+            show_slide_time,
+            visible: false,
+            text: String::new(),
+            texture,
+            quad: FullscreenQuad::new(),
+        }
+    }
+
+    /* The program to pass to render_overlay */
+    pub fn overlay_program() -> Result<ShaderProgram, ShaderError> {
+        let program = ShaderProgram::new(
+            ShaderSource::Inline(OVERLAY_VERTEX_SHADER.to_string()),
+            ShaderSource::Inline(OVERLAY_FRAGMENT_SHADER.to_string()),
+            None,
+            None,
+            None,
+        )?;
+        program.set_uniform_sampler2d("u_text", 0);
+        Ok(program)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn slide_elapsed(&self) -> Duration {
+        self.slide_start.elapsed()
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /* Call once per frame with the current slide, so the per-slide time restarts when it
+     * changes. The text texture is only rebuilt when the displayed seconds change. */
+    pub fn update(&mut self, current_slide: usize) {
+        if self.slide != Some(current_slide) {
+            self.slide = Some(current_slide);
+            self.slide_start = Instant::now();
+        }
+        if !self.visible {
+            return;
+        }
+
+        let mut text = format_duration(self.elapsed());
+        if self.show_slide_time {
+            text.push_str(" / ");
+            text.push_str(&format_duration(self.slide_elapsed()));
+        }
+        if text != self.text {
+            let (width, height, pixels) =
+                font::rasterize(&text, [255, 255, 255, 255], [0, 0, 0, 160]);
+            self.texture.upload(width, height, Some(&pixels));
+            self.text = text;
+        }
+    }
+
+    pub fn render_overlay(&self, program: &ShaderProgram) {
+        if !self.visible || self.text.is_empty() {
+            return;
+        }
+
+        let width = self.texture.width() as GLint * SCALE;
+        let height = self.texture.height() as GLint * SCALE;
+        unsafe {
+            let mut viewport = [0; 4];
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            let blend_enabled = gl::IsEnabled(gl::BLEND) == gl::TRUE;
+
+            gl::Viewport(
+                viewport[0] + viewport[2] - width - MARGIN,
+                viewport[1] + viewport[3] - height - MARGIN,
+                width,
+                height,
+            );
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+            self.texture.bind_to_unit(0);
+            program.activate(|_| self.quad.draw());
+
+            if !blend_enabled {
+                gl::Disable(gl::BLEND);
+            }
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
@@ -0,0 +1,158 @@
+extern crate gl;
+use gl::types::*;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+use bytemuck::{self, Pod, Zeroable};
+
+/* How captured varyings are laid out, see ShaderProgram::enable_transform_feedback */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformFeedbackMode {
+    /* All of them one after another in a single buffer, as one T per vertex */
+    Interleaved,
+    /* Each in its own buffer, bound at the varying's index */
+    Separate,
+}
+
+impl TransformFeedbackMode {
+    pub fn gl_mode(&self) -> GLenum {
+        match *self {
+            TransformFeedbackMode::Interleaved => gl::INTERLEAVED_ATTRIBS,
+            TransformFeedbackMode::Separate => gl::SEPARATE_ATTRIBS,
+        }
+    }
+}
+
+/* Where a program's transform feedback varyings are written, one `T` per vertex, e.g. particle
+ * positions and velocities moved on by a vertex shader. The buffer can then be read back, or
+ * drawn from by binding id() as a vertex buffer, which keeps the particles on the GPU. */
+pub struct TransformFeedbackBuffer<T: Pod> {
+    id: GLuint,
+    /* Counts the primitives written by the last capture */
+    query: GLuint,
+    capacity: usize,
+    /* Vertices per primitive of the last capture */
+    vertices_per_primitive: usize,
+    _data: PhantomData<T>,
+}
+
+impl<T: Pod> TransformFeedbackBuffer<T> {
+    /* Room for `capacity` vertices; GL stops writing, rather than overflowing, once it is full */
+    pub fn new(capacity: usize) -> TransformFeedbackBuffer<T> {
+        let (mut id, mut query) = (0, 0);
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::GenQueries(1, &mut query);
+            gl::BindBuffer(gl::TRANSFORM_FEEDBACK_BUFFER, id);
+            gl::BufferData(
+                gl::TRANSFORM_FEEDBACK_BUFFER,
+                (capacity * mem::size_of::<T>()) as GLsizeiptr,
+                ptr::null(),
+                gl::DYNAMIC_COPY,
+                );
+            gl::BindBuffer(gl::TRANSFORM_FEEDBACK_BUFFER, 0);
+        }
+        TransformFeedbackBuffer {
+            id,
+            query,
+            capacity,
+            vertices_per_primitive: 1,
+            _data: PhantomData,
+        }
+    }
+
+    /* Fills the buffer, e.g. with the starting state of the particles */
+    pub fn upload(&self, data: &[T]) {
+        assert!(data.len() <= self.capacity, "{} vertices do not fit in {}", data.len(), self.capacity);
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        unsafe {
+            gl::BindBuffer(gl::TRANSFORM_FEEDBACK_BUFFER, self.id);
+            gl::BufferSubData(
+                gl::TRANSFORM_FEEDBACK_BUFFER,
+                0,
+                bytes.len() as GLsizeiptr,
+                bytes.as_ptr() as *const c_void,
+                );
+            gl::BindBuffer(gl::TRANSFORM_FEEDBACK_BUFFER, 0);
+        }
+    }
+
+    /* Records what `draw` outputs, as gl::POINTS, gl::LINES or gl::TRIANGLES to match the draw
+     * call. Rasterisation is turned off meanwhile, so nothing reaches the framebuffer. The program
+     * drawn with must have had enable_transform_feedback, and with
+     * TransformFeedbackMode::Separate the buffers for the other varyings must be bound with
+     * bind_to_index first; this one is bound at index 0. */
+    pub fn capture<F>(&mut self, primitive: GLenum, draw: F)
+        where F: FnOnce() {
+        self.vertices_per_primitive = match primitive {
+            gl::LINES => 2,
+            gl::TRIANGLES => 3,
+            _ => 1,
+        };
+        unsafe {
+            gl::Enable(gl::RASTERIZER_DISCARD);
+            gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 0, self.id);
+            gl::BeginQuery(gl::TRANSFORM_FEEDBACK_PRIMITIVES_WRITTEN, self.query);
+            gl::BeginTransformFeedback(primitive);
+        }
+        draw();
+        unsafe {
+            gl::EndTransformFeedback();
+            gl::EndQuery(gl::TRANSFORM_FEEDBACK_PRIMITIVES_WRITTEN);
+            gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, 0, 0);
+            gl::Disable(gl::RASTERIZER_DISCARD);
+        }
+    }
+
+    pub fn bind_to_index(&self, index: u32) {
+        unsafe {
+            gl::BindBufferBase(gl::TRANSFORM_FEEDBACK_BUFFER, index, self.id);
+        }
+    }
+
+    /* Primitives the last capture wrote. This waits for the GPU to finish it. */
+    pub fn primitives_written(&self) -> usize {
+        let mut written = 0;
+        unsafe {
+            gl::GetQueryObjectuiv(self.query, gl::QUERY_RESULT, &mut written);
+        }
+        written as usize
+    }
+
+    /* The vertices the last capture wrote. This waits for the GPU to finish it, so for particles
+     * that never need to come back to the CPU, draw from id() instead. */
+    pub fn read(&self) -> Vec<T> {
+        let count = (self.primitives_written() * self.vertices_per_primitive).min(self.capacity);
+        let mut data = vec![<T as Zeroable>::zeroed(); count];
+        unsafe {
+            gl::BindBuffer(gl::TRANSFORM_FEEDBACK_BUFFER, self.id);
+            gl::GetBufferSubData(
+                gl::TRANSFORM_FEEDBACK_BUFFER,
+                0,
+                (count * mem::size_of::<T>()) as GLsizeiptr,
+                data.as_mut_ptr() as *mut c_void,
+                );
+            gl::BindBuffer(gl::TRANSFORM_FEEDBACK_BUFFER, 0);
+        }
+        data
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl<T: Pod> Drop for TransformFeedbackBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteQueries(1, &self.query);
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+extern crate gl;
+use std::cell::Cell;
+use std::time::Duration;
+
+use easing::{self, EasingFn};
+use shader::{ShaderProgram, ActivatedShader};
+use texture::Texture2D;
+
+/* A transition draws the outgoing slide with the `exit` program and then blends the incoming slide
+ * over it with the `enter` program. Both receive the slide texture as `u_slide` and the
+ * transition progress in [0, 1] as `u_progress`, after `easing` has been applied to it. */
+pub struct Transition {
+    pub enter: ShaderProgram,
+    pub exit: ShaderProgram,
+    pub duration: Duration,
+    pub easing: EasingFn,
+    elapsed: Cell<Duration>,
+}
+
+impl Transition {
+    pub fn new(enter: ShaderProgram, exit: ShaderProgram, duration: Duration) -> Transition {
+        Transition {
+            enter,
+            exit,
+            duration,
+            easing: easing::linear,
+            elapsed: Cell::new(duration),
+        }
+    }
+
+    pub fn with_easing(mut self, easing: EasingFn) -> Transition {
+        self.easing = easing;
+        self
+    }
+
+    pub fn start(&self) {
+        self.elapsed.set(Duration::from_secs(0));
+    }
+
+    pub fn advance(&self, dt: Duration) {
+        self.elapsed.set(self.elapsed.get() + dt);
+    }
+
+    pub fn progress(&self) -> f32 {
+        let duration = duration_secs(self.duration);
+        if duration <= 0.0 {
+            return 1.0;
+        }
+        (self.easing)((duration_secs(self.elapsed.get()) / duration).min(1.0))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.elapsed.get() >= self.duration
+    }
+
+    pub fn render<F>(&self, from: &Texture2D, to: &Texture2D, draw: F)
+        where F: Fn(&ActivatedShader) {
+        let progress = self.progress();
+
+        from.bind_to_unit(0);
+        self.exit.activate(|s| {
+            s.set_int("u_slide", 0).ok();
+            s.set_float("u_progress", progress).ok();
+            draw(s);
+        });
+
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+
+        to.bind_to_unit(0);
+        self.enter.activate(|s| {
+            s.set_int("u_slide", 0).ok();
+            s.set_float("u_progress", progress).ok();
+            draw(s);
+        });
+
+        unsafe {
+            gl::Disable(gl::BLEND);
+        }
+    }
+}
+
+fn duration_secs(d: Duration) -> f32 {
+    d.as_secs() as f32 + d.subsec_nanos() as f32 * 1e-9
+}
@@ -0,0 +1,79 @@
+extern crate gl;
+use gl::types::*;
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+use bytemuck::{self, Pod};
+
+use errors::ShaderError;
+use shader::ShaderProgram;
+
+pub struct UniformBuffer<T: Pod> {
+    id: GLuint,
+    _data: PhantomData<T>,
+}
+
+impl<T: Pod> UniformBuffer<T> {
+    pub fn new() -> UniformBuffer<T> {
+        let mut id = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, id);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                mem::size_of::<T>() as GLsizeiptr,
+                ptr::null(),
+                gl::DYNAMIC_DRAW,
+                );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+        UniformBuffer { id, _data: PhantomData }
+    }
+
+    pub fn upload(&self, data: &T) {
+        let bytes = bytemuck::bytes_of(data);
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.id);
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                0,
+                bytes.len() as GLsizeiptr,
+                bytes.as_ptr() as *const c_void,
+                );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+
+    pub fn bind_to_program(
+        &self,
+        program: &ShaderProgram,
+        block_name: &str,
+        binding_point: u32,
+    ) -> Result<(), ShaderError> {
+        let c_name = CString::new(block_name)
+            .map_err(|_| ShaderError::Uniform(format!("Invalid uniform block name {:?}", block_name)))?;
+        unsafe {
+            let index = gl::GetUniformBlockIndex(program.id(), c_name.as_ptr());
+            if index == gl::INVALID_INDEX {
+                return Err(ShaderError::Uniform(
+                    format!("Cannot find uniform block {} in current shader", block_name),
+                ));
+            }
+
+            gl::UniformBlockBinding(program.id(), index, binding_point);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding_point, self.id);
+        }
+        Ok(())
+    }
+}
+
+impl<T: Pod> Drop for UniformBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+extern crate gl;
+
+use glfw::Key;
+use glfw::modifiers::{self, Modifiers};
+use std::path::PathBuf;
+
+use deck::{self, UniformOverrides};
+use shader::UniformValue;
+use slide::Slide;
+use text::TextRenderer;
+
+/* Uniforms the presentation itself sets every frame, so editing them would have no effect */
+const DRIVEN_UNIFORMS: &[&str] = &[
+    "iTime", "iFrame", "iResolution", "iViewport", "iMouse",
+    "iAudioRMS", "iAudioBass", "iAudioMid", "iAudioTreble",
+    "iChannelTime[0]", "iChannelLayer[0]",
+    "mixValue",
+];
+
+const STEP: f32 = 0.1;
+const FONT_SIZE: f32 = 20.0;
+const MARGIN: f32 = 20.0;
+const COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.8];
+const SELECTED_COLOR: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+
+/* Lets the user pick one of the current slide's active uniforms with Tab and Shift+Tab, choose a
+ * vector component with Left and Right, and nudge it with Up and Down (ten times as far with
+ * Shift, a tenth with Alt). Edited values are kept on the slide so they survive reloads, and S
+ * writes them to the deck's sidecar file. */
+pub struct UniformEditor {
+    pub visible: bool,
+    slide: Option<usize>,
+    selected: usize,
+    component: usize,
+    edited: UniformOverrides,
+    sidecar: PathBuf,
+}
+
+impl UniformEditor {
+    /* `edited` should be the overrides the deck was loaded with, so saving keeps them */
+    pub fn new(sidecar: PathBuf, edited: UniformOverrides) -> UniformEditor {
+        UniformEditor {
+            visible: false,
+            slide: None,
+            selected: 0,
+            component: 0,
+            edited,
+            sidecar,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /* Returns whether the key was used by the editor, and so shouldn't be treated as navigation */
+    pub fn handle_key(&mut self, key: Key, modifiers: Modifiers, index: usize, slide: &mut Slide) -> bool {
+        if !self.visible {
+            return false;
+        }
+        self.select_slide(index);
+
+        let entries = editable_uniforms(slide);
+        let count = entries.len();
+        match key {
+            Key::Escape | Key::U => self.visible = false,
+            Key::Tab if count > 0 => {
+                self.selected = if modifiers.contains(modifiers::Shift) {
+                    (self.selected + count - 1) % count
+                } else {
+                    (self.selected + 1) % count
+                };
+                self.component = 0;
+            }
+            Key::Left => self.component = self.component.saturating_sub(1),
+            Key::Right => {
+                if let Some(&(_, ref value)) = entries.get(self.selected) {
+                    self.component = (self.component + 1).min(components(value) - 1);
+                }
+            }
+            Key::Up | Key::Down => {
+                if let Some(&(ref name, value)) = entries.get(self.selected) {
+                    let mut step = if key == Key::Up { STEP } else { -STEP };
+                    if modifiers.contains(modifiers::Shift) {
+                        step *= 10.0;
+                    } else if modifiers.contains(modifiers::Alt) {
+                        step /= 10.0;
+                    }
+                    let value = nudge(value, self.component, step);
+                    slide.set_uniform_value(name, value);
+                    self.edited.entry(index).or_default().insert(name.clone(), value);
+                    println!("{} = {}", name, format_value(&value, None));
+                }
+            }
+            Key::S => match deck::save_overrides(&self.sidecar, &self.edited) {
+                Ok(()) => println!("Saved uniforms to {}", self.sidecar.display()),
+                Err(e) => println!("Warning: cannot save uniforms: {}", e),
+            },
+            /* Swallow everything else while the editor is open, so stray keys don't navigate */
+            _ => {}
+        }
+        true
+    }
+
+    pub fn render(&mut self, text: &TextRenderer, index: usize, slide: &Slide) {
+        if !self.visible {
+            return;
+        }
+        self.select_slide(index);
+
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        let entries = editable_uniforms(slide);
+        let line_height = text.line_height(FONT_SIZE);
+        let mut y = viewport[3] as f32 - MARGIN - line_height * (entries.len().max(1) as f32);
+        if entries.is_empty() {
+            text.draw_string("No editable uniforms", MARGIN, y, FONT_SIZE, COLOR);
+            return;
+        }
+        for (i, (name, value)) in entries.iter().enumerate() {
+            let (line, color) = if i == self.selected {
+                let component = self.component.min(components(value) - 1);
+                (format!("> {} = {}", name, format_value(value, Some(component))), SELECTED_COLOR)
+            } else {
+                (format!("  {} = {}", name, format_value(value, None)), COLOR)
+            };
+            text.draw_string(&line, MARGIN, y, FONT_SIZE, color);
+            y += line_height;
+        }
+    }
+
+    /* The selection only makes sense for the slide it was made on */
+    fn select_slide(&mut self, index: usize) {
+        if self.slide != Some(index) {
+            self.slide = Some(index);
+            self.selected = 0;
+            self.component = 0;
+        }
+    }
+}
+
+/* The active uniforms of the slide's program that have a UniformValue type and aren't driven by
+ * the presentation, with their current values */
+fn editable_uniforms(slide: &Slide) -> Vec<(String, UniformValue)> {
+    slide
+        .program
+        .active_uniforms()
+        .iter()
+        .filter(|info| !DRIVEN_UNIFORMS.contains(&info.name.as_str()))
+        .filter_map(|info| slide.program.uniform_value(info).map(|v| (info.name.clone(), v)))
+        .collect()
+}
+
+fn components(value: &UniformValue) -> usize {
+    match *value {
+        UniformValue::Float(_) | UniformValue::Bool(_) => 1,
+        UniformValue::Vec2(_) => 2,
+        UniformValue::Vec3(_) => 3,
+        UniformValue::Vec4(_) => 4,
+    }
+}
+
+/* Adds `step` to one component, clamping `component` to the size of the value. Bools flip. */
+fn nudge(value: UniformValue, component: usize, step: f32) -> UniformValue {
+    let c = component.min(components(&value) - 1);
+    match value {
+        UniformValue::Float(f) => UniformValue::Float(f + step),
+        UniformValue::Bool(b) => UniformValue::Bool(!b),
+        UniformValue::Vec2(mut v) => {
+            v[c] += step;
+            UniformValue::Vec2(v)
+        }
+        UniformValue::Vec3(mut v) => {
+            v[c] += step;
+            UniformValue::Vec3(v)
+        }
+        UniformValue::Vec4(mut v) => {
+            v[c] += step;
+            UniformValue::Vec4(v)
+        }
+    }
+}
+
+/* Formats the value, marking the selected component of a vector with brackets */
+fn format_value(value: &UniformValue, selected: Option<usize>) -> String {
+    let floats: &[f32] = match *value {
+        UniformValue::Bool(b) => return b.to_string(),
+        UniformValue::Float(ref f) => return format!("{:.3}", f),
+        UniformValue::Vec2(ref v) => v,
+        UniformValue::Vec3(ref v) => v,
+        UniformValue::Vec4(ref v) => v,
+    };
+    let parts: Vec<String> = floats
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            if Some(i) == selected {
+                format!("[{:.3}]", f)
+            } else {
+                format!("{:.3}", f)
+            }
+        })
+        .collect();
+    format!("({})", parts.join(", "))
+}
@@ -0,0 +1,121 @@
+extern crate gl;
+use glfw::{self, Context, Glfw, RenderContext, Window};
+use image::{DynamicImage, GenericImage};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use errors::WindowError;
+use texture::Texture2D;
+use window::{WindowBuilder, WindowEvents};
+
+/* An image to upload, or a file to decode on the worker first */
+enum Source {
+    Image(DynamicImage),
+    File(PathBuf),
+}
+
+type Job = (Source, Sender<Texture2D>);
+
+/* Uploads textures from a worker thread, so a large image doesn't hold up a frame. The worker has
+ * a context of its own in the main window's share group, and textures are shared across the
+ * group, so a texture it creates can be used by the window as soon as it arrives. */
+pub struct BackgroundUploader {
+    jobs: Option<Sender<Job>>,
+    worker: Option<JoinHandle<()>>,
+    /* The hidden window owning the worker's context, which GLFW can only destroy on this
+     * thread once the worker has finished with it */
+    window: Option<(Window, WindowEvents)>,
+}
+
+/* A texture on its way from a BackgroundUploader */
+pub struct PendingTexture {
+    result: Receiver<Texture2D>,
+}
+
+impl BackgroundUploader {
+    pub fn new(glfw: &mut Glfw, parent: &Window) -> Result<BackgroundUploader, WindowError> {
+        let (mut window, events) = WindowBuilder::new()
+            .title("Uploader")
+            .size(1, 1)
+            .visible(false)
+            .shared_with(parent)
+            .build(glfw)?;
+        let context = window.render_context();
+        let (jobs, job_queue) = channel();
+        let worker = thread::spawn(move || run_worker(context, job_queue));
+
+        Ok(BackgroundUploader {
+            jobs: Some(jobs),
+            worker: Some(worker),
+            window: Some((window, events)),
+        })
+    }
+
+    /* Flips and converts `image` and uploads it on the worker, in the order they were asked for */
+    pub fn upload_texture_async(&self, image: DynamicImage) -> PendingTexture {
+        self.queue(Source::Image(image))
+    }
+
+    /* As upload_texture_async, decoding the file on the worker too. A file that can't be
+     * decoded is reported there and never arrives. */
+    pub fn upload_file_async(&self, path: PathBuf) -> PendingTexture {
+        self.queue(Source::File(path))
+    }
+
+    fn queue(&self, source: Source) -> PendingTexture {
+        let (reply, result) = channel();
+        if let Some(ref jobs) = self.jobs {
+            /* If the worker has gone, the reply sender is dropped here and poll reports it */
+            jobs.send((source, reply)).ok();
+        }
+        PendingTexture { result }
+    }
+}
+
+impl Drop for BackgroundUploader {
+    fn drop(&mut self) {
+        /* The worker finishes what is queued, then sees the queue is closed */
+        self.jobs = None;
+        if let Some(worker) = self.worker.take() {
+            worker.join().ok();
+        }
+        self.window = None;
+    }
+}
+
+impl PendingTexture {
+    /* The texture, once, when it is ready to draw with; None until then, and for good if the
+     * uploader was dropped before getting to it */
+    pub fn poll(&mut self) -> Option<Texture2D> {
+        self.result.try_recv().ok()
+    }
+}
+
+fn run_worker(mut context: RenderContext, job_queue: Receiver<Job>) {
+    context.make_current();
+    for (source, reply) in job_queue {
+        let image = match source {
+            Source::Image(image) => image,
+            Source::File(path) => match ::image::open(&path) {
+                Ok(image) => image,
+                Err(e) => {
+                    println!("Warning: cannot load texture {}: {}", path.display(), e);
+                    continue;
+                }
+            },
+        };
+        /* Images are stored top row first, whereas GL expects the bottom row first */
+        let image = image.flipv();
+        let (width, height) = image.dimensions();
+        let pixels = image.to_rgba().into_raw();
+        let texture = Texture2D::from_rgba(width, height, Some(&pixels));
+        /* The window's context may only use the texture once the upload has finished */
+        unsafe {
+            gl::Finish();
+        }
+        /* If nobody is waiting any more, the texture is dropped and deleted here */
+        reply.send(texture).ok();
+    }
+    glfw::make_context_current(None);
+}
@@ -0,0 +1,180 @@
+extern crate ffmpeg_next as ffmpeg;
+extern crate gl;
+
+use self::ffmpeg::format::{self, Pixel};
+use self::ffmpeg::media::Type;
+use self::ffmpeg::software::scaling::{self, flag::Flags};
+use self::ffmpeg::util::frame::video::Video;
+use self::ffmpeg::Packet;
+use std::path::Path;
+use std::time::Duration;
+
+use errors::VideoError;
+use shader::ShaderProgram;
+use texture::Texture2D;
+
+/* A video decoded with FFmpeg into a texture, exposed to the slide's program as iChannel0. The
+ * playback position only moves when `advance` is called with the presentation clock, so the
+ * video holds still whenever the slide does. It loops at the end. */
+pub struct VideoSlide {
+    input: format::context::Input,
+    decoder: ffmpeg::decoder::Video,
+    scaler: scaling::Context,
+    stream_index: usize,
+    /* Seconds per timestamp unit */
+    time_base: f64,
+    position: f64,
+    /* The next decoded frame and its display time, held until the clock reaches it */
+    pending: Option<(f64, Video)>,
+    eof: bool,
+    pub texture: Texture2D,
+}
+
+impl VideoSlide {
+    pub fn from_file(path: &Path) -> Result<VideoSlide, VideoError> {
+        ffmpeg::init().map_err(decode_error)?;
+        let input = format::input(&path).map_err(decode_error)?;
+        let (stream_index, time_base, decoder) = {
+            let stream = input
+                .streams()
+                .best(Type::Video)
+                .ok_or_else(|| VideoError::NoVideoStream(path.to_path_buf()))?;
+            let decoder = stream.codec().decoder().video().map_err(decode_error)?;
+            (stream.index(), f64::from(stream.time_base()), decoder)
+        };
+        let scaler = scaling::Context::get(
+            decoder.format(),
+            decoder.width(),
+            decoder.height(),
+            Pixel::RGBA,
+            decoder.width(),
+            decoder.height(),
+            Flags::BILINEAR,
+        )
+        .map_err(decode_error)?;
+
+        let texture = Texture2D::from_rgba(decoder.width(), decoder.height(), None);
+        let mut video = VideoSlide {
+            input,
+            decoder,
+            scaler,
+            stream_index,
+            time_base,
+            position: 0.0,
+            pending: None,
+            eof: false,
+            texture,
+        };
+        /* Show the first frame straight away rather than a blank texture */
+        video.advance(Duration::from_secs(0))?;
+        Ok(video)
+    }
+
+    /* Moves playback on by `dt`, uploading the most recent frame that is due */
+    pub fn advance(&mut self, dt: Duration) -> Result<(), VideoError> {
+        self.position += dt.as_secs_f64();
+        let mut latest = None;
+        loop {
+            if self.pending.is_none() {
+                self.pending = self.decode_next().map_err(decode_error)?;
+            }
+            match self.pending.take() {
+                Some((pts, frame)) => {
+                    if pts > self.position {
+                        self.pending = Some((pts, frame));
+                        break;
+                    }
+                    latest = Some(frame);
+                }
+                None => {
+                    if latest.is_none() && self.position == 0.0 {
+                        /* An empty video, nothing to loop */
+                        break;
+                    }
+                    self.rewind().map_err(decode_error)?;
+                    if latest.is_some() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if let Some(frame) = latest {
+            self.upload(&frame);
+        }
+        Ok(())
+    }
+
+    /* Binds the frame to unit 0. Custom shaders read it as iChannel0; the built-in image program
+     * used when a video slide has no shaders of its own reads it as u_image. */
+    pub fn bind(&self, program: &ShaderProgram) {
+        self.texture.bind_to_unit(0);
+        if program.has_uniform("iChannel0") {
+            program.set_uniform_sampler2d("iChannel0", 0);
+        }
+        if program.has_uniform("u_image_size") {
+            let mut viewport = [0; 4];
+            unsafe {
+                gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            }
+            program.set_uniform_vec2(
+                "u_image_size",
+                [self.texture.width() as f32, self.texture.height() as f32],
+            );
+            program.set_uniform_vec2("u_resolution", [viewport[2] as f32, viewport[3] as f32]);
+        }
+    }
+
+    fn decode_next(&mut self) -> Result<Option<(f64, Video)>, ffmpeg::Error> {
+        let mut decoded = Video::empty();
+        loop {
+            if self.decoder.receive_frame(&mut decoded).is_ok() {
+                let pts = decoded.timestamp().unwrap_or(0) as f64 * self.time_base;
+                let mut rgba = Video::empty();
+                self.scaler.run(&decoded, &mut rgba)?;
+                return Ok(Some((pts, rgba)));
+            }
+            if self.eof {
+                return Ok(None);
+            }
+
+            let mut packet = Packet::empty();
+            match packet.read(&mut self.input) {
+                Ok(()) => {
+                    if packet.stream() == self.stream_index {
+                        self.decoder.send_packet(&packet)?;
+                    }
+                }
+                Err(ffmpeg::Error::Eof) => {
+                    self.decoder.send_eof()?;
+                    self.eof = true;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn rewind(&mut self) -> Result<(), ffmpeg::Error> {
+        self.input.seek(0, ..)?;
+        self.decoder.flush();
+        self.eof = false;
+        self.position = 0.0;
+        Ok(())
+    }
+
+    /* Copies out the rows without FFmpeg's padding, bottom first as GL expects */
+    fn upload(&mut self, frame: &Video) {
+        let (width, height) = (frame.width(), frame.height());
+        let (data, stride) = (frame.data(0), frame.stride(0));
+        let row_len = width as usize * 4;
+        let mut pixels = Vec::with_capacity(row_len * height as usize);
+        for row in (0..height as usize).rev() {
+            pixels.extend_from_slice(&data[row * stride..row * stride + row_len]);
+        }
+        self.texture.upload(width, height, Some(&pixels));
+    }
+}
+
+fn decode_error(e: ffmpeg::Error) -> VideoError {
+    VideoError::Decode(e.to_string())
+}
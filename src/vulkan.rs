@@ -0,0 +1,785 @@
+extern crate ash;
+
+use self::ash::util::read_spv;
+use self::ash::vk;
+use self::ash::{Device, Entry, Instance};
+use std::ffi::CStr;
+use std::fs;
+use std::io::Cursor;
+use std::slice;
+
+use backend::RenderBackend;
+use errors::BackendError;
+use shader::{ShaderSource, UniformValue};
+
+/* Textures a draw can sample, as iChannel0 to iChannel3 at bindings 0 to 3 */
+const CHANNELS: u32 = 4;
+/* Draws per frame; each takes one descriptor set from a pool reset by begin_frame */
+const MAX_DRAWS_PER_FRAME: u32 = 64;
+const COLOR_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+/* A RenderBackend on Vulkan, through ash. This is a first step: frames are drawn into an offscreen
+ * image and read back with pixels(), as there is no swapchain yet, and shaders have to be SPIR-V
+ * already, e.g. from glslangValidator -V. The vertex shader gets no vertex input and is drawn for
+ * three vertices, so it should make a fullscreen triangle from gl_VertexIndex. */
+pub struct VulkanBackend {
+    _entry: Entry,
+    instance: Instance,
+    device: Device,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    command_buffer: vk::CommandBuffer,
+    fence: vk::Fence,
+    render_pass: vk::RenderPass,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    pipeline_layout: vk::PipelineLayout,
+    sampler: vk::Sampler,
+    /* Bound to the channels a draw doesn't give a texture for */
+    blank: Option<VulkanTexture>,
+    target: Option<Target>,
+    recording: bool,
+}
+
+pub struct VulkanShader {
+    device: Device,
+    pipeline: vk::Pipeline,
+}
+
+pub struct VulkanTexture {
+    device: Device,
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+}
+
+/* The image frames are drawn into, and a host visible copy of it */
+struct Target {
+    width: u32,
+    height: u32,
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    framebuffer: vk::Framebuffer,
+    readback: vk::Buffer,
+    readback_memory: vk::DeviceMemory,
+}
+
+fn vk_error(what: &'static str) -> impl Fn(vk::Result) -> BackendError {
+    move |result| BackendError::Vulkan(format!("{} failed: {}", what, result))
+}
+
+impl VulkanBackend {
+    pub fn new() -> Result<VulkanBackend, BackendError> {
+        unsafe {
+            let entry = Entry::load()
+                .map_err(|e| BackendError::Vulkan(format!("cannot load the Vulkan loader: {}", e)))?;
+            let app_name = CStr::from_bytes_with_nul(b"slideshow\0").unwrap();
+            let app_info = vk::ApplicationInfo::builder()
+                .application_name(app_name)
+                .api_version(vk::make_api_version(0, 1, 0, 0));
+            let instance = entry
+                .create_instance(&vk::InstanceCreateInfo::builder().application_info(&app_info), None)
+                .map_err(vk_error("vkCreateInstance"))?;
+
+            let (physical_device, queue_family) = instance
+                .enumerate_physical_devices()
+                .map_err(vk_error("vkEnumeratePhysicalDevices"))?
+                .into_iter()
+                .find_map(|device| {
+                    instance
+                        .get_physical_device_queue_family_properties(device)
+                        .iter()
+                        .position(|family| family.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+                        .map(|family| (device, family as u32))
+                })
+                .ok_or_else(|| BackendError::Vulkan("no device with a graphics queue".to_string()))?;
+
+            let priorities = [1.0];
+            let queue_info = vk::DeviceQueueCreateInfo::builder()
+                .queue_family_index(queue_family)
+                .queue_priorities(&priorities);
+            let device = instance
+                .create_device(
+                    physical_device,
+                    &vk::DeviceCreateInfo::builder().queue_create_infos(&[queue_info.build()]),
+                    None,
+                )
+                .map_err(vk_error("vkCreateDevice"))?;
+            let queue = device.get_device_queue(queue_family, 0);
+            let memory_properties = instance.get_physical_device_memory_properties(physical_device);
+
+            let command_pool = device
+                .create_command_pool(
+                    &vk::CommandPoolCreateInfo::builder()
+                        .queue_family_index(queue_family)
+                        .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER),
+                    None,
+                )
+                .map_err(vk_error("vkCreateCommandPool"))?;
+            let command_buffer = device
+                .allocate_command_buffers(
+                    &vk::CommandBufferAllocateInfo::builder()
+                        .command_pool(command_pool)
+                        .level(vk::CommandBufferLevel::PRIMARY)
+                        .command_buffer_count(1),
+                )
+                .map_err(vk_error("vkAllocateCommandBuffers"))?[0];
+            let fence = device
+                .create_fence(&vk::FenceCreateInfo::builder(), None)
+                .map_err(vk_error("vkCreateFence"))?;
+
+            /* Left ready to be copied out once the frame is drawn */
+            let attachment = vk::AttachmentDescription::builder()
+                .format(COLOR_FORMAT)
+                .samples(vk::SampleCountFlags::TYPE_1)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .final_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+            let color_ref = vk::AttachmentReference::builder()
+                .attachment(0)
+                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+            let color_refs = [color_ref.build()];
+            let subpass = vk::SubpassDescription::builder()
+                .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                .color_attachments(&color_refs);
+            let render_pass = device
+                .create_render_pass(
+                    &vk::RenderPassCreateInfo::builder()
+                        .attachments(&[attachment.build()])
+                        .subpasses(&[subpass.build()]),
+                    None,
+                )
+                .map_err(vk_error("vkCreateRenderPass"))?;
+
+            let bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..CHANNELS)
+                .map(|binding| {
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(binding)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .descriptor_count(1)
+                        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                        .build()
+                })
+                .collect();
+            let descriptor_set_layout = device
+                .create_descriptor_set_layout(
+                    &vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings),
+                    None,
+                )
+                .map_err(vk_error("vkCreateDescriptorSetLayout"))?;
+            let pool_size = vk::DescriptorPoolSize::builder()
+                .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(CHANNELS * MAX_DRAWS_PER_FRAME);
+            let descriptor_pool = device
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo::builder()
+                        .max_sets(MAX_DRAWS_PER_FRAME)
+                        .pool_sizes(&[pool_size.build()]),
+                    None,
+                )
+                .map_err(vk_error("vkCreateDescriptorPool"))?;
+            let set_layouts = [descriptor_set_layout];
+            let pipeline_layout = device
+                .create_pipeline_layout(
+                    &vk::PipelineLayoutCreateInfo::builder().set_layouts(&set_layouts),
+                    None,
+                )
+                .map_err(vk_error("vkCreatePipelineLayout"))?;
+
+            let sampler = device
+                .create_sampler(
+                    &vk::SamplerCreateInfo::builder()
+                        .mag_filter(vk::Filter::LINEAR)
+                        .min_filter(vk::Filter::LINEAR)
+                        .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                        .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+                    None,
+                )
+                .map_err(vk_error("vkCreateSampler"))?;
+
+            let mut backend = VulkanBackend {
+                _entry: entry,
+                instance,
+                device,
+                memory_properties,
+                queue,
+                command_pool,
+                command_buffer,
+                fence,
+                render_pass,
+                descriptor_set_layout,
+                descriptor_pool,
+                pipeline_layout,
+                sampler,
+                blank: None,
+                target: None,
+                recording: false,
+            };
+            backend.blank = Some(backend.create_texture(1, 1, &[0, 0, 0, 255])?);
+            Ok(backend)
+        }
+    }
+
+    /* The last frame, as RGBA bytes with the bottom row first like glReadPixels */
+    pub fn pixels(&self) -> Result<Vec<u8>, BackendError> {
+        let target = self
+            .target
+            .as_ref()
+            .ok_or_else(|| BackendError::Vulkan("no frame has been drawn".to_string()))?;
+        let row = target.width as usize * 4;
+        let size = row * target.height as usize;
+        unsafe {
+            let mapped = self
+                .device
+                .map_memory(target.readback_memory, 0, size as u64, vk::MemoryMapFlags::empty())
+                .map_err(vk_error("vkMapMemory"))?;
+            /* Vulkan's first row is the top one */
+            let rows = slice::from_raw_parts(mapped as *const u8, size);
+            let pixels = rows.chunks(row).rev().flatten().cloned().collect();
+            self.device.unmap_memory(target.readback_memory);
+            Ok(pixels)
+        }
+    }
+
+    fn memory_type(&self, bits: u32, flags: vk::MemoryPropertyFlags) -> Result<u32, BackendError> {
+        let types = &self.memory_properties.memory_types[..self.memory_properties.memory_type_count as usize];
+        types
+            .iter()
+            .enumerate()
+            .position(|(i, memory)| bits & (1 << i) != 0 && memory.property_flags.contains(flags))
+            .map(|i| i as u32)
+            .ok_or_else(|| BackendError::Vulkan("no suitable memory type".to_string()))
+    }
+
+    unsafe fn create_buffer(&self, size: u64, usage: vk::BufferUsageFlags)
+        -> Result<(vk::Buffer, vk::DeviceMemory), BackendError> {
+        let buffer = self
+            .device
+            .create_buffer(&vk::BufferCreateInfo::builder().size(size).usage(usage), None)
+            .map_err(vk_error("vkCreateBuffer"))?;
+        let requirements = self.device.get_buffer_memory_requirements(buffer);
+        let memory_type = self.memory_type(
+            requirements.memory_type_bits,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+        let memory = self
+            .device
+            .allocate_memory(
+                &vk::MemoryAllocateInfo::builder()
+                    .allocation_size(requirements.size)
+                    .memory_type_index(memory_type),
+                None,
+            )
+            .map_err(vk_error("vkAllocateMemory"))?;
+        self.device.bind_buffer_memory(buffer, memory, 0).map_err(vk_error("vkBindBufferMemory"))?;
+        Ok((buffer, memory))
+    }
+
+    unsafe fn create_image(&self, width: u32, height: u32, usage: vk::ImageUsageFlags)
+        -> Result<(vk::Image, vk::DeviceMemory, vk::ImageView), BackendError> {
+        let image = self
+            .device
+            .create_image(
+                &vk::ImageCreateInfo::builder()
+                    .image_type(vk::ImageType::TYPE_2D)
+                    .format(COLOR_FORMAT)
+                    .extent(vk::Extent3D { width, height, depth: 1 })
+                    .mip_levels(1)
+                    .array_layers(1)
+                    .samples(vk::SampleCountFlags::TYPE_1)
+                    .tiling(vk::ImageTiling::OPTIMAL)
+                    .usage(usage)
+                    .initial_layout(vk::ImageLayout::UNDEFINED),
+                None,
+            )
+            .map_err(vk_error("vkCreateImage"))?;
+        let requirements = self.device.get_image_memory_requirements(image);
+        let memory_type =
+            self.memory_type(requirements.memory_type_bits, vk::MemoryPropertyFlags::DEVICE_LOCAL)?;
+        let memory = self
+            .device
+            .allocate_memory(
+                &vk::MemoryAllocateInfo::builder()
+                    .allocation_size(requirements.size)
+                    .memory_type_index(memory_type),
+                None,
+            )
+            .map_err(vk_error("vkAllocateMemory"))?;
+        self.device.bind_image_memory(image, memory, 0).map_err(vk_error("vkBindImageMemory"))?;
+        let view = self
+            .device
+            .create_image_view(
+                &vk::ImageViewCreateInfo::builder()
+                    .image(image)
+                    .view_type(vk::ImageViewType::TYPE_2D)
+                    .format(COLOR_FORMAT)
+                    .subresource_range(color_range()),
+                None,
+            )
+            .map_err(vk_error("vkCreateImageView"))?;
+        Ok((image, memory, view))
+    }
+
+    /* Records `record` into the command buffer and waits for it to run */
+    unsafe fn submit<F>(&self, record: F) -> Result<(), BackendError>
+        where F: FnOnce(&Device, vk::CommandBuffer) {
+        self.device
+            .begin_command_buffer(
+                self.command_buffer,
+                &vk::CommandBufferBeginInfo::builder()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            )
+            .map_err(vk_error("vkBeginCommandBuffer"))?;
+        record(&self.device, self.command_buffer);
+        self.end_and_wait()
+    }
+
+    unsafe fn end_and_wait(&self) -> Result<(), BackendError> {
+        self.device
+            .end_command_buffer(self.command_buffer)
+            .map_err(vk_error("vkEndCommandBuffer"))?;
+        let command_buffers = [self.command_buffer];
+        let submit = vk::SubmitInfo::builder().command_buffers(&command_buffers);
+        self.device
+            .queue_submit(self.queue, &[submit.build()], self.fence)
+            .map_err(vk_error("vkQueueSubmit"))?;
+        self.device
+            .wait_for_fences(&[self.fence], true, u64::MAX)
+            .map_err(vk_error("vkWaitForFences"))?;
+        self.device.reset_fences(&[self.fence]).map_err(vk_error("vkResetFences"))
+    }
+
+    /* Replaces the target if the frame size has changed */
+    unsafe fn ensure_target(&mut self, width: u32, height: u32) -> Result<(), BackendError> {
+        if self.target.as_ref().map_or(false, |t| t.width == width && t.height == height) {
+            return Ok(());
+        }
+        if let Some(target) = self.target.take() {
+            self.destroy_target(target);
+        }
+        let (image, memory, view) = self.create_image(
+            width,
+            height,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+        )?;
+        let attachments = [view];
+        let framebuffer = self
+            .device
+            .create_framebuffer(
+                &vk::FramebufferCreateInfo::builder()
+                    .render_pass(self.render_pass)
+                    .attachments(&attachments)
+                    .width(width)
+                    .height(height)
+                    .layers(1),
+                None,
+            )
+            .map_err(vk_error("vkCreateFramebuffer"))?;
+        let (readback, readback_memory) = self.create_buffer(
+            u64::from(width) * u64::from(height) * 4,
+            vk::BufferUsageFlags::TRANSFER_DST,
+        )?;
+        self.target = Some(Target {
+            width,
+            height,
+            image,
+            memory,
+            view,
+            framebuffer,
+            readback,
+            readback_memory,
+        });
+        Ok(())
+    }
+
+    unsafe fn destroy_target(&self, target: Target) {
+        self.device.destroy_framebuffer(target.framebuffer, None);
+        self.device.destroy_image_view(target.view, None);
+        self.device.destroy_image(target.image, None);
+        self.device.free_memory(target.memory, None);
+        self.device.destroy_buffer(target.readback, None);
+        self.device.free_memory(target.readback_memory, None);
+    }
+}
+
+impl RenderBackend for VulkanBackend {
+    type Shader = VulkanShader;
+    type Texture = VulkanTexture;
+
+    fn compile_shader(&mut self, vertex: &ShaderSource, fragment: &ShaderSource)
+        -> Result<VulkanShader, BackendError> {
+        unsafe {
+            let vertex = self.shader_module(vertex)?;
+            let fragment = match self.shader_module(fragment) {
+                Ok(fragment) => fragment,
+                Err(e) => {
+                    self.device.destroy_shader_module(vertex, None);
+                    return Err(e);
+                }
+            };
+            let entry = CStr::from_bytes_with_nul(b"main\0").unwrap();
+            let stages = [
+                vk::PipelineShaderStageCreateInfo::builder()
+                    .stage(vk::ShaderStageFlags::VERTEX)
+                    .module(vertex)
+                    .name(entry)
+                    .build(),
+                vk::PipelineShaderStageCreateInfo::builder()
+                    .stage(vk::ShaderStageFlags::FRAGMENT)
+                    .module(fragment)
+                    .name(entry)
+                    .build(),
+            ];
+            let vertex_input = vk::PipelineVertexInputStateCreateInfo::builder();
+            let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
+                .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+            /* Set per draw, so one pipeline does for every frame size */
+            let viewport = vk::PipelineViewportStateCreateInfo::builder()
+                .viewport_count(1)
+                .scissor_count(1);
+            let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+            let dynamic = vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+            let rasterization = vk::PipelineRasterizationStateCreateInfo::builder()
+                .polygon_mode(vk::PolygonMode::FILL)
+                .cull_mode(vk::CullModeFlags::NONE)
+                .line_width(1.0);
+            let multisample = vk::PipelineMultisampleStateCreateInfo::builder()
+                .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+            let blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
+                .color_write_mask(vk::ColorComponentFlags::RGBA);
+            let blend_attachments = [blend_attachment.build()];
+            let blend = vk::PipelineColorBlendStateCreateInfo::builder().attachments(&blend_attachments);
+
+            let info = vk::GraphicsPipelineCreateInfo::builder()
+                .stages(&stages)
+                .vertex_input_state(&vertex_input)
+                .input_assembly_state(&input_assembly)
+                .viewport_state(&viewport)
+                .rasterization_state(&rasterization)
+                .multisample_state(&multisample)
+                .color_blend_state(&blend)
+                .dynamic_state(&dynamic)
+                .layout(self.pipeline_layout)
+                .render_pass(self.render_pass)
+                .subpass(0);
+            let result = self
+                .device
+                .create_graphics_pipelines(vk::PipelineCache::null(), &[info.build()], None);
+            /* The pipeline keeps what it needs of the modules */
+            self.device.destroy_shader_module(vertex, None);
+            self.device.destroy_shader_module(fragment, None);
+            let pipeline = result.map_err(|(_, result)| vk_error("vkCreateGraphicsPipelines")(result))?[0];
+            Ok(VulkanShader { device: self.device.clone(), pipeline })
+        }
+    }
+
+    fn create_texture(&mut self, width: u32, height: u32, rgba: &[u8])
+        -> Result<VulkanTexture, BackendError> {
+        unsafe {
+            let size = u64::from(width) * u64::from(height) * 4;
+            if rgba.len() as u64 != size {
+                return Err(BackendError::Vulkan(format!(
+                    "{} bytes is not {}x{} RGBA",
+                    rgba.len(),
+                    width,
+                    height
+                )));
+            }
+            let (staging, staging_memory) =
+                self.create_buffer(size, vk::BufferUsageFlags::TRANSFER_SRC)?;
+            let mapped = self
+                .device
+                .map_memory(staging_memory, 0, size, vk::MemoryMapFlags::empty())
+                .map_err(vk_error("vkMapMemory"))?;
+            /* Flipped, as Vulkan's first row is the top one */
+            let row = width as usize * 4;
+            let destination = slice::from_raw_parts_mut(mapped as *mut u8, rgba.len());
+            for (to, from) in destination.chunks_mut(row).zip(rgba.chunks(row).rev()) {
+                to.copy_from_slice(from);
+            }
+            self.device.unmap_memory(staging_memory);
+
+            let (image, memory, view) = self.create_image(
+                width,
+                height,
+                vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+            )?;
+            let result = self.submit(|device, commands| {
+                transition(
+                    device,
+                    commands,
+                    image,
+                    (vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL),
+                    (vk::AccessFlags::empty(), vk::AccessFlags::TRANSFER_WRITE),
+                    (vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::TRANSFER),
+                );
+                let region = vk::BufferImageCopy::builder()
+                    .image_subresource(color_layers())
+                    .image_extent(vk::Extent3D { width, height, depth: 1 });
+                device.cmd_copy_buffer_to_image(
+                    commands,
+                    staging,
+                    image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[region.build()],
+                );
+                transition(
+                    device,
+                    commands,
+                    image,
+                    (vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+                    (vk::AccessFlags::TRANSFER_WRITE, vk::AccessFlags::SHADER_READ),
+                    (vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::FRAGMENT_SHADER),
+                );
+            });
+            self.device.destroy_buffer(staging, None);
+            self.device.free_memory(staging_memory, None);
+            let texture = VulkanTexture { device: self.device.clone(), image, memory, view };
+            result.map(|()| texture)
+        }
+    }
+
+    fn begin_frame(&mut self, width: u32, height: u32) -> Result<(), BackendError> {
+        unsafe {
+            self.ensure_target(width, height)?;
+            self.device
+                .reset_descriptor_pool(self.descriptor_pool, vk::DescriptorPoolResetFlags::empty())
+                .map_err(vk_error("vkResetDescriptorPool"))?;
+            self.device
+                .begin_command_buffer(
+                    self.command_buffer,
+                    &vk::CommandBufferBeginInfo::builder()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+                .map_err(vk_error("vkBeginCommandBuffer"))?;
+            let clear = [vk::ClearValue {
+                color: vk::ClearColorValue { float32: [0.0, 0.0, 0.0, 1.0] },
+            }];
+            let target = self.target.as_ref().unwrap();
+            let area = vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: vk::Extent2D { width, height },
+            };
+            self.device.cmd_begin_render_pass(
+                self.command_buffer,
+                &vk::RenderPassBeginInfo::builder()
+                    .render_pass(self.render_pass)
+                    .framebuffer(target.framebuffer)
+                    .render_area(area)
+                    .clear_values(&clear),
+                vk::SubpassContents::INLINE,
+            );
+            self.recording = true;
+        }
+        Ok(())
+    }
+
+    fn draw_fullscreen_quad(&mut self, shader: &VulkanShader, textures: &[&VulkanTexture])
+        -> Result<(), BackendError> {
+        if !self.recording {
+            return Err(BackendError::Vulkan("draw outside begin_frame and end_frame".to_string()));
+        }
+        let blank = self.blank.as_ref().expect("created in new");
+        let target = self.target.as_ref().expect("created by begin_frame");
+        unsafe {
+            let layouts = [self.descriptor_set_layout];
+            let set = self
+                .device
+                .allocate_descriptor_sets(
+                    &vk::DescriptorSetAllocateInfo::builder()
+                        .descriptor_pool(self.descriptor_pool)
+                        .set_layouts(&layouts),
+                )
+                .map_err(vk_error("vkAllocateDescriptorSets"))?[0];
+            let images: Vec<[vk::DescriptorImageInfo; 1]> = (0..CHANNELS as usize)
+                .map(|channel| {
+                    let texture = textures.get(channel).cloned().unwrap_or(blank);
+                    [vk::DescriptorImageInfo::builder()
+                        .sampler(self.sampler)
+                        .image_view(texture.view)
+                        .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                        .build()]
+                })
+                .collect();
+            let writes: Vec<vk::WriteDescriptorSet> = images
+                .iter()
+                .enumerate()
+                .map(|(binding, image)| {
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(set)
+                        .dst_binding(binding as u32)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .image_info(image)
+                        .build()
+                })
+                .collect();
+            self.device.update_descriptor_sets(&writes, &[]);
+
+            let commands = self.command_buffer;
+            self.device.cmd_bind_pipeline(commands, vk::PipelineBindPoint::GRAPHICS, shader.pipeline);
+            self.device.cmd_bind_descriptor_sets(
+                commands,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[set],
+                &[],
+            );
+            let viewport = vk::Viewport {
+                x: 0.0,
+                y: 0.0,
+                width: target.width as f32,
+                height: target.height as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            };
+            let scissor = vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: vk::Extent2D { width: target.width, height: target.height },
+            };
+            self.device.cmd_set_viewport(commands, 0, &[viewport]);
+            self.device.cmd_set_scissor(commands, 0, &[scissor]);
+            self.device.cmd_draw(commands, 3, 1, 0, 0);
+        }
+        Ok(())
+    }
+
+    /* Waits for the frame, which pixels() then returns */
+    fn end_frame(&mut self) -> Result<(), BackendError> {
+        if !self.recording {
+            return Err(BackendError::Vulkan("end_frame without begin_frame".to_string()));
+        }
+        self.recording = false;
+        let target = self.target.as_ref().expect("created by begin_frame");
+        unsafe {
+            let commands = self.command_buffer;
+            self.device.cmd_end_render_pass(commands);
+            let region = vk::BufferImageCopy::builder()
+                .image_subresource(color_layers())
+                .image_extent(vk::Extent3D { width: target.width, height: target.height, depth: 1 });
+            self.device.cmd_copy_image_to_buffer(
+                commands,
+                target.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                target.readback,
+                &[region.build()],
+            );
+            self.end_and_wait()
+        }
+    }
+
+    /* There are no uniform buffers or push constants yet, so a shader only sees its textures */
+    fn set_uniform(_shader: &VulkanShader, _name: &str, _value: &UniformValue) {}
+}
+
+impl VulkanBackend {
+    unsafe fn shader_module(&self, source: &ShaderSource) -> Result<vk::ShaderModule, BackendError> {
+        let bytes = match *source {
+            ShaderSource::File(ref path) => fs::read(path).map_err(|e| {
+                BackendError::Vulkan(format!("cannot read {}: {}", path.display(), e))
+            })?,
+            ShaderSource::Embedded(bytes) => bytes.to_vec(),
+            ShaderSource::Inline(_) => {
+                return Err(BackendError::Vulkan("inline GLSL needs compiling to SPIR-V first".to_string()))
+            }
+        };
+        let code = read_spv(&mut Cursor::new(&bytes)).map_err(|e| {
+            BackendError::Vulkan(format!("{} is not SPIR-V: {}", source, e))
+        })?;
+        self.device
+            .create_shader_module(&vk::ShaderModuleCreateInfo::builder().code(&code), None)
+            .map_err(vk_error("vkCreateShaderModule"))
+    }
+}
+
+impl Drop for VulkanBackend {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.device_wait_idle().ok();
+            self.blank = None;
+            if let Some(target) = self.target.take() {
+                self.destroy_target(target);
+            }
+            self.device.destroy_sampler(self.sampler, None);
+            self.device.destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            self.device.destroy_render_pass(self.render_pass, None);
+            self.device.destroy_fence(self.fence, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.device.destroy_device(None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+/* Shaders and textures have to be dropped before the backend that made them */
+impl Drop for VulkanShader {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.pipeline, None);
+        }
+    }
+}
+
+impl Drop for VulkanTexture {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_image_view(self.view, None);
+            self.device.destroy_image(self.image, None);
+            self.device.free_memory(self.memory, None);
+        }
+    }
+}
+
+fn color_range() -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    }
+}
+
+fn color_layers() -> vk::ImageSubresourceLayers {
+    vk::ImageSubresourceLayers {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        mip_level: 0,
+        base_array_layer: 0,
+        layer_count: 1,
+    }
+}
+
+/* Records a layout change of `image` as (old, new) layouts, accesses and stages */
+unsafe fn transition(
+    device: &Device,
+    commands: vk::CommandBuffer,
+    image: vk::Image,
+    layouts: (vk::ImageLayout, vk::ImageLayout),
+    access: (vk::AccessFlags, vk::AccessFlags),
+    stages: (vk::PipelineStageFlags, vk::PipelineStageFlags),
+) {
+    let barrier = vk::ImageMemoryBarrier::builder()
+        .old_layout(layouts.0)
+        .new_layout(layouts.1)
+        .src_access_mask(access.0)
+        .dst_access_mask(access.1)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(color_range());
+    device.cmd_pipeline_barrier(
+        commands,
+        stages.0,
+        stages.1,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[barrier.build()],
+    );
+}
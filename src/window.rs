@@ -0,0 +1,154 @@
+use glfw::{self, ClientApiHint, Glfw, OpenGlProfileHint, Window, WindowEvent, WindowHint, WindowMode};
+use std::sync::mpsc::Receiver;
+
+use errors::WindowError;
+
+pub type WindowEvents = Receiver<(f64, WindowEvent)>;
+
+/* Which API the context implements */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GlProfile {
+    /* Desktop OpenGL, core profile, at the builder's gl_version */
+    #[default]
+    Core,
+    /* OpenGL ES 3.0, as on a Raspberry Pi or in WebGL2. Shaders are rewritten to GLSL ES, see
+     * shader::use_glsl_es. */
+    Es3,
+}
+
+/* Collects the GLFW hints for a window and its context. Hints are global in GLFW, so `build`
+ * resets them first and every window gets only what its builder asked for. */
+pub struct WindowBuilder<'a> {
+    title: String,
+    width: u32,
+    height: u32,
+    /* Index into the connected monitors, or None for a window on the desktop */
+    monitor: Option<usize>,
+    gl_version: (u32, u32),
+    profile: GlProfile,
+    samples: Option<u32>,
+    visible: bool,
+    debug: bool,
+    share: Option<&'a Window>,
+}
+
+impl<'a> WindowBuilder<'a> {
+    pub fn new() -> WindowBuilder<'a> {
+        WindowBuilder {
+            title: String::new(),
+            width: 800,
+            height: 600,
+            monitor: None,
+            gl_version: (3, 3),
+            profile: GlProfile::Core,
+            samples: None,
+            visible: true,
+            debug: false,
+            share: None,
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> WindowBuilder<'a> {
+        self.title = title.to_string();
+        self
+    }
+
+    /* Ignored in fullscreen, where the window takes the monitor's current video mode */
+    pub fn size(mut self, width: u32, height: u32) -> WindowBuilder<'a> {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn fullscreen(mut self, monitor: Option<usize>) -> WindowBuilder<'a> {
+        self.monitor = monitor;
+        self
+    }
+
+    /* For GlProfile::Core; ES contexts are always 3.0 */
+    pub fn gl_version(mut self, major: u32, minor: u32) -> WindowBuilder<'a> {
+        self.gl_version = (major, minor);
+        self
+    }
+
+    /* Shared contexts have to use the same API, so windows built with shared_with take their
+     * parent's and ignore this */
+    pub fn profile(mut self, profile: GlProfile) -> WindowBuilder<'a> {
+        self.profile = profile;
+        self
+    }
+
+    /* Samples per pixel for the default framebuffer; 1 or fewer leaves multisampling off */
+    pub fn samples(mut self, samples: u32) -> WindowBuilder<'a> {
+        self.samples = if samples > 1 { Some(samples) } else { None };
+        self
+    }
+
+    pub fn visible(mut self, visible: bool) -> WindowBuilder<'a> {
+        self.visible = visible;
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> WindowBuilder<'a> {
+        self.debug = debug;
+        self
+    }
+
+    /* Puts the new context in `parent`'s share group, so textures and buffers made in one can be
+     * used in the other */
+    pub fn shared_with(mut self, parent: &'a Window) -> WindowBuilder<'a> {
+        self.share = Some(parent);
+        self
+    }
+
+    /* The window is its GL context in GLFW. Creating it does not make the context current. */
+    pub fn build(self, glfw: &mut Glfw) -> Result<(Window, WindowEvents), WindowError> {
+        glfw.default_window_hints();
+        let profile = match self.share {
+            Some(parent) if parent.get_client_api() == glfw::ffi::OPENGL_ES_API => GlProfile::Es3,
+            Some(_) => GlProfile::Core,
+            None => self.profile,
+        };
+        match profile {
+            GlProfile::Core => {
+                glfw.window_hint(WindowHint::ContextVersion(self.gl_version.0, self.gl_version.1));
+                glfw.window_hint(WindowHint::OpenGlProfile(OpenGlProfileHint::Core));
+                #[cfg(target_os = "macos")] glfw.window_hint(WindowHint::OpenGlForwardCompat(true));
+            }
+            GlProfile::Es3 => {
+                glfw.window_hint(WindowHint::ClientApi(ClientApiHint::OpenGlEs));
+                glfw.window_hint(WindowHint::ContextVersion(3, 0));
+            }
+        }
+        glfw.window_hint(WindowHint::Samples(self.samples));
+        glfw.window_hint(WindowHint::Visible(self.visible));
+        glfw.window_hint(WindowHint::OpenGlDebugContext(self.debug));
+
+        match self.monitor {
+            None => self.create(glfw, self.width, self.height, WindowMode::Windowed)
+                .ok_or(WindowError::Create),
+            Some(index) => glfw.with_connected_monitors_mut(|glfw, monitors| {
+                let monitor = monitors.get(index).ok_or(WindowError::NoMonitor(index))?;
+                let (width, height) = monitor
+                    .get_video_mode()
+                    .map_or((self.width, self.height), |mode| (mode.width, mode.height));
+                self.create(glfw, width, height, WindowMode::FullScreen(monitor))
+                    .ok_or(WindowError::Create)
+            }),
+        }
+    }
+
+    fn create(&self, glfw: &Glfw, width: u32, height: u32, mode: WindowMode)
+        -> Option<(Window, WindowEvents)> {
+        match self.share {
+            Some(parent) => parent.create_shared(width, height, &self.title, mode),
+            None => glfw.create_window(width, height, &self.title, mode),
+        }
+    }
+}
+
+impl<'a> Default for WindowBuilder<'a> {
+    fn default() -> WindowBuilder<'a> {
+        WindowBuilder::new()
+    }
+}